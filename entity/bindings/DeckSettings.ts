@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Directions } from "./Directions";
+
+export interface DeckSettings { new_per_day: number, reviews_per_day: number, directions: Directions | null, reverse: boolean, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Language } from "./Language";
+
+export interface Direction { from: Language, to: Language, }
\ No newline at end of file
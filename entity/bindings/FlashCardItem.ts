@@ -2,4 +2,4 @@
 
 export type FlashCardItem = { "title": string } | { "pronunciation": { ipa: string, audioUrl: string | null, } } | {
     "image": string
-} | { "example": string };
\ No newline at end of file
+} | { "example": string } | { "answer": { text: string, alternatives?: Array<string>, } } | { "markdown": { source: string, html?: string, } } | { "math": { source: string, block?: boolean, mathml?: string, } };
\ No newline at end of file
@@ -1,9 +1,12 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type {FlashCardItem} from "./FlashCardItem";
 import type {Language} from "./Language";
+import type {MultipleChoice} from "./MultipleChoice";
 
 export type FlashCardSection =
     "Separator"
     | FlashCardItem
     | { front: FlashCardItem, back: FlashCardItem, }
-    | Record<Language, FlashCardItem>;
\ No newline at end of file
+    | Record<Language, FlashCardItem>
+    | { type: "Cloze", content: string }
+    | { type: "MultipleChoice", content: MultipleChoice };
\ No newline at end of file
@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type Language = "pl" | "en" | "en-uk" | "en-us";
\ No newline at end of file
+export type Language = string;
\ No newline at end of file
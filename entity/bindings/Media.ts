@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface Media { uid: string, owner: number, hash: string, mime: string, size: number, width: number | null, height: number | null, created: string, }
\ No newline at end of file
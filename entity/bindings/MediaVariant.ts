@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MediaSize } from "./MediaSize";
+
+export interface MediaVariant { media: string, variant: MediaSize, hash: string, mime: string, size: number, width: number, height: number, }
\ No newline at end of file
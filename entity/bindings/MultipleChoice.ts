@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface MultipleChoice { prompt: string, options: Array<string>, correct: Array<number>, distractors: number, }
\ No newline at end of file
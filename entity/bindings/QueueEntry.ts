@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CardState } from "./CardState";
+
+export interface QueueEntry { card: string, unit: string, deck: string, state: CardState, }
\ No newline at end of file
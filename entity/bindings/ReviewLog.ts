@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CardState } from "./CardState";
+import type { Grade } from "./Grade";
+
+export interface ReviewLog { user: number, card: string, unit: string, grade: Grade, state: CardState, ease: number, interval: number, reviewed_at: string, }
\ No newline at end of file
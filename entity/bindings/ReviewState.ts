@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CardState } from "./CardState";
+
+export interface ReviewState { user: number, card: string, unit: string, state: CardState, ease: number, interval: number, reps: number, lapses: number, due: string, last_review: string, stability: number | null, difficulty: number | null, }
\ No newline at end of file
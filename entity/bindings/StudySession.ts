@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { StudyQueue } from "./StudyQueue";
+
+export interface StudySession { deck: string | null, queue: StudyQueue, done: number, created: string, updated: string, }
\ No newline at end of file
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Algorithm } from "./Algorithm";
+import type { FsrsWeights } from "./FsrsWeights";
+
+export interface UserSettings { algorithm: Algorithm, desired_retention: number, fsrs_weights: FsrsWeights | null, }
\ No newline at end of file
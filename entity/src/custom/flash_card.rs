@@ -11,6 +11,8 @@ use crate::custom::lang::Language;
 #[serde(rename_all = "camelCase", rename_all_fields = "camelCase")]
 pub enum FlashCardItem {
 	Title(String),
+	/// An IPA transcription, checked when the card is saved. X-SAMPA is
+	/// converted to IPA then.
 	Pronunciation {
 		ipa: String,
 		#[serde(skip_serializing_if = "Option::is_none")]
@@ -18,6 +20,29 @@ pub enum FlashCardItem {
 	},
 	Image(String),
 	Example(String),
+	/// Text to be typed in, along with other spellings that count as right.
+	Answer {
+		text: String,
+		#[serde(default, skip_serializing_if = "Vec::is_empty")]
+		alternatives: Vec<String>,
+	},
+	/// Formatted text. Raw HTML in it is escaped when the card is saved.
+	Markdown {
+		source: String,
+		/// The source rendered to safe HTML, for clients that ask for it.
+		#[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+		html: Option<String>,
+	},
+	/// A TeX formula, checked when the card is saved.
+	Math {
+		source: String,
+		/// Shown on a line of its own rather than inline.
+		#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+		block: bool,
+		/// The formula rendered to MathML, for clients that ask for it.
+		#[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+		mathml: Option<String>,
+	},
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
@@ -33,9 +58,80 @@ pub enum FlashCardSection {
 	},
 	// #[serde(untagged)]
 	Lang(HashMap<Language, FlashCardItem>),
+	/// Text with numbered gaps, `{{c1::answer}}` or `{{c1::answer::hint}}`.
+	/// Each number is reviewed on its own.
+	Cloze(String),
+	MultipleChoice(MultipleChoice),
+}
+
+/// A question answered by picking one or more of its options.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct MultipleChoice {
+	pub prompt: String,
+	pub options: Vec<String>,
+	/// Indices of the right options. Only the card's creator gets them; everyone
+	/// else has their choice graded by the server.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub correct: Vec<usize>,
+	/// How many wrong options to add from other cards of the deck it is studied in.
+	#[serde(default)]
+	pub distractors: u32,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult, TS)]
 #[ts(export)]
 #[serde(transparent)]
 pub struct FlashCardContent(pub Vec<FlashCardSection>);
+
+impl FlashCardItem {
+	/// URL of the image or recording the item shows, if any.
+	pub fn media_url(&self) -> Option<&String> {
+		match self {
+			Self::Image(url) => Some(url),
+			Self::Pronunciation { audio_url, .. } => audio_url.as_ref(),
+			Self::Title(_)
+			| Self::Example(_)
+			| Self::Answer { .. }
+			| Self::Markdown { .. }
+			| Self::Math { .. } => None,
+		}
+	}
+
+	pub fn media_url_mut(&mut self) -> Option<&mut String> {
+		match self {
+			Self::Image(url) => Some(url),
+			Self::Pronunciation { audio_url, .. } => audio_url.as_mut(),
+			Self::Title(_)
+			| Self::Example(_)
+			| Self::Answer { .. }
+			| Self::Markdown { .. }
+			| Self::Math { .. } => None,
+		}
+	}
+}
+
+impl FlashCardContent {
+	/// Every item of the card, in section order.
+	pub fn items(&self) -> impl Iterator<Item = &FlashCardItem> {
+		self.0.iter().flat_map(|section| match section {
+			FlashCardSection::Separator
+			| FlashCardSection::Cloze(_)
+			| FlashCardSection::MultipleChoice(_) => Vec::new(),
+			FlashCardSection::Item(item) => vec![item],
+			FlashCardSection::FrontBack { front, back } => vec![front, back],
+			FlashCardSection::Lang(items) => items.values().collect(),
+		})
+	}
+
+	pub fn items_mut(&mut self) -> impl Iterator<Item = &mut FlashCardItem> {
+		self.0.iter_mut().flat_map(|section| match section {
+			FlashCardSection::Separator
+			| FlashCardSection::Cloze(_)
+			| FlashCardSection::MultipleChoice(_) => Vec::new(),
+			FlashCardSection::Item(item) => vec![item],
+			FlashCardSection::FrontBack { front, back } => vec![front, back],
+			FlashCardSection::Lang(items) => items.values_mut().collect(),
+		})
+	}
+}
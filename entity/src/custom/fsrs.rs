@@ -0,0 +1,11 @@
+use sea_orm::FromJsonQueryResult;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Number of weights in an FSRS v4.5 parameter set.
+pub const WEIGHT_COUNT: usize = 17;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromJsonQueryResult, TS)]
+#[ts(export)]
+#[serde(transparent)]
+pub struct FsrsWeights(pub Vec<f64>);
@@ -1,15 +1,318 @@
+use std::{
+	collections::HashMap,
+	fmt::{self, Display, Formatter},
+	ops::RangeInclusive,
+	str::FromStr,
+};
+
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-#[derive(Clone, Debug, Serialize, Deserialize, Hash, Eq, PartialEq, TS)]
+/// A BCP 47 language tag, such as `de`, `pt-BR` or `zh-Hant`.
+///
+/// Tags are validated against the BCP 47 syntax and kept in canonical case,
+/// so `EN-gb` and `en-GB` are the same language. They can be up to
+/// [`MAX_LEN`] characters long. The `UK` region, used by
+/// the `en-uk` tag before full tags were supported, stands for `GB`.
+///
+/// `en-GB` and `en-US` are still written `en-uk` and `en-us`, as they were
+/// then, for the clients that match on those.
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, Eq, PartialEq, Ord, PartialOrd, TS)]
 #[ts(export)]
-#[serde(rename_all = "kebab-case")]
-pub enum Language {
-	Pl,
-	// Polish
-	En,
-	// English
-	EnUk,
-	// British English
-	EnUs, // American English
+#[serde(try_from = "String", into = "String")]
+pub struct Language(String);
+
+/// Longest tag accepted, the length RFC 5646 has every implementation
+/// support. Stored tags are given columns of this size.
+pub const MAX_LEN: usize = 35;
+
+/// Canonical tags written as they were before full tags were supported.
+const LEGACY: [(&str, &str); 2] = [("en-GB", "en-uk"), ("en-US", "en-us")];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidLanguage(pub String);
+
+impl Display for InvalidLanguage {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "\"{}\" is not a BCP 47 language tag", self.0)
+	}
+}
+
+impl std::error::Error for InvalidLanguage {}
+
+impl Language {
+	/// The tag as it is written, see [`Language`].
+	pub fn as_str(&self) -> &str {
+		LEGACY
+			.iter()
+			.find(|(tag, _)| *tag == self.0)
+			.map_or(&self.0, |(_, legacy)| legacy)
+	}
+
+	/// The primary language subtag, `en` for `en-GB`.
+	pub fn primary(&self) -> &str {
+		self.0.split('-').next().unwrap_or(&self.0)
+	}
+
+	/// Whether this is `other` or a more specific tag of it, like `en-GB` of `en`.
+	pub fn within(&self, other: &Language) -> bool {
+		self.0
+			.strip_prefix(&other.0)
+			.is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+	}
+
+	/// This tag and the ever less specific tags it falls back to, `zh-Hant-TW`,
+	/// `zh-Hant` and `zh`.
+	pub fn fallbacks(&self) -> impl Iterator<Item = Language> + '_ {
+		std::iter::successors(Some(self.0.as_str()), |tag| {
+			let mut tag = &tag[..tag.rfind('-')?];
+			// An extension without its subtags means nothing on its own.
+			while let Some(end) = tag.rfind('-').filter(|&end| tag.len() - end == 2) {
+				tag = &tag[..end];
+			}
+			Some(tag)
+		})
+		.map(|tag| Language(tag.to_string()))
+	}
+
+	/// The entry of `items` to show someone studying this language.
+	///
+	/// An exact match wins, then a less specific tag (`en` for `en-GB`), then
+	/// a more specific one of those (`en-GB` for `en`), the shortest first.
+	/// So `en-AU` falls back to `en`, then to `en-GB` or `en-US`.
+	pub fn resolve<'a, V>(&self, items: &'a HashMap<Language, V>) -> Option<(&'a Language, &'a V)> {
+		self.fallbacks().find_map(|fallback| {
+			items.get_key_value(&fallback).or_else(|| {
+				items
+					.iter()
+					.filter(|(lang, _)| lang.within(&fallback))
+					.min_by(|(a, _), (b, _)| a.0.len().cmp(&b.0.len()).then_with(|| a.cmp(b)))
+			})
+		})
+	}
+}
+
+impl FromStr for Language {
+	type Err = InvalidLanguage;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Some(s)
+			.filter(|s| s.len() <= MAX_LEN)
+			.and_then(|s| canonical(&s.to_ascii_lowercase()))
+			.map(Language)
+			.ok_or_else(|| InvalidLanguage(s.to_string()))
+	}
+}
+
+impl TryFrom<String> for Language {
+	type Error = InvalidLanguage;
+
+	fn try_from(s: String) -> Result<Self, Self::Error> {
+		s.parse()
+	}
+}
+
+impl From<Language> for String {
+	fn from(lang: Language) -> Self {
+		lang.as_str().to_string()
+	}
+}
+
+impl Display for Language {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+fn is(subtag: &str, len: RangeInclusive<usize>, class: fn(&u8) -> bool) -> bool {
+	len.contains(&subtag.len()) && subtag.as_bytes().iter().all(class)
+}
+
+fn alpha(subtag: &str, len: RangeInclusive<usize>) -> bool {
+	is(subtag, len, u8::is_ascii_alphabetic)
+}
+
+fn alphanum(subtag: &str, len: RangeInclusive<usize>) -> bool {
+	is(subtag, len, u8::is_ascii_alphanumeric)
+}
+
+/// Canonical form of a lowercase tag: language, extended language, script,
+/// region, variants, extensions and private use subtags, in that order.
+///
+/// Only two and three letter languages are accepted; longer ones are
+/// reserved and none are registered.
+fn canonical(tag: &str) -> Option<String> {
+	let mut subtags = tag.split('-').peekable();
+	let mut out = Vec::new();
+
+	let language = subtags.next().filter(|s| alpha(s, 2..=3))?;
+	out.push(language.to_string());
+	for _ in 0..3 {
+		match subtags.next_if(|s| alpha(s, 3..=3)) {
+			Some(extlang) => out.push(extlang.to_string()),
+			None => break,
+		}
+	}
+	if let Some(script) = subtags.next_if(|s| alpha(s, 4..=4)) {
+		out.push(script[..1].to_ascii_uppercase() + &script[1..]);
+	}
+	if let Some(region) = subtags.next_if(|s| alpha(s, 2..=2) || is(s, 3..=3, u8::is_ascii_digit)) {
+		out.push(match region {
+			"uk" => "GB".to_string(),
+			_ => region.to_ascii_uppercase(),
+		});
+	}
+
+	let mut variants = Vec::new();
+	while let Some(variant) = subtags
+		.next_if(|s| alphanum(s, 5..=8) || (alphanum(s, 4..=4) && s.as_bytes()[0].is_ascii_digit()))
+	{
+		if variants.contains(&variant) {
+			return None;
+		}
+		variants.push(variant);
+		out.push(variant.to_string());
+	}
+
+	let mut singletons = Vec::new();
+	while let Some(singleton) = subtags.next_if(|s| alphanum(s, 1..=1) && *s != "x") {
+		if singletons.contains(&singleton) {
+			return None;
+		}
+		singletons.push(singleton);
+		out.push(singleton.to_string());
+		let before = out.len();
+		while let Some(subtag) = subtags.next_if(|s| alphanum(s, 2..=8)) {
+			out.push(subtag.to_string());
+		}
+		if out.len() == before {
+			return None;
+		}
+	}
+
+	if let Some(x) = subtags.next_if(|s| *s == "x") {
+		out.push(x.to_string());
+		let before = out.len();
+		while let Some(subtag) = subtags.next_if(|s| alphanum(s, 1..=8)) {
+			out.push(subtag.to_string());
+		}
+		if out.len() == before {
+			return None;
+		}
+	}
+
+	subtags.next().is_none().then(|| out.join("-"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn lang(tag: &str) -> Language {
+		tag.parse().unwrap()
+	}
+
+	#[test]
+	fn canonicalizes_tags() {
+		for (tag, canonical) in [
+			("de", "de"),
+			("EN-gb", "en-GB"),
+			("en-UK", "en-GB"),
+			("zh-hant-tw", "zh-Hant-TW"),
+			("zh-yue-hk", "zh-yue-HK"),
+			("es-419", "es-419"),
+			("sl-rozaj-BISKE", "sl-rozaj-biske"),
+			("de-CH-1996", "de-CH-1996"),
+			("en-a-bbb-X-Private", "en-a-bbb-x-private"),
+			(
+				"en-a-bbbbbbbb-b-cccccccc-c-dddddddd",
+				"en-a-bbbbbbbb-b-cccccccc-c-dddddddd",
+			),
+		] {
+			assert_eq!(lang(tag).0, canonical, "{tag}");
+		}
+	}
+
+	#[test]
+	fn rejects_malformed_tags() {
+		for tag in [
+			"",
+			"e",
+			"english",
+			"x-private",
+			"en-",
+			"en--gb",
+			"en_GB",
+			"de-1996-1996",
+			"en-a",
+			"en-a-bbb-a-ccc",
+			"en-x",
+			"en-gb-us",
+			"en-a-bbbbbbbb-b-cccccccc-c-dd-dddddd",
+		] {
+			assert_eq!(
+				tag.parse::<Language>(),
+				Err(InvalidLanguage(tag.to_string())),
+				"{tag}"
+			);
+		}
+	}
+
+	#[test]
+	fn writes_legacy_tags() {
+		assert_eq!(lang("en-GB").to_string(), "en-uk");
+		assert_eq!(String::from(lang("en-us")), "en-us");
+		assert_eq!(lang("en-AU").to_string(), "en-AU");
+		assert_eq!(serde_json::to_string(&lang("en-gb")).unwrap(), "\"en-uk\"");
+		assert_eq!(
+			serde_json::from_str::<Language>("\"en-uk\"").unwrap(),
+			lang("en-GB")
+		);
+	}
+
+	#[test]
+	fn falls_back_to_less_specific_tags() {
+		let fallbacks = |tag: &str| lang(tag).fallbacks().map(|lang| lang.0).collect::<Vec<_>>();
+		assert_eq!(fallbacks("zh-Hant-TW"), ["zh-Hant-TW", "zh-Hant", "zh"]);
+		assert_eq!(
+			fallbacks("en-a-bbb-ccc"),
+			["en-a-bbb-ccc", "en-a-bbb", "en"]
+		);
+		assert_eq!(fallbacks("pl"), ["pl"]);
+		assert!(lang("en-GB").within(&lang("en")));
+		assert!(!lang("en").within(&lang("en-GB")));
+		assert!(!lang("enm").within(&lang("en")));
+	}
+
+	#[test]
+	fn resolves_the_closest_entry() {
+		let items: HashMap<Language, &str> = [
+			("en-US", "us"),
+			("en-GB", "gb"),
+			("pl", "pl"),
+			("zh-Hant", "hant"),
+		]
+		.into_iter()
+		.map(|(tag, item)| (lang(tag), item))
+		.collect();
+		let resolve = |tag: &str| lang(tag).resolve(&items).map(|(_, item)| *item);
+		assert_eq!(resolve("en-US"), Some("us"));
+		assert_eq!(resolve("en-AU"), Some("gb"));
+		assert_eq!(resolve("en"), Some("gb"));
+		assert_eq!(resolve("pl-PL"), Some("pl"));
+		assert_eq!(resolve("zh"), Some("hant"));
+		assert_eq!(resolve("zh-Hant-TW"), Some("hant"));
+		assert_eq!(resolve("enm"), None);
+		assert_eq!(resolve("de"), None);
+
+		let items: HashMap<Language, &str> = [(lang("en"), "en"), (lang("en-GB"), "gb")].into();
+		assert_eq!(
+			lang("en-AU").resolve(&items).map(|(_, item)| *item),
+			Some("en")
+		);
+		assert_eq!(
+			lang("en-GB").resolve(&items).map(|(_, item)| *item),
+			Some("gb")
+		);
+	}
 }
@@ -1,2 +1,4 @@
 pub mod flash_card;
+pub mod fsrs;
 pub mod lang;
+pub mod study;
@@ -0,0 +1,36 @@
+use sea_orm::FromJsonQueryResult;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::{custom::lang::Language, sea_orm_active_enums::CardState};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct QueueEntry {
+	pub card: Uuid,
+	/// Part of the card to review, see `review_state::Model::unit`.
+	#[serde(default)]
+	pub unit: String,
+	pub deck: Uuid,
+	pub state: CardState,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize, FromJsonQueryResult, TS)]
+#[ts(export)]
+#[serde(transparent)]
+pub struct StudyQueue(pub Vec<QueueEntry>);
+
+/// A way of studying `Lang` sections: shown the side in one language, recall
+/// the side in the other.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Direction {
+	pub from: Language,
+	pub to: Language,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize, FromJsonQueryResult, TS)]
+#[ts(export)]
+#[serde(transparent)]
+pub struct Directions(pub Vec<Direction>);
@@ -22,14 +22,22 @@ pub struct Model {
 	pub creator: u32,
 	pub kind: Kind,
 	pub share: Share,
+	#[serde(skip_deserializing)]
+	pub created: DateTimeUtc,
+	#[serde(skip_deserializing)]
+	pub updated: DateTimeUtc,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
 	#[sea_orm(has_many = "super::deck_cards::Entity")]
 	DeckCards,
+	#[sea_orm(has_many = "super::deck_settings::Entity")]
+	DeckSettings,
 	#[sea_orm(has_many = "super::followed_decks::Entity")]
 	FollowedDecks,
+	#[sea_orm(has_many = "super::study_session::Entity")]
+	StudySession,
 	#[sea_orm(
 		belongs_to = "super::user::Entity",
 		from = "Column::Creator",
@@ -52,6 +60,18 @@ impl Related<super::followed_decks::Entity> for Entity {
 	}
 }
 
+impl Related<super::deck_settings::Entity> for Entity {
+	fn to() -> RelationDef {
+		Relation::DeckSettings.def()
+	}
+}
+
+impl Related<super::study_session::Entity> for Entity {
+	fn to() -> RelationDef {
+		Relation::StudySession.def()
+	}
+}
+
 impl Related<super::flash_card::Entity> for Entity {
 	fn to() -> RelationDef {
 		super::deck_cards::Relation::FlashCard.def()
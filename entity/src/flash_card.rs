@@ -21,12 +21,22 @@ pub struct Model {
 	pub share: Share,
 	#[sea_orm(column_type = "custom(\"LONGTEXT\")")]
 	pub content: super::custom::flash_card::FlashCardContent,
+	#[serde(skip_deserializing)]
+	pub created: DateTimeUtc,
+	#[serde(skip_deserializing)]
+	pub updated: DateTimeUtc,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+	#[sea_orm(has_many = "super::card_media::Entity")]
+	CardMedia,
 	#[sea_orm(has_many = "super::deck_cards::Entity")]
 	DeckCards,
+	#[sea_orm(has_many = "super::review_log::Entity")]
+	ReviewLog,
+	#[sea_orm(has_many = "super::review_state::Entity")]
+	ReviewState,
 	#[sea_orm(
 		belongs_to = "super::user::Entity",
 		from = "Column::Creator",
@@ -37,6 +47,12 @@ pub enum Relation {
 	User,
 }
 
+impl Related<super::card_media::Entity> for Entity {
+	fn to() -> RelationDef {
+		Relation::CardMedia.def()
+	}
+}
+
 impl Related<super::deck_cards::Entity> for Entity {
 	fn to() -> RelationDef {
 		Relation::DeckCards.def()
@@ -49,6 +65,18 @@ impl Related<super::user::Entity> for Entity {
 	}
 }
 
+impl Related<super::review_log::Entity> for Entity {
+	fn to() -> RelationDef {
+		Relation::ReviewLog.def()
+	}
+}
+
+impl Related<super::review_state::Entity> for Entity {
+	fn to() -> RelationDef {
+		Relation::ReviewState.def()
+	}
+}
+
 impl Related<super::deck::Entity> for Entity {
 	fn to() -> RelationDef {
 		super::deck_cards::Relation::Deck.def()
@@ -2,10 +2,21 @@
 
 pub mod prelude;
 
+pub mod card_fork;
+pub mod card_media;
 pub mod custom;
 pub mod deck;
 pub mod deck_cards;
+pub mod deck_fork;
+pub mod deck_settings;
 pub mod flash_card;
 pub mod followed_decks;
+pub mod media;
+pub mod media_variant;
+pub mod review_log;
+pub mod review_state;
 pub mod sea_orm_active_enums;
+pub mod speech;
+pub mod study_session;
 pub mod user;
+pub mod user_settings;
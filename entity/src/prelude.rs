@@ -1,7 +1,18 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.14
 
+pub use super::card_fork::Entity as CardFork;
+pub use super::card_media::Entity as CardMedia;
 pub use super::deck::Entity as Deck;
 pub use super::deck_cards::Entity as DeckCards;
+pub use super::deck_fork::Entity as DeckFork;
+pub use super::deck_settings::Entity as DeckSettings;
 pub use super::flash_card::Entity as FlashCard;
 pub use super::followed_decks::Entity as FollowedDecks;
+pub use super::media::Entity as Media;
+pub use super::media_variant::Entity as MediaVariant;
+pub use super::review_log::Entity as ReviewLog;
+pub use super::review_state::Entity as ReviewState;
+pub use super::speech::Entity as Speech;
+pub use super::study_session::Entity as StudySession;
 pub use super::user::Entity as User;
+pub use super::user_settings::Entity as UserSettings;
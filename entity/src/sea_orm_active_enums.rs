@@ -3,6 +3,74 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
+#[derive(
+	Debug,
+	Clone,
+	PartialEq,
+	Eq,
+	EnumIter,
+	DeriveActiveEnum,
+	Copy,
+	Serialize,
+	Deserialize,
+	ts_rs :: TS,
+)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "algorithm")]
+#[ts(export)]
+pub enum Algorithm {
+	#[sea_orm(string_value = "Sm2")]
+	Sm2,
+	#[sea_orm(string_value = "Fsrs")]
+	Fsrs,
+}
+#[derive(
+	Debug,
+	Clone,
+	PartialEq,
+	Eq,
+	EnumIter,
+	DeriveActiveEnum,
+	Copy,
+	Serialize,
+	Deserialize,
+	ts_rs :: TS,
+)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "card_state")]
+#[ts(export)]
+pub enum CardState {
+	#[sea_orm(string_value = "New")]
+	New,
+	#[sea_orm(string_value = "Learning")]
+	Learning,
+	#[sea_orm(string_value = "Review")]
+	Review,
+	#[sea_orm(string_value = "Relearning")]
+	Relearning,
+}
+#[derive(
+	Debug,
+	Clone,
+	PartialEq,
+	Eq,
+	EnumIter,
+	DeriveActiveEnum,
+	Copy,
+	Serialize,
+	Deserialize,
+	ts_rs :: TS,
+)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "grade")]
+#[ts(export)]
+pub enum Grade {
+	#[sea_orm(string_value = "Again")]
+	Again,
+	#[sea_orm(string_value = "Hard")]
+	Hard,
+	#[sea_orm(string_value = "Good")]
+	Good,
+	#[sea_orm(string_value = "Easy")]
+	Easy,
+}
 #[derive(
 	Debug,
 	Clone,
@@ -35,6 +103,31 @@ pub enum Kind {
 	Deserialize,
 	ts_rs :: TS,
 )]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "media_size")]
+#[ts(export)]
+pub enum MediaSize {
+	/// Fits in 256 pixels.
+	#[sea_orm(string_value = "Thumb")]
+	Thumb,
+	/// Fits in 1024 pixels.
+	#[sea_orm(string_value = "Card")]
+	Card,
+	/// The full size.
+	#[sea_orm(string_value = "Full")]
+	Full,
+}
+#[derive(
+	Debug,
+	Clone,
+	PartialEq,
+	Eq,
+	EnumIter,
+	DeriveActiveEnum,
+	Copy,
+	Serialize,
+	Deserialize,
+	ts_rs :: TS,
+)]
 #[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "share")]
 #[ts(export)]
 pub enum Share {
@@ -21,10 +21,22 @@ pub struct Model {
 pub enum Relation {
 	#[sea_orm(has_many = "super::deck::Entity")]
 	Deck,
+	#[sea_orm(has_many = "super::deck_settings::Entity")]
+	DeckSettings,
 	#[sea_orm(has_many = "super::flash_card::Entity")]
 	FlashCard,
 	#[sea_orm(has_many = "super::followed_decks::Entity")]
 	FollowedDecks,
+	#[sea_orm(has_many = "super::media::Entity")]
+	Media,
+	#[sea_orm(has_many = "super::review_log::Entity")]
+	ReviewLog,
+	#[sea_orm(has_many = "super::review_state::Entity")]
+	ReviewState,
+	#[sea_orm(has_many = "super::study_session::Entity")]
+	StudySession,
+	#[sea_orm(has_one = "super::user_settings::Entity")]
+	UserSettings,
 }
 
 impl Related<super::flash_card::Entity> for Entity {
@@ -39,6 +51,42 @@ impl Related<super::followed_decks::Entity> for Entity {
 	}
 }
 
+impl Related<super::media::Entity> for Entity {
+	fn to() -> RelationDef {
+		Relation::Media.def()
+	}
+}
+
+impl Related<super::review_log::Entity> for Entity {
+	fn to() -> RelationDef {
+		Relation::ReviewLog.def()
+	}
+}
+
+impl Related<super::review_state::Entity> for Entity {
+	fn to() -> RelationDef {
+		Relation::ReviewState.def()
+	}
+}
+
+impl Related<super::deck_settings::Entity> for Entity {
+	fn to() -> RelationDef {
+		Relation::DeckSettings.def()
+	}
+}
+
+impl Related<super::study_session::Entity> for Entity {
+	fn to() -> RelationDef {
+		Relation::StudySession.def()
+	}
+}
+
+impl Related<super::user_settings::Entity> for Entity {
+	fn to() -> RelationDef {
+		Relation::UserSettings.def()
+	}
+}
+
 impl Related<super::deck::Entity> for Entity {
 	fn to() -> RelationDef {
 		super::followed_decks::Relation::Deck.def()
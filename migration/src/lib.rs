@@ -1,12 +1,36 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_init;
+mod m20240315_000001_review;
+mod m20240322_000001_fsrs;
+mod m20240329_000001_study;
+mod m20240405_000001_fork;
+mod m20240412_000001_timestamps;
+mod m20240419_000001_review_unit;
+mod m20240426_000001_media;
+mod m20240503_000001_media_variant;
+mod m20240510_000001_language_tags;
+mod m20240517_000001_speech;
+mod m20240524_000001_study_directions;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
 	fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-		vec![Box::new(m20220101_000001_init::Migration)]
+		vec![
+			Box::new(m20220101_000001_init::Migration),
+			Box::new(m20240315_000001_review::Migration),
+			Box::new(m20240322_000001_fsrs::Migration),
+			Box::new(m20240329_000001_study::Migration),
+			Box::new(m20240405_000001_fork::Migration),
+			Box::new(m20240412_000001_timestamps::Migration),
+			Box::new(m20240419_000001_review_unit::Migration),
+			Box::new(m20240426_000001_media::Migration),
+			Box::new(m20240503_000001_media_variant::Migration),
+			Box::new(m20240510_000001_language_tags::Migration),
+			Box::new(m20240517_000001_speech::Migration),
+			Box::new(m20240524_000001_study_directions::Migration),
+		]
 	}
 }
@@ -0,0 +1,196 @@
+use crate::sea_orm::{DbBackend, EnumIter, Iterable};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		let sqlite = matches!(manager.get_database_backend(), DbBackend::Sqlite);
+
+		manager
+			.create_table(
+				Table::create()
+					.table(ReviewState::Table)
+					.if_not_exists()
+					.col(
+						ColumnDef::new(ReviewState::Id)
+							.integer()
+							.unsigned()
+							.not_null()
+							.auto_increment()
+							.primary_key(),
+					)
+					.col(ColumnDef::new(ReviewState::User).unsigned().not_null())
+					.col(ColumnDef::new(ReviewState::Card).uuid().not_null())
+					.col(
+						ColumnDef::new(ReviewState::State)
+							.enumeration(Alias::new("card_state"), CardState::iter())
+							.not_null(),
+					)
+					.col(ColumnDef::new(ReviewState::Ease).double().not_null())
+					.col(ColumnDef::new(ReviewState::Interval).unsigned().not_null())
+					.col(ColumnDef::new(ReviewState::Reps).unsigned().not_null())
+					.col(ColumnDef::new(ReviewState::Lapses).unsigned().not_null())
+					.col(ColumnDef::new(ReviewState::Due).date_time().not_null())
+					.col(
+						ColumnDef::new(ReviewState::LastReview)
+							.date_time()
+							.not_null(),
+					)
+					.foreign_key(
+						ForeignKey::create()
+							.from(ReviewState::Table, ReviewState::User)
+							.to(User::Table, User::Id),
+					)
+					.foreign_key(
+						ForeignKey::create()
+							.from(ReviewState::Table, ReviewState::Card)
+							.to(FlashCard::Table, FlashCard::Uid)
+							.on_delete(ForeignKeyAction::Cascade),
+					)
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.name("review_state-user-card")
+					.table(ReviewState::Table)
+					.col(ReviewState::User)
+					.col(ReviewState::Card)
+					.unique()
+					.to_owned(),
+			)
+			.await?;
+
+		let mut table = Table::create();
+		table
+			.table(ReviewLog::Table)
+			.if_not_exists()
+			.col(
+				ColumnDef::new(ReviewLog::Id)
+					.integer()
+					.unsigned()
+					.not_null()
+					.auto_increment()
+					.primary_key(),
+			)
+			.col(ColumnDef::new(ReviewLog::User).unsigned().not_null())
+			.col(ColumnDef::new(ReviewLog::Card).uuid().not_null())
+			.col(
+				ColumnDef::new(ReviewLog::Grade)
+					.enumeration(Alias::new("grade"), Grade::iter())
+					.not_null(),
+			)
+			.col(
+				ColumnDef::new(ReviewLog::State)
+					.enumeration(Alias::new("card_state"), CardState::iter())
+					.not_null(),
+			)
+			.col(ColumnDef::new(ReviewLog::Ease).double().not_null())
+			.col(ColumnDef::new(ReviewLog::Interval).unsigned().not_null())
+			.col(ColumnDef::new(ReviewLog::ReviewedAt).date_time().not_null())
+			.foreign_key(
+				ForeignKey::create()
+					.from(ReviewLog::Table, ReviewLog::User)
+					.to(User::Table, User::Id),
+			)
+			.foreign_key(
+				ForeignKey::create()
+					.from(ReviewLog::Table, ReviewLog::Card)
+					.to(FlashCard::Table, FlashCard::Uid)
+					.on_delete(ForeignKeyAction::Cascade),
+			);
+		if !sqlite {
+			table.index(
+				Index::create()
+					.name("user-reviewed_at")
+					.col(ReviewLog::User)
+					.col(ReviewLog::ReviewedAt),
+			);
+		}
+
+		manager.create_table(table.to_owned()).await?;
+
+		Ok(())
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(ReviewLog::Table).to_owned())
+			.await?;
+
+		manager
+			.drop_table(Table::drop().table(ReviewState::Table).to_owned())
+			.await?;
+
+		Ok(())
+	}
+}
+
+#[derive(DeriveIden)]
+enum User {
+	Table,
+	Id,
+}
+
+#[derive(DeriveIden)]
+enum FlashCard {
+	Table,
+	Uid,
+}
+
+#[derive(DeriveIden)]
+enum ReviewState {
+	Table,
+	Id,
+	User,
+	Card,
+	State,
+	Ease,
+	Interval,
+	Reps,
+	Lapses,
+	Due,
+	LastReview,
+}
+
+#[derive(DeriveIden)]
+enum ReviewLog {
+	Table,
+	Id,
+	User,
+	Card,
+	Grade,
+	State,
+	Ease,
+	Interval,
+	ReviewedAt,
+}
+
+#[derive(Iden, EnumIter)]
+pub enum CardState {
+	#[iden = "New"]
+	New,
+	#[iden = "Learning"]
+	Learning,
+	#[iden = "Review"]
+	Review,
+	#[iden = "Relearning"]
+	Relearning,
+}
+
+#[derive(Iden, EnumIter)]
+pub enum Grade {
+	#[iden = "Again"]
+	Again,
+	#[iden = "Hard"]
+	Hard,
+	#[iden = "Good"]
+	Good,
+	#[iden = "Easy"]
+	Easy,
+}
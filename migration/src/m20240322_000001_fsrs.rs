@@ -0,0 +1,116 @@
+use crate::sea_orm::{EnumIter, Iterable};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(UserSettings::Table)
+					.if_not_exists()
+					.col(
+						ColumnDef::new(UserSettings::User)
+							.unsigned()
+							.not_null()
+							.primary_key(),
+					)
+					.col(
+						ColumnDef::new(UserSettings::Algorithm)
+							.enumeration(Alias::new("algorithm"), Algorithm::iter())
+							.not_null(),
+					)
+					.col(
+						ColumnDef::new(UserSettings::DesiredRetention)
+							.double()
+							.not_null(),
+					)
+					.col(ColumnDef::new(UserSettings::FsrsWeights).json().null())
+					.foreign_key(
+						ForeignKey::create()
+							.from(UserSettings::Table, UserSettings::User)
+							.to(User::Table, User::Id),
+					)
+					.to_owned(),
+			)
+			.await?;
+
+		// Sqlite can only add one column per statement.
+		manager
+			.alter_table(
+				Table::alter()
+					.table(ReviewState::Table)
+					.add_column(ColumnDef::new(ReviewState::Stability).double().null())
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(ReviewState::Table)
+					.add_column(ColumnDef::new(ReviewState::Difficulty).double().null())
+					.to_owned(),
+			)
+			.await?;
+
+		Ok(())
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.alter_table(
+				Table::alter()
+					.table(ReviewState::Table)
+					.drop_column(ReviewState::Difficulty)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(ReviewState::Table)
+					.drop_column(ReviewState::Stability)
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.drop_table(Table::drop().table(UserSettings::Table).to_owned())
+			.await?;
+
+		Ok(())
+	}
+}
+
+#[derive(DeriveIden)]
+enum User {
+	Table,
+	Id,
+}
+
+#[derive(DeriveIden)]
+enum UserSettings {
+	Table,
+	User,
+	Algorithm,
+	DesiredRetention,
+	FsrsWeights,
+}
+
+#[derive(DeriveIden)]
+enum ReviewState {
+	Table,
+	Stability,
+	Difficulty,
+}
+
+#[derive(Iden, EnumIter)]
+pub enum Algorithm {
+	#[iden = "Sm2"]
+	Sm2,
+	#[iden = "Fsrs"]
+	Fsrs,
+}
@@ -0,0 +1,125 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(DeckSettings::Table)
+					.if_not_exists()
+					.col(ColumnDef::new(DeckSettings::User).unsigned().not_null())
+					.col(ColumnDef::new(DeckSettings::Deck).uuid().not_null())
+					.col(
+						ColumnDef::new(DeckSettings::NewPerDay)
+							.unsigned()
+							.not_null(),
+					)
+					.col(
+						ColumnDef::new(DeckSettings::ReviewsPerDay)
+							.unsigned()
+							.not_null(),
+					)
+					.primary_key(
+						Index::create()
+							.col(DeckSettings::User)
+							.col(DeckSettings::Deck),
+					)
+					.foreign_key(
+						ForeignKey::create()
+							.from(DeckSettings::Table, DeckSettings::User)
+							.to(User::Table, User::Id),
+					)
+					.foreign_key(
+						ForeignKey::create()
+							.from(DeckSettings::Table, DeckSettings::Deck)
+							.to(Deck::Table, Deck::Uid)
+							.on_delete(ForeignKeyAction::Cascade),
+					)
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_table(
+				Table::create()
+					.table(StudySession::Table)
+					.if_not_exists()
+					.col(
+						ColumnDef::new(StudySession::Uid)
+							.uuid()
+							.not_null()
+							.primary_key(),
+					)
+					.col(ColumnDef::new(StudySession::User).unsigned().not_null())
+					.col(ColumnDef::new(StudySession::Deck).uuid().null())
+					.col(ColumnDef::new(StudySession::Queue).json().not_null())
+					.col(ColumnDef::new(StudySession::Done).unsigned().not_null())
+					.col(ColumnDef::new(StudySession::Created).date_time().not_null())
+					.col(ColumnDef::new(StudySession::Updated).date_time().not_null())
+					.foreign_key(
+						ForeignKey::create()
+							.from(StudySession::Table, StudySession::User)
+							.to(User::Table, User::Id),
+					)
+					.foreign_key(
+						ForeignKey::create()
+							.from(StudySession::Table, StudySession::Deck)
+							.to(Deck::Table, Deck::Uid)
+							.on_delete(ForeignKeyAction::Cascade),
+					)
+					.to_owned(),
+			)
+			.await?;
+
+		Ok(())
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(StudySession::Table).to_owned())
+			.await?;
+
+		manager
+			.drop_table(Table::drop().table(DeckSettings::Table).to_owned())
+			.await?;
+
+		Ok(())
+	}
+}
+
+#[derive(DeriveIden)]
+enum User {
+	Table,
+	Id,
+}
+
+#[derive(DeriveIden)]
+enum Deck {
+	Table,
+	Uid,
+}
+
+#[derive(DeriveIden)]
+enum DeckSettings {
+	Table,
+	User,
+	Deck,
+	NewPerDay,
+	ReviewsPerDay,
+}
+
+#[derive(DeriveIden)]
+enum StudySession {
+	Table,
+	Uid,
+	User,
+	Deck,
+	Queue,
+	Done,
+	Created,
+	Updated,
+}
@@ -0,0 +1,109 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(DeckFork::Table)
+					.if_not_exists()
+					.col(
+						ColumnDef::new(DeckFork::Deck)
+							.uuid()
+							.not_null()
+							.primary_key(),
+					)
+					.col(ColumnDef::new(DeckFork::Source).uuid().null())
+					.col(ColumnDef::new(DeckFork::Created).date_time().not_null())
+					.foreign_key(
+						ForeignKey::create()
+							.from(DeckFork::Table, DeckFork::Deck)
+							.to(Deck::Table, Deck::Uid)
+							.on_delete(ForeignKeyAction::Cascade),
+					)
+					.foreign_key(
+						ForeignKey::create()
+							.from(DeckFork::Table, DeckFork::Source)
+							.to(Deck::Table, Deck::Uid)
+							.on_delete(ForeignKeyAction::SetNull),
+					)
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_table(
+				Table::create()
+					.table(CardFork::Table)
+					.if_not_exists()
+					.col(
+						ColumnDef::new(CardFork::Card)
+							.uuid()
+							.not_null()
+							.primary_key(),
+					)
+					.col(ColumnDef::new(CardFork::Source).uuid().null())
+					.col(ColumnDef::new(CardFork::SourceHash).char_len(40).not_null())
+					.foreign_key(
+						ForeignKey::create()
+							.from(CardFork::Table, CardFork::Card)
+							.to(FlashCard::Table, FlashCard::Uid)
+							.on_delete(ForeignKeyAction::Cascade),
+					)
+					.foreign_key(
+						ForeignKey::create()
+							.from(CardFork::Table, CardFork::Source)
+							.to(FlashCard::Table, FlashCard::Uid)
+							.on_delete(ForeignKeyAction::SetNull),
+					)
+					.to_owned(),
+			)
+			.await?;
+
+		Ok(())
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(CardFork::Table).to_owned())
+			.await?;
+
+		manager
+			.drop_table(Table::drop().table(DeckFork::Table).to_owned())
+			.await?;
+
+		Ok(())
+	}
+}
+
+#[derive(DeriveIden)]
+enum Deck {
+	Table,
+	Uid,
+}
+
+#[derive(DeriveIden)]
+enum FlashCard {
+	Table,
+	Uid,
+}
+
+#[derive(DeriveIden)]
+enum DeckFork {
+	Table,
+	Deck,
+	Source,
+	Created,
+}
+
+#[derive(DeriveIden)]
+enum CardFork {
+	Table,
+	Card,
+	Source,
+	SourceHash,
+}
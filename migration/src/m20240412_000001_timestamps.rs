@@ -0,0 +1,121 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		// Sqlite can only add one column per statement, and only with a constant
+		// default; existing rows are stamped with the time of the migration.
+		for (table, column) in [
+			(Deck::Table.into_iden(), Deck::Created.into_iden()),
+			(Deck::Table.into_iden(), Deck::Updated.into_iden()),
+			(FlashCard::Table.into_iden(), FlashCard::Created.into_iden()),
+			(FlashCard::Table.into_iden(), FlashCard::Updated.into_iden()),
+		] {
+			manager
+				.alter_table(
+					Table::alter()
+						.table(table)
+						.add_column(
+							ColumnDef::new(column)
+								.date_time()
+								.not_null()
+								.default("1970-01-01 00:00:00"),
+						)
+						.to_owned(),
+				)
+				.await?;
+		}
+
+		manager
+			.exec_stmt(
+				Query::update()
+					.table(Deck::Table)
+					.value(Deck::Created, Expr::current_timestamp())
+					.value(Deck::Updated, Expr::current_timestamp())
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.exec_stmt(
+				Query::update()
+					.table(FlashCard::Table)
+					.value(FlashCard::Created, Expr::current_timestamp())
+					.value(FlashCard::Updated, Expr::current_timestamp())
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.name("flash_card-creator-created")
+					.table(FlashCard::Table)
+					.col(FlashCard::Creator)
+					.col(FlashCard::Created)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.create_index(
+				Index::create()
+					.name("flash_card-creator-updated")
+					.table(FlashCard::Table)
+					.col(FlashCard::Creator)
+					.col(FlashCard::Updated)
+					.to_owned(),
+			)
+			.await?;
+
+		Ok(())
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_index(
+				Index::drop()
+					.name("flash_card-creator-updated")
+					.table(FlashCard::Table)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.drop_index(
+				Index::drop()
+					.name("flash_card-creator-created")
+					.table(FlashCard::Table)
+					.to_owned(),
+			)
+			.await?;
+
+		for (table, column) in [
+			(FlashCard::Table.into_iden(), FlashCard::Updated.into_iden()),
+			(FlashCard::Table.into_iden(), FlashCard::Created.into_iden()),
+			(Deck::Table.into_iden(), Deck::Updated.into_iden()),
+			(Deck::Table.into_iden(), Deck::Created.into_iden()),
+		] {
+			manager
+				.alter_table(Table::alter().table(table).drop_column(column).to_owned())
+				.await?;
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(DeriveIden)]
+enum Deck {
+	Table,
+	Created,
+	Updated,
+}
+
+#[derive(DeriveIden)]
+enum FlashCard {
+	Table,
+	Creator,
+	Created,
+	Updated,
+}
@@ -0,0 +1,126 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.alter_table(
+				Table::alter()
+					.table(ReviewState::Table)
+					.add_column(
+						ColumnDef::new(ReviewState::Unit)
+							.string_len(16)
+							.not_null()
+							.default(""),
+					)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(ReviewLog::Table)
+					.add_column(
+						ColumnDef::new(ReviewLog::Unit)
+							.string_len(16)
+							.not_null()
+							.default(""),
+					)
+					.to_owned(),
+			)
+			.await?;
+
+		// The new index comes first, so MySQL still has one for the user foreign
+		// key when the old one is dropped.
+		manager
+			.create_index(
+				Index::create()
+					.name("review_state-user-card-unit")
+					.table(ReviewState::Table)
+					.col(ReviewState::User)
+					.col(ReviewState::Card)
+					.col(ReviewState::Unit)
+					.unique()
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.drop_index(
+				Index::drop()
+					.name("review_state-user-card")
+					.table(ReviewState::Table)
+					.to_owned(),
+			)
+			.await?;
+
+		Ok(())
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		// Only the whole card can be kept under the old index.
+		manager
+			.exec_stmt(
+				Query::delete()
+					.from_table(ReviewState::Table)
+					.and_where(Expr::col(ReviewState::Unit).ne(""))
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_index(
+				Index::create()
+					.name("review_state-user-card")
+					.table(ReviewState::Table)
+					.col(ReviewState::User)
+					.col(ReviewState::Card)
+					.unique()
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.drop_index(
+				Index::drop()
+					.name("review_state-user-card-unit")
+					.table(ReviewState::Table)
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.alter_table(
+				Table::alter()
+					.table(ReviewLog::Table)
+					.drop_column(ReviewLog::Unit)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(ReviewState::Table)
+					.drop_column(ReviewState::Unit)
+					.to_owned(),
+			)
+			.await?;
+
+		Ok(())
+	}
+}
+
+#[derive(DeriveIden)]
+enum ReviewState {
+	Table,
+	User,
+	Card,
+	Unit,
+}
+
+#[derive(DeriveIden)]
+enum ReviewLog {
+	Table,
+	Unit,
+}
@@ -0,0 +1,125 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(Media::Table)
+					.if_not_exists()
+					.col(ColumnDef::new(Media::Uid).uuid().not_null().primary_key())
+					.col(ColumnDef::new(Media::Owner).unsigned().not_null())
+					.col(ColumnDef::new(Media::Hash).char_len(64).not_null())
+					.col(ColumnDef::new(Media::Mime).string_len(32).not_null())
+					.col(ColumnDef::new(Media::Size).unsigned().not_null())
+					.col(ColumnDef::new(Media::Created).date_time().not_null())
+					.foreign_key(
+						ForeignKey::create()
+							.from(Media::Table, Media::Owner)
+							.to(User::Table, User::Id),
+					)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.create_index(
+				Index::create()
+					.name("media-owner-hash")
+					.table(Media::Table)
+					.col(Media::Owner)
+					.col(Media::Hash)
+					.unique()
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.create_index(
+				Index::create()
+					.name("media-hash")
+					.table(Media::Table)
+					.col(Media::Hash)
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_table(
+				Table::create()
+					.table(CardMedia::Table)
+					.if_not_exists()
+					.col(ColumnDef::new(CardMedia::Card).uuid().not_null())
+					.col(ColumnDef::new(CardMedia::Media).uuid().not_null())
+					.primary_key(Index::create().col(CardMedia::Card).col(CardMedia::Media))
+					.foreign_key(
+						ForeignKey::create()
+							.from(CardMedia::Table, CardMedia::Card)
+							.to(FlashCard::Table, FlashCard::Uid)
+							.on_delete(ForeignKeyAction::Cascade),
+					)
+					.foreign_key(
+						ForeignKey::create()
+							.from(CardMedia::Table, CardMedia::Media)
+							.to(Media::Table, Media::Uid),
+					)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.create_index(
+				Index::create()
+					.name("card_media-media")
+					.table(CardMedia::Table)
+					.col(CardMedia::Media)
+					.to_owned(),
+			)
+			.await?;
+
+		Ok(())
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(CardMedia::Table).to_owned())
+			.await?;
+
+		manager
+			.drop_table(Table::drop().table(Media::Table).to_owned())
+			.await?;
+
+		Ok(())
+	}
+}
+
+#[derive(DeriveIden)]
+enum User {
+	Table,
+	Id,
+}
+
+#[derive(DeriveIden)]
+enum FlashCard {
+	Table,
+	Uid,
+}
+
+#[derive(DeriveIden)]
+enum Media {
+	Table,
+	Uid,
+	Owner,
+	Hash,
+	Mime,
+	Size,
+	Created,
+}
+
+#[derive(DeriveIden)]
+enum CardMedia {
+	Table,
+	Card,
+	Media,
+}
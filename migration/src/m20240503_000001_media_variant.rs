@@ -0,0 +1,124 @@
+use crate::sea_orm::{EnumIter, Iterable};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.alter_table(
+				Table::alter()
+					.table(Media::Table)
+					.add_column(ColumnDef::new(Media::Width).unsigned().null())
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(Media::Table)
+					.add_column(ColumnDef::new(Media::Height).unsigned().null())
+					.to_owned(),
+			)
+			.await?;
+
+		manager
+			.create_table(
+				Table::create()
+					.table(MediaVariant::Table)
+					.if_not_exists()
+					.col(ColumnDef::new(MediaVariant::Media).uuid().not_null())
+					.col(
+						ColumnDef::new(MediaVariant::Variant)
+							.enumeration(Alias::new("media_size"), MediaSize::iter())
+							.not_null(),
+					)
+					.col(ColumnDef::new(MediaVariant::Hash).char_len(64).not_null())
+					.col(ColumnDef::new(MediaVariant::Mime).string_len(32).not_null())
+					.col(ColumnDef::new(MediaVariant::Size).unsigned().not_null())
+					.col(ColumnDef::new(MediaVariant::Width).unsigned().not_null())
+					.col(ColumnDef::new(MediaVariant::Height).unsigned().not_null())
+					.primary_key(
+						Index::create()
+							.col(MediaVariant::Media)
+							.col(MediaVariant::Variant),
+					)
+					.foreign_key(
+						ForeignKey::create()
+							.from(MediaVariant::Table, MediaVariant::Media)
+							.to(Media::Table, Media::Uid)
+							.on_delete(ForeignKeyAction::Cascade),
+					)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.create_index(
+				Index::create()
+					.name("media_variant-hash")
+					.table(MediaVariant::Table)
+					.col(MediaVariant::Hash)
+					.to_owned(),
+			)
+			.await?;
+
+		Ok(())
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(MediaVariant::Table).to_owned())
+			.await?;
+
+		manager
+			.alter_table(
+				Table::alter()
+					.table(Media::Table)
+					.drop_column(Media::Height)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(Media::Table)
+					.drop_column(Media::Width)
+					.to_owned(),
+			)
+			.await?;
+
+		Ok(())
+	}
+}
+
+#[derive(DeriveIden)]
+enum Media {
+	Table,
+	Uid,
+	Width,
+	Height,
+}
+
+#[derive(DeriveIden)]
+enum MediaVariant {
+	Table,
+	Media,
+	Variant,
+	Hash,
+	Mime,
+	Size,
+	Width,
+	Height,
+}
+
+#[derive(Iden, EnumIter)]
+pub enum MediaSize {
+	#[iden = "Thumb"]
+	Thumb,
+	#[iden = "Card"]
+	Card,
+	#[iden = "Full"]
+	Full,
+}
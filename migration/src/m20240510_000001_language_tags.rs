@@ -0,0 +1,237 @@
+use entity::custom::lang::Language;
+use sea_orm_migration::{
+	prelude::*,
+	sea_orm::{prelude::Uuid, ConnectionTrait, JsonValue},
+};
+use sha1::{Digest, Sha1};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Brings the languages of `Lang` sections to the spelling [`Language`]
+/// stores, such as `de-CH` for `de-ch` and `en-uk` for `en-GB`. Tags that
+/// aren't valid are left as they are.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		rename(manager, |lang| {
+			lang.parse::<Language>()
+				.ok()
+				.map(|lang| lang.to_string())
+				.filter(|stored| stored != lang)
+		})
+		.await
+	}
+
+	async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+		// The tags are left in the spelling older versions read.
+		Ok(())
+	}
+}
+
+/// Hash of a card's content, as the server takes it when a card is forked:
+/// of its JSON with sorted maps.
+fn content_hash(json: &JsonValue) -> String {
+	Sha1::digest(json.to_string().as_bytes())
+		.iter()
+		.map(|b| format!("{b:02x}"))
+		.collect()
+}
+
+/// Renames the languages of the `Lang` sections of every card. A language
+/// renamed to one the section already has makes way for it.
+///
+/// Forks of a renamed card that hadn't changed since they were made get the
+/// hash of the renamed content, so that they still show as unchanged.
+async fn rename(
+	manager: &SchemaManager<'_>,
+	renamed: impl Fn(&str) -> Option<String>,
+) -> Result<(), DbErr> {
+	let db = manager.get_connection();
+	let select = Query::select()
+		.columns([FlashCard::Uid, FlashCard::Content])
+		.from(FlashCard::Table)
+		.and_where(Expr::col(FlashCard::Content).like("%\"Lang\"%"))
+		.to_owned();
+	let rows = db
+		.query_all(db.get_database_backend().build(&select))
+		.await?;
+
+	for row in rows {
+		let uid: Uuid = row.try_get("", "uid")?;
+		let content: String = row.try_get("", "content")?;
+		let mut json: JsonValue = content
+			.parse()
+			.map_err(|e| DbErr::Custom(format!("card {uid}: {e}")))?;
+		let old_hash = content_hash(&json);
+
+		let mut changed = false;
+		for section in json.as_array_mut().into_iter().flatten() {
+			if section["type"] != "Lang" {
+				continue;
+			}
+			let Some(items) = section["content"].as_object_mut() else {
+				continue;
+			};
+			let langs: Vec<String> = items.keys().cloned().collect();
+			for lang in langs {
+				if let Some(new) = renamed(&lang) {
+					if let Some(item) = items.remove(&lang) {
+						items.entry(new).or_insert(item);
+						changed = true;
+					}
+				}
+			}
+		}
+
+		if changed {
+			let update = Query::update()
+				.table(FlashCard::Table)
+				.value(FlashCard::Content, json.to_string())
+				.and_where(Expr::col(FlashCard::Uid).eq(uid))
+				.to_owned();
+			db.execute(db.get_database_backend().build(&update)).await?;
+
+			let update = Query::update()
+				.table(CardFork::Table)
+				.value(CardFork::SourceHash, content_hash(&json))
+				.and_where(Expr::col(CardFork::Source).eq(uid))
+				.and_where(Expr::col(CardFork::SourceHash).eq(old_hash))
+				.to_owned();
+			db.execute(db.get_database_backend().build(&update)).await?;
+		}
+	}
+
+	Ok(())
+}
+
+#[derive(DeriveIden)]
+enum FlashCard {
+	Table,
+	Uid,
+	Content,
+}
+
+#[derive(DeriveIden)]
+enum CardFork {
+	Table,
+	Source,
+	SourceHash,
+}
+
+#[cfg(test)]
+mod tests {
+	use sea_orm_migration::sea_orm::{ConnectOptions, Database, DatabaseConnection};
+
+	use super::*;
+
+	const TABLES: &str = "
+		CREATE TABLE flash_card (uid blob(16) NOT NULL PRIMARY KEY, content json_text NOT NULL);
+		CREATE TABLE card_fork (card blob(16) NOT NULL PRIMARY KEY, source blob(16) NULL, source_hash char(40) NOT NULL);
+	";
+
+	async fn card(db: &DatabaseConnection, uid: u128, content: &str) -> Uuid {
+		let uid = Uuid::from_u128(uid);
+		let insert = Query::insert()
+			.into_table(FlashCard::Table)
+			.columns([FlashCard::Uid, FlashCard::Content])
+			.values_panic([uid.into(), content.into()])
+			.to_owned();
+		db.execute(db.get_database_backend().build(&insert))
+			.await
+			.unwrap();
+		uid
+	}
+
+	async fn fork(db: &DatabaseConnection, uid: u128, source: Uuid, hash: String) -> Uuid {
+		let uid = Uuid::from_u128(uid);
+		let insert = Query::insert()
+			.into_table(CardFork::Table)
+			.columns([
+				Alias::new("card"),
+				Alias::new("source"),
+				Alias::new("source_hash"),
+			])
+			.values_panic([uid.into(), source.into(), hash.into()])
+			.to_owned();
+		db.execute(db.get_database_backend().build(&insert))
+			.await
+			.unwrap();
+		uid
+	}
+
+	async fn content(db: &DatabaseConnection, uid: Uuid) -> JsonValue {
+		let select = Query::select()
+			.column(FlashCard::Content)
+			.from(FlashCard::Table)
+			.and_where(Expr::col(FlashCard::Uid).eq(uid))
+			.to_owned();
+		let row = db
+			.query_one(db.get_database_backend().build(&select))
+			.await
+			.unwrap()
+			.unwrap();
+		row.try_get::<String>("", "content")
+			.unwrap()
+			.parse()
+			.unwrap()
+	}
+
+	async fn source_hash(db: &DatabaseConnection, fork: Uuid) -> String {
+		let select = Query::select()
+			.column(CardFork::SourceHash)
+			.from(CardFork::Table)
+			.and_where(Expr::col(Alias::new("card")).eq(fork))
+			.to_owned();
+		let row = db
+			.query_one(db.get_database_backend().build(&select))
+			.await
+			.unwrap()
+			.unwrap();
+		row.try_get("", "source_hash").unwrap()
+	}
+
+	fn hash(content: &str) -> String {
+		content_hash(&content.parse().unwrap())
+	}
+
+	#[tokio::test]
+	async fn stores_every_tag_in_canonical_form() {
+		let mut options = ConnectOptions::new("sqlite::memory:");
+		options.max_connections(1);
+		let db = Database::connect(options).await.unwrap();
+		db.execute_unprepared(TABLES).await.unwrap();
+
+		let mixed = r#"[{"type":"Lang","content":{"en-GB":[{"title":"cat"}],"de-ch":[{"title":"Katze"}],"english":[{"title":"cat"}]}}]"#;
+		let clashing = r#"[{"type":"Lang","content":{"EN-gb":[{"title":"colour"}],"en-uk":[{"title":"color"}]}}]"#;
+		let canonical = r#"[{"type":"Lang","content":{"de-CH":[{"title":"Katze"}],"en-uk":[{"title":"cat"}]}}]"#;
+		let mixed_card = card(&db, 1, mixed).await;
+		let clashing_card = card(&db, 2, clashing).await;
+		let canonical_card = card(&db, 3, canonical).await;
+
+		let unchanged = fork(&db, 4, mixed_card, hash(mixed)).await;
+		let edited = fork(&db, 5, mixed_card, "0".repeat(40)).await;
+		let untouched = fork(&db, 6, canonical_card, hash(canonical)).await;
+
+		let manager = SchemaManager::new(&db);
+		Migration.up(&manager).await.unwrap();
+
+		let renamed = r#"[{"type":"Lang","content":{"de-CH":[{"title":"Katze"}],"en-uk":[{"title":"cat"}],"english":[{"title":"cat"}]}}]"#;
+		assert_eq!(
+			content(&db, mixed_card).await,
+			renamed.parse::<JsonValue>().unwrap()
+		);
+		assert_eq!(
+			content(&db, clashing_card).await[0]["content"],
+			r#"{"en-uk":[{"title":"color"}]}"#.parse::<JsonValue>().unwrap()
+		);
+		assert_eq!(
+			content(&db, canonical_card).await,
+			canonical.parse::<JsonValue>().unwrap()
+		);
+
+		assert_eq!(source_hash(&db, unchanged).await, hash(renamed));
+		assert_eq!(source_hash(&db, edited).await, "0".repeat(40));
+		assert_eq!(source_hash(&db, untouched).await, hash(canonical));
+	}
+}
@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.create_table(
+				Table::create()
+					.table(Speech::Table)
+					.if_not_exists()
+					.col(
+						ColumnDef::new(Speech::Key)
+							.char_len(64)
+							.not_null()
+							.primary_key(),
+					)
+					// The longest tag `Language` accepts.
+					.col(ColumnDef::new(Speech::Language).string_len(35).not_null())
+					.col(ColumnDef::new(Speech::Voice).string_len(64).not_null())
+					.col(ColumnDef::new(Speech::Text).text().not_null())
+					.col(ColumnDef::new(Speech::Hash).char_len(64).not_null())
+					.col(ColumnDef::new(Speech::Created).date_time().not_null())
+					.to_owned(),
+			)
+			.await
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.drop_table(Table::drop().table(Speech::Table).to_owned())
+			.await
+	}
+}
+
+#[derive(DeriveIden)]
+enum Speech {
+	Table,
+	Key,
+	Language,
+	Voice,
+	Text,
+	Hash,
+	Created,
+}
@@ -0,0 +1,132 @@
+use crate::sea_orm::DbBackend;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+	async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		manager
+			.alter_table(
+				Table::alter()
+					.table(DeckSettings::Table)
+					.add_column(ColumnDef::new(DeckSettings::Directions).json().null())
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(DeckSettings::Table)
+					.add_column(
+						ColumnDef::new(DeckSettings::Reverse)
+							.boolean()
+							.not_null()
+							.default(false),
+					)
+					.to_owned(),
+			)
+			.await?;
+
+		// Units name the languages studied now. Sqlite doesn't limit lengths, nor
+		// can it change columns.
+		if !matches!(manager.get_database_backend(), DbBackend::Sqlite) {
+			unit_length(manager, 80).await?;
+		}
+
+		Ok(())
+	}
+
+	async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+		if !matches!(manager.get_database_backend(), DbBackend::Sqlite) {
+			// Units that don't fit anymore go, along with their history.
+			manager
+				.exec_stmt(
+					Query::delete()
+						.from_table(ReviewState::Table)
+						.and_where(
+							Expr::expr(Func::char_length(Expr::col(ReviewState::Unit))).gt(16),
+						)
+						.to_owned(),
+				)
+				.await?;
+			manager
+				.exec_stmt(
+					Query::delete()
+						.from_table(ReviewLog::Table)
+						.and_where(Expr::expr(Func::char_length(Expr::col(ReviewLog::Unit))).gt(16))
+						.to_owned(),
+				)
+				.await?;
+			unit_length(manager, 16).await?;
+		}
+
+		manager
+			.alter_table(
+				Table::alter()
+					.table(DeckSettings::Table)
+					.drop_column(DeckSettings::Reverse)
+					.to_owned(),
+			)
+			.await?;
+		manager
+			.alter_table(
+				Table::alter()
+					.table(DeckSettings::Table)
+					.drop_column(DeckSettings::Directions)
+					.to_owned(),
+			)
+			.await?;
+
+		Ok(())
+	}
+}
+
+async fn unit_length(manager: &SchemaManager<'_>, length: u32) -> Result<(), DbErr> {
+	manager
+		.alter_table(
+			Table::alter()
+				.table(ReviewState::Table)
+				.modify_column(
+					ColumnDef::new(ReviewState::Unit)
+						.string_len(length)
+						.not_null()
+						.default(""),
+				)
+				.to_owned(),
+		)
+		.await?;
+	manager
+		.alter_table(
+			Table::alter()
+				.table(ReviewLog::Table)
+				.modify_column(
+					ColumnDef::new(ReviewLog::Unit)
+						.string_len(length)
+						.not_null()
+						.default(""),
+				)
+				.to_owned(),
+		)
+		.await
+}
+
+#[derive(DeriveIden)]
+enum DeckSettings {
+	Table,
+	Directions,
+	Reverse,
+}
+
+#[derive(DeriveIden)]
+enum ReviewState {
+	Table,
+	Unit,
+}
+
+#[derive(DeriveIden)]
+enum ReviewLog {
+	Table,
+	Unit,
+}
@@ -0,0 +1,428 @@
+use std::io::{Cursor, Write};
+
+use chrono::Utc;
+use entity::{
+	custom::flash_card::{FlashCardContent, FlashCardItem, FlashCardSection},
+	deck, flash_card,
+};
+use rustc_hash::FxHashMap;
+use sea_orm::{ConnectionTrait, Database, DbBackend, Statement};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use zip::{write::FileOptions, ZipWriter};
+
+use super::{strip_html, Error, FIELD_SEPARATOR};
+use crate::{
+	cloze, markdown,
+	media::{extension, hex, parse_data_url},
+	quiz,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE col (id integer primary key, crt integer not null, mod integer not null, scm integer not null, ver integer not null, dty integer not null, usn integer not null, ls integer not null, conf text not null, models text not null, decks text not null, dconf text not null, tags text not null);
+CREATE TABLE notes (id integer primary key, guid text not null, mid integer not null, mod integer not null, usn integer not null, tags text not null, flds text not null, sfld integer not null, csum integer not null, flags integer not null, data text not null);
+CREATE TABLE cards (id integer primary key, nid integer not null, did integer not null, ord integer not null, mod integer not null, usn integer not null, type integer not null, queue integer not null, due integer not null, ivl integer not null, factor integer not null, reps integer not null, lapses integer not null, left integer not null, odue integer not null, odid integer not null, flags integer not null, data text not null);
+CREATE TABLE revlog (id integer primary key, cid integer not null, usn integer not null, ease integer not null, ivl integer not null, lastIvl integer not null, factor integer not null, time integer not null, type integer not null);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn on notes (usn);
+CREATE INDEX ix_cards_usn on cards (usn);
+CREATE INDEX ix_revlog_usn on revlog (usn);
+CREATE INDEX ix_cards_nid on cards (nid);
+CREATE INDEX ix_cards_sched on cards (did, queue, due);
+CREATE INDEX ix_revlog_cid on revlog (cid);
+CREATE INDEX ix_notes_csum on notes (csum);
+";
+
+/// Field a card's content is written to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Field {
+	Front,
+	Back,
+	Lang(String),
+	Ipa,
+	Example,
+	Image,
+}
+
+impl Field {
+	fn name(&self) -> &str {
+		match self {
+			Self::Front => "Front",
+			Self::Back => "Back",
+			Self::Lang(lang) => lang,
+			Self::Ipa => "IPA",
+			Self::Example => "Example",
+			Self::Image => "Image",
+		}
+	}
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('\n', "<br>")
+}
+
+/// Media files collected while rendering, stored under their content hash.
+#[derive(Default)]
+struct Media {
+	files: Vec<(String, Vec<u8>)>,
+	names: FxHashMap<String, String>,
+}
+
+impl Media {
+	/// Returns the name a card should refer to `url` by, packing inline media.
+	fn name(&mut self, url: &str) -> String {
+		let Some((mime, data)) = parse_data_url(url) else {
+			return url.to_string();
+		};
+
+		let hash = hex(&Sha1::digest(&data));
+		if let Some(name) = self.names.get(&hash) {
+			return name.clone();
+		}
+
+		let name = format!("{hash}.{}", extension(mime));
+		self.names.insert(hash, name.clone());
+		self.files.push((name.clone(), data));
+		name
+	}
+}
+
+/// Renders `item` into `field`, sending pronunciations to the IPA field.
+fn render(
+	fields: &mut FxHashMap<Field, Vec<String>>,
+	field: Field,
+	item: &FlashCardItem,
+	media: &mut Media,
+) {
+	let (field, value) = match item {
+		FlashCardItem::Title(text) => (field, escape(text)),
+		FlashCardItem::Example(text) => (field, escape(text)),
+		FlashCardItem::Answer { text, .. } => (field, escape(text)),
+		// Fields are HTML already.
+		FlashCardItem::Markdown { source, .. } => (field, markdown::html(source)),
+		// Anki renders these delimiters with MathJax.
+		FlashCardItem::Math { source, block, .. } => {
+			let (open, close) = if *block {
+				("\\[", "\\]")
+			} else {
+				("\\(", "\\)")
+			};
+			(field, format!("{open}{}{close}", escape(source)))
+		}
+		FlashCardItem::Image(url) => (field, format!("<img src=\"{}\">", media.name(url))),
+		FlashCardItem::Pronunciation { ipa, audio_url } => {
+			let sound = audio_url
+				.as_ref()
+				.map(|url| format!("[sound:{}]", media.name(url)));
+			match field {
+				// Keep the recording with its language, the IPA goes to the IPA field.
+				Field::Lang(_) => {
+					if !ipa.is_empty() {
+						fields.entry(Field::Ipa).or_default().push(escape(ipa));
+					}
+					match sound {
+						Some(sound) => (field, sound),
+						None => return,
+					}
+				}
+				_ => (
+					Field::Ipa,
+					[escape(ipa), sound.unwrap_or_default()]
+						.into_iter()
+						.filter(|s| !s.is_empty())
+						.collect::<Vec<_>>()
+						.join(" "),
+				),
+			}
+		}
+	};
+	fields.entry(field).or_default().push(value);
+}
+
+/// The front, unless something was already asked there.
+fn free_front(fields: &FxHashMap<Field, Vec<String>>) -> Field {
+	if fields.contains_key(&Field::Front) {
+		Field::Example
+	} else {
+		Field::Front
+	}
+}
+
+fn fields(content: &FlashCardContent, media: &mut Media) -> FxHashMap<Field, Vec<String>> {
+	let mut fields = FxHashMap::default();
+	for section in &content.0 {
+		match section {
+			FlashCardSection::Separator => {}
+			FlashCardSection::Item(item) => {
+				let field = match item {
+					FlashCardItem::Title(_) | FlashCardItem::Answer { .. }
+						if !fields.contains_key(&Field::Front) =>
+					{
+						Field::Front
+					}
+					FlashCardItem::Image(_) => Field::Image,
+					_ => Field::Example,
+				};
+				render(&mut fields, field, item, media);
+			}
+			FlashCardSection::FrontBack { front, back } => {
+				render(&mut fields, Field::Front, front, media);
+				render(&mut fields, Field::Back, back, media);
+			}
+			FlashCardSection::Lang(items) => {
+				let mut items: Vec<_> = items.iter().collect();
+				items.sort_by_key(|(lang, _)| *lang);
+				for (lang, item) in items {
+					render(&mut fields, Field::Lang(lang.to_string()), item, media);
+				}
+			}
+			// Notes use a basic note type, so gaps are filled in and questions
+			// answered with their right options.
+			FlashCardSection::Cloze(text) => {
+				fields
+					.entry(free_front(&fields))
+					.or_default()
+					.push(escape(&cloze::plain(text)));
+			}
+			FlashCardSection::MultipleChoice(question) => {
+				fields
+					.entry(free_front(&fields))
+					.or_default()
+					.push(escape(&question.prompt));
+				let answer = quiz::right_options(question)
+					.map(|option| escape(option))
+					.collect::<Vec<_>>()
+					.join(", ");
+				fields.entry(Field::Back).or_default().push(answer);
+			}
+		}
+	}
+	fields
+}
+
+fn template(fields: &[Field]) -> (String, String) {
+	// Ask with the front, or the first language on notes without one.
+	let lang = fields.iter().find(|f| matches!(f, Field::Lang(_)));
+	let (prompt, fallback) = match (fields.contains(&Field::Front), lang) {
+		(true, lang) => (&Field::Front, lang),
+		(false, Some(lang)) => (lang, None),
+		(false, None) => (&fields[0], None),
+	};
+
+	let field = |f: &Field| format!("{{{{#{0}}}}}<div>{{{{{0}}}}}</div>{{{{/{0}}}}}", f.name());
+	let (qfmt, afmt) = match fallback {
+		Some(fallback) => (
+			format!(
+				"{{{{#{0}}}}}{{{{{0}}}}}{{{{/{0}}}}}{{{{^{0}}}}}{{{{{1}}}}}{{{{/{0}}}}}",
+				prompt.name(),
+				fallback.name()
+			),
+			format!("{{{{#{}}}}}{}{{{{/{0}}}}}", prompt.name(), field(fallback)),
+		),
+		None => (format!("{{{{{}}}}}", prompt.name()), String::new()),
+	};
+	let afmt = fields
+		.iter()
+		.filter(|f| *f != prompt)
+		.map(|f| {
+			if Some(f) == fallback {
+				afmt.clone()
+			} else {
+				field(f)
+			}
+		})
+		.fold(String::from("{{FrontSide}}<hr id=answer>"), |a, f| a + &f);
+
+	(qfmt, afmt)
+}
+
+/// Writes `cards` as an Anki package holding a single deck.
+///
+/// All cards share one note type with a field for each kind of content found
+/// in the deck, named so that [`super::import`] maps them back.
+pub async fn write(deck: &deck::Model, cards: &[flash_card::Model]) -> Result<Vec<u8>, Error> {
+	let mut media = Media::default();
+	let notes: Vec<FxHashMap<Field, Vec<String>>> = cards
+		.iter()
+		.map(|c| fields(&c.content, &mut media))
+		.collect();
+
+	let mut names: Vec<Field> = notes.iter().flat_map(|n| n.keys().cloned()).collect();
+	names.sort();
+	names.dedup();
+	if names.is_empty() {
+		names.push(Field::Front);
+	}
+
+	let now = Utc::now();
+	let millis = now.timestamp_millis();
+	let secs = now.timestamp();
+	// Stable across exports, so importing again into Anki updates the same deck.
+	let deck_id = i64::try_from(deck.uid.as_u128() >> 80).unwrap_or(millis);
+	let model_id = millis;
+
+	let (qfmt, afmt) = template(&names);
+	let models = json!({
+		model_id.to_string(): {
+			"id": model_id,
+			"name": format!("flashmind: {}", deck.name),
+			"type": 0,
+			"mod": secs,
+			"usn": -1,
+			"sortf": 0,
+			"did": deck_id,
+			"tmpls": [{
+				"name": "Card 1",
+				"ord": 0,
+				"qfmt": qfmt,
+				"afmt": afmt,
+				"did": null,
+				"bqfmt": "",
+				"bafmt": "",
+			}],
+			"flds": names.iter().enumerate().map(|(ord, f)| json!({
+				"name": f.name(),
+				"ord": ord,
+				"sticky": false,
+				"rtl": false,
+				"font": "Arial",
+				"size": 20,
+				"media": [],
+			})).collect::<Vec<_>>(),
+			"css": ".card { font-family: arial; font-size: 20px; text-align: center; }",
+			"latexPre": "",
+			"latexPost": "",
+			"tags": [],
+			"vers": [],
+			"req": [[0, "any", (0..names.len()).collect::<Vec<_>>()]],
+		}
+	});
+	let deck_json = |id: i64, name: &str| {
+		json!({
+			"id": id,
+			"name": name,
+			"desc": "",
+			"mod": secs,
+			"usn": -1,
+			"collapsed": false,
+			"dyn": 0,
+			"conf": 1,
+			"extendNew": 10,
+			"extendRev": 50,
+			"newToday": [0, 0],
+			"revToday": [0, 0],
+			"lrnToday": [0, 0],
+			"timeToday": [0, 0],
+		})
+	};
+	let decks = json!({
+		"1": deck_json(1, "Default"),
+		deck_id.to_string(): deck_json(deck_id, &deck.name),
+	});
+	let dconf = json!({
+		"1": {
+			"id": 1,
+			"name": "Default",
+			"mod": 0,
+			"usn": 0,
+			"maxTaken": 60,
+			"autoplay": true,
+			"timer": 0,
+			"replayq": true,
+			"new": {"bury": true, "delays": [1, 10], "initialFactor": 2500, "ints": [1, 4, 7], "order": 1, "perDay": 20, "separate": true},
+			"rev": {"bury": true, "ease4": 1.3, "fuzz": 0.05, "ivlFct": 1, "maxIvl": 36500, "minSpace": 1, "perDay": 100},
+			"lapse": {"delays": [10], "leechAction": 0, "leechFails": 8, "minInt": 1, "mult": 0},
+		}
+	});
+	let conf = json!({
+		"activeDecks": [1],
+		"curDeck": 1,
+		"curModel": model_id.to_string(),
+		"nextPos": notes.len() + 1,
+		"newSpread": 0,
+		"collapseTime": 1200,
+		"timeLim": 0,
+		"estTimes": true,
+		"dueCounts": true,
+		"sortType": "noteFld",
+		"sortBackwards": false,
+		"addToCur": true,
+	});
+
+	let collection = tempfile::NamedTempFile::new()?;
+	let db =
+		Database::connect(format!("sqlite://{}?mode=rwc", collection.path().display())).await?;
+	db.execute_unprepared(SCHEMA).await?;
+	db.execute(Statement::from_sql_and_values(
+		DbBackend::Sqlite,
+		"INSERT INTO col VALUES (1, ?, ?, ?, 11, 0, 0, 0, ?, ?, ?, ?, '{}')",
+		[
+			secs.into(),
+			millis.into(),
+			millis.into(),
+			conf.to_string().into(),
+			models.to_string().into(),
+			decks.to_string().into(),
+			dconf.to_string().into(),
+		],
+	))
+	.await?;
+
+	for (i, (note, card)) in notes.iter().zip(cards).enumerate() {
+		let id = millis + i64::try_from(i).unwrap_or_default();
+		let values: Vec<String> = names
+			.iter()
+			.map(|f| note.get(f).map(|v| v.join("<br>")).unwrap_or_default())
+			.collect();
+		let sort_field = strip_html(&values[0]);
+		let checksum = i64::from_str_radix(&hex(&Sha1::digest(sort_field.as_bytes()))[..8], 16)
+			.unwrap_or_default();
+
+		db.execute(Statement::from_sql_and_values(
+			DbBackend::Sqlite,
+			"INSERT INTO notes VALUES (?, ?, ?, ?, -1, '', ?, ?, ?, 0, '')",
+			[
+				id.into(),
+				card.uid.simple().to_string().into(),
+				model_id.into(),
+				secs.into(),
+				values.join(&FIELD_SEPARATOR.to_string()).into(),
+				sort_field.into(),
+				checksum.into(),
+			],
+		))
+		.await?;
+		db.execute(Statement::from_sql_and_values(
+			DbBackend::Sqlite,
+			"INSERT INTO cards VALUES (?, ?, ?, 0, ?, -1, 0, 0, ?, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+			[
+				id.into(),
+				id.into(),
+				deck_id.into(),
+				secs.into(),
+				i64::try_from(i + 1).unwrap_or_default().into(),
+			],
+		))
+		.await?;
+	}
+	db.close().await?;
+
+	let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+	let options = FileOptions::default();
+	zip.start_file("collection.anki2", options)?;
+	zip.write_all(&std::fs::read(collection.path())?)?;
+
+	let mut index = serde_json::Map::new();
+	for (i, (name, data)) in media.files.iter().enumerate() {
+		zip.start_file(i.to_string(), options)?;
+		zip.write_all(data)?;
+		index.insert(i.to_string(), name.clone().into());
+	}
+	zip.start_file("media", options)?;
+	zip.write_all(serde_json::Value::Object(index).to_string().as_bytes())?;
+
+	Ok(zip.finish()?.into_inner())
+}
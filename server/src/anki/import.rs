@@ -0,0 +1,484 @@
+use std::{
+	collections::HashMap,
+	io::{self, Cursor, Read},
+};
+
+use axum::body::Bytes;
+use entity::{
+	custom::{
+		flash_card::{FlashCardContent, FlashCardItem, FlashCardSection},
+		lang::Language,
+	},
+	sea_orm_active_enums::Kind,
+};
+use sea_orm::{Database, DbBackend, FromQueryResult, Statement};
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
+
+use super::{strip_html, Error, FIELD_SEPARATOR};
+use crate::{
+	ipa,
+	media::{self, data_url, mime_type},
+};
+
+/// Largest collection unpacked from a package. Zip headers can claim any
+/// size, so this is counted while unpacking.
+const MAX_COLLECTION_SIZE: u64 = 1024 * 1024 * 1024;
+/// Largest `media` file, which maps the numbered entries to file names.
+const MAX_MEDIA_LIST_SIZE: u64 = 16 * 1024 * 1024;
+
+/// A note that could not be turned into a card.
+#[derive(Debug, Serialize)]
+pub struct Failure {
+	pub note: i64,
+	pub error: String,
+}
+
+#[derive(Debug)]
+pub struct Imported {
+	pub name: String,
+	pub kind: Kind,
+	pub cards: Vec<FlashCardContent>,
+	pub failures: Vec<Failure>,
+}
+
+#[derive(FromQueryResult)]
+struct Col {
+	models: String,
+	decks: String,
+}
+
+#[derive(FromQueryResult)]
+struct Note {
+	id: i64,
+	mid: i64,
+	flds: String,
+}
+
+#[derive(FromQueryResult)]
+struct MainDeck {
+	did: i64,
+}
+
+#[derive(Deserialize)]
+struct NoteType {
+	flds: Vec<FieldDef>,
+}
+
+#[derive(Deserialize)]
+struct FieldDef {
+	name: String,
+	ord: usize,
+}
+
+#[derive(Deserialize)]
+struct DeckDef {
+	name: String,
+}
+
+/// Media files of a package, looked up by the names notes refer to them with.
+struct Media {
+	zip: ZipArchive<Cursor<Bytes>>,
+	entries: HashMap<String, String>,
+}
+
+impl Media {
+	/// The file as a `data:` URL, for [`media::upload_inlined`] to store. Files
+	/// it couldn't store fail the note rather than stay inline.
+	fn url(&mut self, name: &str) -> Result<String, String> {
+		if name.starts_with("http://") || name.starts_with("https://") {
+			return Ok(name.to_string());
+		}
+
+		let mime = mime_type(name).ok_or_else(|| format!("Unsupported media file {name}"))?;
+		let entry = self
+			.entries
+			.get(name)
+			.ok_or_else(|| format!("Missing media file {name}"))?;
+		let file = self
+			.zip
+			.by_name(entry)
+			.map_err(|_| format!("Missing media file {name}"))?;
+
+		let too_large = || format!("Media file {name} is too large");
+		if file.size() > media::MAX_SIZE as u64 {
+			return Err(too_large());
+		}
+		let mut data = Vec::new();
+		file.take(media::MAX_SIZE as u64 + 1)
+			.read_to_end(&mut data)
+			.map_err(|e| e.to_string())?;
+		if data.len() > media::MAX_SIZE {
+			return Err(too_large());
+		}
+		if media::sniff(&data).is_none() {
+			return Err(format!("Unsupported media file {name}"));
+		}
+		Ok(data_url(mime, &data))
+	}
+}
+
+/// Text, images and sounds of a single note field.
+#[derive(Default)]
+struct Field {
+	text: String,
+	images: Vec<String>,
+	sounds: Vec<String>,
+}
+
+impl Field {
+	fn parse(html: &str) -> Self {
+		let mut field = Self::default();
+
+		let mut rest = html;
+		let mut text = String::new();
+		while let Some(start) = rest.find("[sound:") {
+			text.push_str(&rest[..start]);
+			let Some(end) = rest[start..].find(']') else {
+				break;
+			};
+			field
+				.sounds
+				.push(rest[start + 7..start + end].trim().to_string());
+			rest = &rest[start + end + 1..];
+		}
+		text.push_str(rest);
+
+		let mut rest = text.as_str();
+		while let Some(start) = rest.find("<img") {
+			let tag = &rest[start..rest[start..].find('>').map_or(rest.len(), |e| start + e)];
+			if let Some(src) = attribute(tag, "src") {
+				field.images.push(src);
+			}
+			rest = &rest[start + tag.len()..];
+		}
+
+		field.text = strip_html(&text);
+		field
+	}
+
+	fn is_empty(&self) -> bool {
+		self.text.is_empty() && self.images.is_empty() && self.sounds.is_empty()
+	}
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+	let start = tag.find(&format!("{name}="))? + name.len() + 1;
+	let value = &tag[start..];
+	let (quote, value) = match value.chars().next()? {
+		q @ ('"' | '\'') => (q, &value[1..]),
+		_ => (' ', value),
+	};
+	let end = value.find(quote).unwrap_or(value.len());
+	Some(value[..end].to_string())
+}
+
+enum Role {
+	Lang(Language),
+	Pronunciation,
+	Audio,
+	Example,
+	Image,
+	Other,
+}
+
+impl Role {
+	fn of(name: &str) -> Self {
+		match name.trim().to_lowercase().as_str() {
+			"ipa" | "pronunciation" | "transcription" => Self::Pronunciation,
+			"audio" | "sound" => Self::Audio,
+			"example" | "examples" | "sentence" => Self::Example,
+			"image" | "picture" | "photo" => Self::Image,
+			name => name.parse().map_or(Self::Other, Self::Lang),
+		}
+	}
+}
+
+/// Maps the named fields of a note onto card sections.
+///
+/// Fields named after a [`Language`] become `Lang` sections, "IPA"/"Audio",
+/// "Example" and "Image" fields become the matching items, and the first two
+/// remaining fields (usually Front and Back) become a `FrontBack` section.
+fn map_note(fields: &[(&str, &str)], media: &mut Media) -> Result<FlashCardContent, String> {
+	let mut other = Vec::new();
+	let mut langs: Vec<HashMap<Language, FlashCardItem>> = Vec::new();
+	let mut ipa = None;
+	let mut sounds = Vec::new();
+	let mut images = Vec::new();
+	let mut examples = Vec::new();
+
+	for &(name, value) in fields {
+		let field = Field::parse(value);
+		if field.is_empty() {
+			continue;
+		}
+
+		match Role::of(name) {
+			Role::Lang(lang) => {
+				let mut items = Vec::new();
+				if !field.text.is_empty() {
+					items.push(FlashCardItem::Title(field.text));
+				}
+				for sound in &field.sounds {
+					items.push(FlashCardItem::Pronunciation {
+						ipa: String::new(),
+						audio_url: Some(media.url(sound)?),
+					});
+				}
+				for image in &field.images {
+					items.push(FlashCardItem::Image(media.url(image)?));
+				}
+
+				for (i, item) in items.into_iter().enumerate() {
+					if langs.len() <= i {
+						langs.push(HashMap::new());
+					}
+					langs[i].insert(lang.clone(), item);
+				}
+			}
+			Role::Pronunciation => {
+				ipa = Some(field.text);
+				sounds.extend(field.sounds);
+			}
+			Role::Audio => sounds.extend(field.sounds),
+			Role::Example => {
+				examples.extend(field.text.lines().map(str::to_string));
+				sounds.extend(field.sounds);
+				images.extend(field.images);
+			}
+			Role::Image => images.extend(field.images),
+			Role::Other => other.push(field),
+		}
+	}
+
+	let mut sections = Vec::new();
+
+	let mut sides = Vec::new();
+	for field in &mut other {
+		let side = if field.text.is_empty() {
+			field
+				.images
+				.first()
+				.map(|i| media.url(i))
+				.transpose()?
+				.map(|url| {
+					field.images.remove(0);
+					FlashCardItem::Image(url)
+				})
+		} else {
+			Some(FlashCardItem::Title(std::mem::take(&mut field.text)))
+		};
+		sides.extend(side);
+		images.append(&mut field.images);
+		sounds.append(&mut field.sounds);
+	}
+	let mut sides = sides.into_iter();
+	match (sides.next(), sides.next()) {
+		(Some(front), Some(back)) => sections.push(FlashCardSection::FrontBack { front, back }),
+		(Some(item), None) => sections.push(FlashCardSection::Item(item)),
+		_ => {}
+	}
+	sections.extend(sides.map(|item| match item {
+		FlashCardItem::Title(text) => FlashCardSection::Item(FlashCardItem::Example(text)),
+		item => FlashCardSection::Item(item),
+	}));
+
+	sections.extend(langs.into_iter().map(FlashCardSection::Lang));
+
+	let mut sounds = sounds.into_iter();
+	if ipa.is_some() || sounds.len() > 0 {
+		let audio_url = sounds.next().map(|s| media.url(&s)).transpose()?;
+		sections.push(FlashCardSection::Item(FlashCardItem::Pronunciation {
+			ipa: ipa
+				.as_deref()
+				.map(ipa::transcribe)
+				.transpose()
+				.map_err(|e| e.to_string())?
+				.unwrap_or_default(),
+			audio_url,
+		}));
+	}
+	for sound in sounds {
+		sections.push(FlashCardSection::Item(FlashCardItem::Pronunciation {
+			ipa: String::new(),
+			audio_url: Some(media.url(&sound)?),
+		}));
+	}
+
+	for image in images {
+		sections.push(FlashCardSection::Item(FlashCardItem::Image(
+			media.url(&image)?,
+		)));
+	}
+	for example in examples {
+		sections.push(FlashCardSection::Item(FlashCardItem::Example(example)));
+	}
+
+	if sections.is_empty() {
+		return Err("Note has no fields with content".to_string());
+	}
+	Ok(FlashCardContent(sections))
+}
+
+/// Runs zip and file work off the async threads.
+async fn blocking<T: Send + 'static>(
+	f: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+	tokio::task::spawn_blocking(f)
+		.await
+		.map_err(io::Error::other)?
+}
+
+/// Copies the collection of a package out to a file SQLite can open.
+fn open(data: Bytes) -> Result<(tempfile::NamedTempFile, Media), Error> {
+	let mut zip = ZipArchive::new(Cursor::new(data))?;
+
+	let Some(name) = ["collection.anki21", "collection.anki2"]
+		.into_iter()
+		.find(|name| zip.by_name(name).is_ok())
+	else {
+		return Err(if zip.by_name("collection.anki21b").is_ok() {
+			Error::Invalid(
+				"Packages in the latest Anki format are not supported, export with \"Support older Anki versions\" checked",
+			)
+		} else {
+			Error::Invalid("Not an Anki package")
+		});
+	};
+
+	let mut collection = tempfile::NamedTempFile::new()?;
+	let size = io::copy(
+		&mut zip.by_name(name)?.take(MAX_COLLECTION_SIZE + 1),
+		&mut collection,
+	)?;
+	if size > MAX_COLLECTION_SIZE {
+		return Err(Error::Invalid("Collection is too large"));
+	}
+
+	let entries: HashMap<String, String> = match zip.by_name("media") {
+		Ok(file) => {
+			let mut json = String::new();
+			file.take(MAX_MEDIA_LIST_SIZE + 1)
+				.read_to_string(&mut json)?;
+			if json.len() as u64 > MAX_MEDIA_LIST_SIZE {
+				return Err(Error::Invalid("Media list is too large"));
+			}
+			serde_json::from_str::<HashMap<String, String>>(&json)?
+				.into_iter()
+				.map(|(entry, name)| (name, entry))
+				.collect()
+		}
+		Err(_) => HashMap::new(),
+	};
+	Ok((collection, Media { zip, entries }))
+}
+
+/// Reads the notes of an Anki package as cards.
+pub async fn read(data: Bytes) -> Result<Imported, Error> {
+	let (collection, mut media) = blocking(move || open(data)).await?;
+
+	let db = Database::connect(format!("sqlite://{}?mode=ro", collection.path().display())).await?;
+
+	let col = Col::find_by_statement(Statement::from_string(
+		DbBackend::Sqlite,
+		"SELECT models, decks FROM col",
+	))
+	.one(&db)
+	.await?
+	.ok_or(Error::Invalid("Collection has no configuration"))?;
+	let note_types: HashMap<String, NoteType> = serde_json::from_str(&col.models)?;
+	let decks: HashMap<String, DeckDef> = serde_json::from_str(&col.decks)?;
+
+	let main_deck = MainDeck::find_by_statement(Statement::from_string(
+		DbBackend::Sqlite,
+		"SELECT did, COUNT(*) AS count FROM cards GROUP BY did ORDER BY count DESC LIMIT 1",
+	))
+	.one(&db)
+	.await?;
+	let name = main_deck
+		.and_then(|d| decks.get(&d.did.to_string()))
+		.map_or_else(|| "Anki import".to_string(), |d| d.name.clone());
+
+	let notes = Note::find_by_statement(Statement::from_string(
+		DbBackend::Sqlite,
+		"SELECT id, mid, flds FROM notes ORDER BY id",
+	))
+	.all(&db)
+	.await?;
+	db.close().await?;
+
+	let (cards, failures) = blocking(move || {
+		let mut cards = Vec::new();
+		let mut failures = Vec::new();
+		for note in notes {
+			let Some(note_type) = note_types.get(&note.mid.to_string()) else {
+				failures.push(Failure {
+					note: note.id,
+					error: format!("Unknown note type {}", note.mid),
+				});
+				continue;
+			};
+
+			let values: Vec<&str> = note.flds.split(FIELD_SEPARATOR).collect();
+			let mut defs: Vec<&FieldDef> = note_type.flds.iter().collect();
+			defs.sort_by_key(|f| f.ord);
+			let fields: Vec<(&str, &str)> = defs
+				.iter()
+				.filter_map(|f| Some((f.name.as_str(), *values.get(f.ord)?)))
+				.collect();
+
+			match map_note(&fields, &mut media) {
+				Ok(card) => cards.push(card),
+				Err(error) => failures.push(Failure {
+					note: note.id,
+					error,
+				}),
+			}
+		}
+		Ok((cards, failures))
+	})
+	.await?;
+
+	let kind = if cards
+		.iter()
+		.any(|c| c.0.iter().any(|s| matches!(s, FlashCardSection::Lang(_))))
+	{
+		Kind::Language
+	} else {
+		Kind::Other
+	};
+
+	Ok(Imported {
+		name,
+		kind,
+		cards,
+		failures,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Cursor, Write};
+
+	use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+	use super::{open, Error, MAX_MEDIA_LIST_SIZE};
+
+	#[test]
+	fn counts_what_it_unpacks() {
+		let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+		let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+		zip.start_file("collection.anki2", options).unwrap();
+		zip.start_file("media", options).unwrap();
+		let spaces = vec![b' '; 1024 * 1024];
+		for _ in 0..=MAX_MEDIA_LIST_SIZE / 1024 / 1024 {
+			zip.write_all(&spaces).unwrap();
+		}
+		let package = zip.finish().unwrap().into_inner();
+
+		assert!(matches!(
+			open(package.into()),
+			Err(Error::Invalid("Media list is too large"))
+		));
+	}
+}
@@ -0,0 +1,106 @@
+//! Anki package (`.apkg`) support.
+//!
+//! Only the legacy collection format (`collection.anki2`/`collection.anki21`) is
+//! read and written; packages exported with "support older Anki versions"
+//! unchecked only contain `collection.anki21b` and are rejected.
+
+use std::fmt::{Display, Formatter};
+
+use axum::http::StatusCode;
+use sea_orm::DbErr;
+
+pub mod export;
+pub mod import;
+
+/// Separator between note fields in the `notes.flds` column.
+pub const FIELD_SEPARATOR: char = '\x1f';
+
+#[derive(Debug)]
+pub enum Error {
+	Zip(zip::result::ZipError),
+	Io(std::io::Error),
+	Db(DbErr),
+	Json(serde_json::Error),
+	Invalid(&'static str),
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Zip(err) => write!(f, "Invalid package: {err}"),
+			Self::Io(err) => err.fmt(f),
+			Self::Db(err) => write!(f, "Invalid collection: {err}"),
+			Self::Json(err) => write!(f, "Invalid collection: {err}"),
+			Self::Invalid(msg) => f.write_str(msg),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<zip::result::ZipError> for Error {
+	fn from(err: zip::result::ZipError) -> Self {
+		Self::Zip(err)
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(err: std::io::Error) -> Self {
+		Self::Io(err)
+	}
+}
+
+impl From<DbErr> for Error {
+	fn from(err: DbErr) -> Self {
+		Self::Db(err)
+	}
+}
+
+impl From<serde_json::Error> for Error {
+	fn from(err: serde_json::Error) -> Self {
+		Self::Json(err)
+	}
+}
+
+impl From<Error> for (StatusCode, String) {
+	fn from(err: Error) -> Self {
+		match err {
+			Error::Io(_) => crate::internal_error(err),
+			_ => (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()),
+		}
+	}
+}
+
+/// Turns field HTML into plain text.
+pub fn strip_html(html: &str) -> String {
+	let mut text = String::with_capacity(html.len());
+	let mut rest = html;
+	while let Some(start) = rest.find('<') {
+		text.push_str(&rest[..start]);
+		let Some(end) = rest[start..].find('>') else {
+			rest = &rest[start..];
+			break;
+		};
+		let tag = rest[start + 1..start + end].trim_start_matches('/');
+		if ["br", "div", "p", "li"]
+			.iter()
+			.any(|t| tag.split([' ', '/']).next() == Some(t))
+		{
+			text.push('\n');
+		}
+		rest = &rest[start + end + 1..];
+	}
+	text.push_str(rest);
+
+	text.replace("&nbsp;", " ")
+		.replace("&lt;", "<")
+		.replace("&gt;", ">")
+		.replace("&quot;", "\"")
+		.replace("&#39;", "'")
+		.replace("&amp;", "&")
+		.lines()
+		.map(str::trim)
+		.filter(|l| !l.is_empty())
+		.collect::<Vec<_>>()
+		.join("\n")
+}
@@ -0,0 +1,460 @@
+//! Checking typed answers against the side of a card they should match.
+//!
+//! Answers are compared one character at a time, after trimming and collapsing
+//! whitespace. How strictly depends on [`Matching`]; loose matching folds the
+//! characters by the rules of the answer's language.
+
+use std::fmt::{Display, Formatter};
+
+use axum::http::StatusCode;
+use entity::custom::{
+	flash_card::{FlashCardContent, FlashCardItem, FlashCardSection},
+	lang::Language,
+};
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest answer checked, in characters.
+pub const MAX_LENGTH: usize = 1000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Matching {
+	/// Every character has to match.
+	Exact,
+	/// Case and diacritics are ignored.
+	#[default]
+	Loose,
+	/// Like `Loose`, with a few typos allowed.
+	Fuzzy,
+}
+
+#[derive(Debug)]
+pub enum Error {
+	NoSection(usize),
+	NothingToType,
+	NoLang,
+	NoSide,
+	TooLong,
+	ExpectedTooLong,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::NoSection(i) => write!(f, "There is no section {i}"),
+			Self::NothingToType => f.write_str("Nothing to type an answer for"),
+			Self::NoLang => f.write_str("Pick the language to type the answer in"),
+			Self::NoSide => f.write_str("There is no side in that language to type"),
+			Self::TooLong => write!(f, "Answers can't be longer than {MAX_LENGTH} characters"),
+			Self::ExpectedTooLong => write!(
+				f,
+				"The answer to type is longer than {MAX_LENGTH} characters"
+			),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for (StatusCode, String) {
+	fn from(err: Error) -> Self {
+		(StatusCode::UNPROCESSABLE_ENTITY, err.to_string())
+	}
+}
+
+/// Spellings `item` accepts, the first being the one shown.
+fn accepted(item: &FlashCardItem) -> Option<Vec<&str>> {
+	match item {
+		FlashCardItem::Title(text) | FlashCardItem::Example(text) => Some(vec![text]),
+		FlashCardItem::Answer { text, alternatives } => Some(
+			std::iter::once(text)
+				.chain(alternatives)
+				.map(String::as_str)
+				.collect(),
+		),
+		FlashCardItem::Pronunciation { ipa, .. } if !ipa.trim().is_empty() => Some(vec![ipa]),
+		FlashCardItem::Pronunciation { .. }
+		| FlashCardItem::Image(_)
+		| FlashCardItem::Markdown { .. }
+		| FlashCardItem::Math { .. } => None,
+	}
+}
+
+/// Spellings accepted for the answer to a section.
+///
+/// The answer is the back of a front/back section, the `lang` side of a `Lang`
+/// section, or the item of an item section. Without `section`, the first
+/// section that has one is used.
+pub fn expected<'a>(
+	content: &'a FlashCardContent,
+	section: Option<usize>,
+	lang: Option<&Language>,
+) -> Result<Vec<&'a str>, Error> {
+	let answer = |section: &'a FlashCardSection| {
+		let item = match section {
+			FlashCardSection::FrontBack { back, .. } => back,
+			FlashCardSection::Lang(items) => {
+				lang.ok_or(Error::NoLang)?
+					.resolve(items)
+					.ok_or(Error::NoSide)?
+					.1
+			}
+			FlashCardSection::Item(item) => item,
+			FlashCardSection::Separator
+			| FlashCardSection::Cloze(_)
+			| FlashCardSection::MultipleChoice(_) => return Err(Error::NothingToType),
+		};
+		accepted(item).ok_or(Error::NothingToType)
+	};
+
+	match section {
+		Some(i) => answer(content.0.get(i).ok_or(Error::NoSection(i))?),
+		None => {
+			// Report a missing language over there being nothing at all.
+			let mut error = Error::NothingToType;
+			for section in &content.0 {
+				match answer(section) {
+					Ok(accepted) => return Ok(accepted),
+					Err(Error::NoLang) => error = Error::NoLang,
+					Err(_) => {}
+				}
+			}
+			Err(error)
+		}
+	}
+}
+
+/// A character as loose matching sees it: lowercase, without diacritics.
+fn fold(c: char, lang: Option<&Language>) -> char {
+	let c = c.to_lowercase().next().unwrap_or(c);
+	// Letters with a stroke rather than a combining mark don't decompose.
+	let c = match (lang.map(Language::primary), c) {
+		(Some("pl"), 'ł') => 'l',
+		_ => c,
+	};
+	c.nfd().next().unwrap_or(c)
+}
+
+fn tidy(text: &str) -> Vec<char> {
+	text.split_whitespace()
+		.collect::<Vec<_>>()
+		.join(" ")
+		.chars()
+		.collect()
+}
+
+/// A stretch of the answer compared to the expected text.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Diff {
+	/// Expected text that was typed, possibly differing in case or diacritics.
+	Same(String),
+	/// Typed text that shouldn't be there.
+	Extra(String),
+	/// Expected text that wasn't typed.
+	Missing(String),
+}
+
+/// Edit distance between `typed` and `expected`, with the edits. Two
+/// neighbouring characters typed the wrong way round count as one edit.
+fn diff(
+	typed: &[char],
+	expected: &[char],
+	same: impl Fn(char, char) -> bool,
+) -> (usize, Vec<Diff>) {
+	let swapped = |i: usize, j: usize| {
+		i > 1 && j > 1 && same(typed[i - 1], expected[j - 2]) && same(typed[i - 2], expected[j - 1])
+	};
+	let width = expected.len() + 1;
+	let mut cost = vec![0; (typed.len() + 1) * width];
+	for i in 0..=typed.len() {
+		for j in 0..=expected.len() {
+			cost[i * width + j] = if i == 0 || j == 0 {
+				i + j
+			} else {
+				let substitute = usize::from(!same(typed[i - 1], expected[j - 1]));
+				let mut here = (cost[(i - 1) * width + j - 1] + substitute)
+					.min(cost[(i - 1) * width + j] + 1)
+					.min(cost[i * width + j - 1] + 1);
+				if swapped(i, j) {
+					here = here.min(cost[(i - 2) * width + j - 2] + 1);
+				}
+				here
+			};
+		}
+	}
+
+	// Walk back, then group the edits between matching stretches.
+	let mut steps = Vec::new();
+	let (mut i, mut j) = (typed.len(), expected.len());
+	while i > 0 || j > 0 {
+		let here = cost[i * width + j];
+		if i > 0
+			&& j > 0 && same(typed[i - 1], expected[j - 1])
+			&& here == cost[(i - 1) * width + j - 1]
+		{
+			steps.push((None, Some(expected[j - 1]), true));
+			(i, j) = (i - 1, j - 1);
+		} else if swapped(i, j) && here == cost[(i - 2) * width + j - 2] + 1 {
+			steps.push((Some(typed[i - 1]), Some(expected[j - 1]), false));
+			steps.push((Some(typed[i - 2]), Some(expected[j - 2]), false));
+			(i, j) = (i - 2, j - 2);
+		} else if i > 0 && j > 0 && here == cost[(i - 1) * width + j - 1] + 1 {
+			steps.push((Some(typed[i - 1]), Some(expected[j - 1]), false));
+			(i, j) = (i - 1, j - 1);
+		} else if i > 0 && here == cost[(i - 1) * width + j] + 1 {
+			steps.push((Some(typed[i - 1]), None, false));
+			i -= 1;
+		} else {
+			steps.push((None, Some(expected[j - 1]), false));
+			j -= 1;
+		}
+	}
+
+	let mut parts = Vec::new();
+	let (mut matched, mut extra, mut missing) = (String::new(), String::new(), String::new());
+	for (typed, expected, same) in steps.into_iter().rev() {
+		if same {
+			flush_edits(&mut parts, &mut extra, &mut missing);
+			matched.extend(expected);
+		} else {
+			if !matched.is_empty() {
+				parts.push(Diff::Same(std::mem::take(&mut matched)));
+			}
+			extra.extend(typed);
+			missing.extend(expected);
+		}
+	}
+	flush_edits(&mut parts, &mut extra, &mut missing);
+	if !matched.is_empty() {
+		parts.push(Diff::Same(matched));
+	}
+	(cost[cost.len() - 1], parts)
+}
+
+fn flush_edits(parts: &mut Vec<Diff>, extra: &mut String, missing: &mut String) {
+	if !extra.is_empty() {
+		parts.push(Diff::Extra(std::mem::take(extra)));
+	}
+	if !missing.is_empty() {
+		parts.push(Diff::Missing(std::mem::take(missing)));
+	}
+}
+
+#[derive(Debug, Serialize)]
+pub struct Checked {
+	pub correct: bool,
+	/// The accepted spelling closest to the answer.
+	pub expected: String,
+	/// Characters to change to get from the answer to `expected`, counting
+	/// those that `matching` ignores as the same.
+	pub typos: usize,
+	pub diff: Vec<Diff>,
+}
+
+/// Typos allowed in fuzzy matching: one per four characters expected.
+fn allowed(expected: &[char]) -> usize {
+	expected.len() / 4
+}
+
+/// Checks `typed` against every accepted spelling, keeping the closest.
+///
+/// `typos` overrides how many typos fuzzy matching lets through. Both sides
+/// are limited to [`MAX_LENGTH`], as comparing them takes their product in
+/// time and memory.
+pub fn check(
+	accepted: &[&str],
+	typed: &str,
+	matching: Matching,
+	typos: Option<usize>,
+	lang: Option<&Language>,
+) -> Result<Checked, Error> {
+	if typed.chars().count() > MAX_LENGTH {
+		return Err(Error::TooLong);
+	}
+	let typed = tidy(typed);
+	let same = |a: char, b: char| match matching {
+		Matching::Exact => a == b,
+		Matching::Loose | Matching::Fuzzy => fold(a, lang) == fold(b, lang),
+	};
+
+	let mut best: Option<Checked> = None;
+	for answer in accepted {
+		let expected = tidy(answer);
+		if expected.len() > MAX_LENGTH {
+			return Err(Error::ExpectedTooLong);
+		}
+		let (distance, diff) = diff(&typed, &expected, same);
+		let limit = match matching {
+			Matching::Exact | Matching::Loose => 0,
+			Matching::Fuzzy => typos.unwrap_or_else(|| allowed(&expected)),
+		};
+		let checked = Checked {
+			correct: distance <= limit,
+			expected: expected.into_iter().collect(),
+			typos: distance,
+			diff,
+		};
+		if best.as_ref().is_none_or(|best| {
+			(checked.correct, std::cmp::Reverse(checked.typos))
+				> (best.correct, std::cmp::Reverse(best.typos))
+		}) {
+			best = Some(checked);
+		}
+	}
+	best.ok_or(Error::NothingToType)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn check_one(typed: &str, expected: &str, matching: Matching) -> Checked {
+		check(&[expected], typed, matching, None, None).unwrap()
+	}
+
+	fn chars(text: &str) -> Vec<char> {
+		text.chars().collect()
+	}
+
+	#[test]
+	fn diffs_answers() {
+		let diff = |typed: &str, expected: &str| {
+			super::diff(&chars(typed), &chars(expected), |a, b| a == b)
+		};
+		assert_eq!(diff("kot", "kot"), (0, vec![Diff::Same("kot".to_string())]));
+		assert_eq!(diff("", ""), (0, Vec::new()));
+		assert_eq!(
+			diff("kto", "kot"),
+			(
+				1,
+				vec![
+					Diff::Same("k".to_string()),
+					Diff::Extra("to".to_string()),
+					Diff::Missing("ot".to_string()),
+				]
+			)
+		);
+		assert_eq!(
+			diff("kotx", "kot"),
+			(
+				1,
+				vec![Diff::Same("kot".to_string()), Diff::Extra("x".to_string())]
+			)
+		);
+		assert_eq!(
+			diff("ko", "kot"),
+			(
+				1,
+				vec![Diff::Same("ko".to_string()), Diff::Missing("t".to_string())]
+			)
+		);
+		assert_eq!(
+			diff("pies", "kot"),
+			(
+				4,
+				vec![
+					Diff::Extra("pies".to_string()),
+					Diff::Missing("kot".to_string()),
+				]
+			)
+		);
+	}
+
+	#[test]
+	fn matches_as_strictly_as_asked() {
+		let exact = check_one("  Zolw ", "żółw", Matching::Exact);
+		assert!(!exact.correct);
+		assert_eq!(exact.typos, 3);
+
+		let loose = check_one("  CAFE  au\tLAIT", "café  au lait", Matching::Loose);
+		assert!(loose.correct);
+		assert_eq!(loose.typos, 0);
+		assert_eq!(loose.expected, "café au lait");
+		assert_eq!(loose.diff, vec![Diff::Same("café au lait".to_string())]);
+
+		// Only Polish folds `ł`, which doesn't decompose.
+		let pl = "pl".parse().unwrap();
+		assert!(
+			check(&["łódź"], "lodz", Matching::Loose, None, Some(&pl))
+				.unwrap()
+				.correct
+		);
+		assert!(!check_one("lodz", "łódź", Matching::Loose).correct);
+
+		assert!(check_one("ksiązka", "książka", Matching::Loose).correct);
+		assert!(!check_one("ksiazak", "książka", Matching::Loose).correct);
+		assert!(check_one("ksiazak", "książka", Matching::Fuzzy).correct);
+		assert!(!check_one("ksizak", "książka", Matching::Fuzzy).correct);
+		let strict = check(&["książka"], "ksiazak", Matching::Fuzzy, Some(0), None).unwrap();
+		assert!(!strict.correct);
+		assert_eq!(strict.typos, 1);
+	}
+
+	#[test]
+	fn keeps_the_closest_spelling() {
+		let checked = check(&["colour", "color"], "colr", Matching::Fuzzy, None, None).unwrap();
+		assert!(checked.correct);
+		assert_eq!(checked.expected, "color");
+		let checked = check(&["colour", "color"], "colour", Matching::Exact, None, None).unwrap();
+		assert!(checked.correct);
+		assert_eq!(checked.expected, "colour");
+		assert!(matches!(
+			check(&[], "colour", Matching::Exact, None, None),
+			Err(Error::NothingToType)
+		));
+	}
+
+	#[test]
+	fn limits_lengths() {
+		let long = "a".repeat(MAX_LENGTH + 1);
+		assert!(matches!(
+			check(&["a"], &long, Matching::Exact, None, None),
+			Err(Error::TooLong)
+		));
+		assert!(matches!(
+			check(&[&long], "a", Matching::Exact, None, None),
+			Err(Error::ExpectedTooLong)
+		));
+	}
+
+	#[test]
+	fn finds_the_expected_answer() {
+		let content: FlashCardContent = serde_json::from_value(serde_json::json!([
+			{"type": "Item", "content": {"image": "https://a/b.png"}},
+			{"type": "Lang", "content": {
+				"pl": {"title": "kot"},
+				"en-GB": {"answer": {"text": "cat", "alternatives": ["puss"]}},
+			}},
+			{"type": "FrontBack", "content": {"front": {"title": "dog"}, "back": {"title": "pies"}}},
+		]))
+		.unwrap();
+		let en = "en".parse().unwrap();
+		let de = "de".parse().unwrap();
+		assert_eq!(
+			expected(&content, None, Some(&en)).unwrap(),
+			["cat", "puss"]
+		);
+		assert_eq!(expected(&content, None, None).unwrap(), ["pies"]);
+		assert_eq!(expected(&content, None, Some(&de)).unwrap(), ["pies"]);
+		assert!(matches!(
+			expected(&content, Some(1), None),
+			Err(Error::NoLang)
+		));
+		assert!(matches!(
+			expected(&content, Some(1), Some(&de)),
+			Err(Error::NoSide)
+		));
+		assert!(matches!(
+			expected(&content, Some(0), None),
+			Err(Error::NothingToType)
+		));
+		assert!(matches!(
+			expected(&content, Some(3), None),
+			Err(Error::NoSection(3))
+		));
+		let images = FlashCardContent(content.0[..2].to_vec());
+		assert!(matches!(expected(&images, None, None), Err(Error::NoLang)));
+	}
+}
@@ -11,11 +11,21 @@ use migration::MigratorTrait;
 use sea_orm::DatabaseConnection;
 use serde_json::json;
 
-use crate::{config::AppConfig, db::db, oidc, oidc::OIDCProviders, route};
+use crate::{
+	config::AppConfig,
+	db::db,
+	media, oidc,
+	oidc::OIDCProviders,
+	route,
+	storage::{self, Storage},
+	tts::{self, Tts},
+};
 
 pub struct AppStateInner {
 	pub providers: OIDCProviders,
 	pub db: DatabaseConnection,
+	pub storage: Arc<dyn Storage>,
+	pub tts: Arc<dyn Tts>,
 }
 
 #[derive(Clone)]
@@ -36,6 +46,18 @@ impl FromRef<AppState> for DatabaseConnection {
 	}
 }
 
+impl FromRef<AppState> for Arc<dyn Storage> {
+	fn from_ref(input: &AppState) -> Self {
+		input.storage.clone()
+	}
+}
+
+impl FromRef<AppState> for Arc<dyn Tts> {
+	fn from_ref(input: &AppState) -> Self {
+		input.tts.clone()
+	}
+}
+
 pub async fn app(config: AppConfig) -> Router {
 	let providers = oidc::get_oidc_providers(format!("{}/login", config.public_url)).await;
 	let db = db(&config).await;
@@ -44,7 +66,25 @@ pub async fn app(config: AppConfig) -> Router {
 		.await
 		.expect("Migration failed");
 
-	let state = AppState(Arc::new(AppStateInner { providers, db }));
+	let storage: Arc<dyn Storage> = match &config.s3 {
+		Some(s3) => Arc::new(storage::S3::new(s3).expect("Invalid S3 configuration")),
+		None => Arc::new(storage::Local::new(&config.media_dir)),
+	};
+	let tts: Arc<dyn Tts> = match &config.espeak {
+		Some(program) => Arc::new(tts::Espeak::new(program).expect("Can't run eSpeak NG")),
+		None => Arc::new(tts::Silent),
+	};
+	tokio::spawn(media::collect_garbage_periodically(
+		db.clone(),
+		storage.clone(),
+	));
+
+	let state = AppState(Arc::new(AppStateInner {
+		providers,
+		db,
+		storage,
+		tts,
+	}));
 
 	let session_config = SessionConfig::default()
 		.with_key(Key::generate())
@@ -0,0 +1,382 @@
+//! Query language for searching a user's cards.
+//!
+//! A query is a list of terms separated by spaces, and a card has to match all
+//! of them:
+//! - `lang:<tag>`: has a section to show in that language, e.g. `lang:pl`, or
+//!   `lang:en` for a card in `en-GB` only;
+//! - `has:<audio|image|ipa|example>`: has that kind of content;
+//! - `type:<title|pronunciation|image|example|answer|markdown|math|frontback|lang|cloze|choice|separator>`:
+//!   has an item or section of that type;
+//! - `deck:<uuid>`: is in that deck, `deck:*` is in any deck;
+//! - anything else is text to find in titles, examples, answers, Markdown,
+//!   formulas, cloze text and questions, ignoring case.
+//!   `"quoted text"` can contain spaces.
+//!
+//! A term starting with `-` matches the cards the term doesn't, so `-deck:*`
+//! finds cards that are in no deck.
+
+use std::fmt::{Display, Formatter};
+
+use axum::http::StatusCode;
+use entity::custom::{
+	flash_card::{FlashCardContent, FlashCardItem, FlashCardSection},
+	lang::Language,
+};
+use uuid::Uuid;
+
+use crate::cloze;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Has {
+	Audio,
+	Image,
+	Ipa,
+	Example,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Type {
+	Title,
+	Pronunciation,
+	Image,
+	Example,
+	Answer,
+	Markdown,
+	Math,
+	FrontBack,
+	Lang,
+	Cloze,
+	Choice,
+	Separator,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InDeck {
+	Any,
+	Deck(Uuid),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+	Lang(Language),
+	Has(Has),
+	Type(Type),
+	/// Evaluated by the database, as card content doesn't know its decks.
+	Deck(InDeck),
+	/// Lowercased.
+	Text(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Term {
+	pub negated: bool,
+	pub filter: Filter,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Query(pub Vec<Term>);
+
+#[derive(Debug)]
+pub enum Error {
+	UnclosedQuote,
+	EmptyTerm,
+	UnknownFilter(String),
+	Value(String, String),
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::UnclosedQuote => f.write_str("Unclosed quote"),
+			Self::EmptyTerm => f.write_str("Empty search term"),
+			Self::UnknownFilter(key) => write!(f, "Unknown filter `{key}:`"),
+			Self::Value(key, value) => write!(f, "Invalid value `{value}` for `{key}:`"),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for (StatusCode, String) {
+	fn from(err: Error) -> Self {
+		(StatusCode::BAD_REQUEST, err.to_string())
+	}
+}
+
+/// Lowercased text a card can be found by: titles, examples, answers with
+/// their alternatives, Markdown and TeX source, including those in `Lang`
+/// sections, cloze text with the gaps filled in, and multiple choice questions
+/// with their options.
+pub fn text(content: &FlashCardContent) -> String {
+	content
+		.items()
+		.filter_map(|item| match item {
+			FlashCardItem::Title(text)
+			| FlashCardItem::Example(text)
+			| FlashCardItem::Markdown { source: text, .. }
+			| FlashCardItem::Math { source: text, .. } => Some(text.to_lowercase()),
+			FlashCardItem::Answer { text, alternatives } => Some(
+				std::iter::once(text)
+					.chain(alternatives)
+					.map(|text| text.to_lowercase())
+					.collect::<Vec<_>>()
+					.join("\n"),
+			),
+			_ => None,
+		})
+		.chain(content.0.iter().filter_map(|section| {
+			match section {
+				FlashCardSection::Cloze(text) => Some(cloze::plain(text).to_lowercase()),
+				FlashCardSection::MultipleChoice(question) => Some(
+					std::iter::once(&question.prompt)
+						.chain(&question.options)
+						.map(|text| text.to_lowercase())
+						.collect::<Vec<_>>()
+						.join("\n"),
+				),
+				_ => None,
+			}
+		}))
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+fn filter(key: &str, value: &str) -> Result<Filter, Error> {
+	let invalid = || Error::Value(key.to_string(), value.to_string());
+	Ok(match key {
+		"lang" => Filter::Lang(value.parse().map_err(|_| invalid())?),
+		"has" => Filter::Has(match value {
+			"audio" => Has::Audio,
+			"image" => Has::Image,
+			"ipa" => Has::Ipa,
+			"example" => Has::Example,
+			_ => return Err(invalid()),
+		}),
+		"type" => Filter::Type(match value {
+			"title" => Type::Title,
+			"pronunciation" => Type::Pronunciation,
+			"image" => Type::Image,
+			"example" => Type::Example,
+			"answer" => Type::Answer,
+			"markdown" => Type::Markdown,
+			"math" => Type::Math,
+			"frontback" => Type::FrontBack,
+			"lang" => Type::Lang,
+			"cloze" => Type::Cloze,
+			"choice" => Type::Choice,
+			"separator" => Type::Separator,
+			_ => return Err(invalid()),
+		}),
+		"deck" => Filter::Deck(match value {
+			"*" => InDeck::Any,
+			_ => InDeck::Deck(value.parse().map_err(|_| invalid())?),
+		}),
+		_ => return Err(Error::UnknownFilter(key.to_string())),
+	})
+}
+
+impl std::str::FromStr for Query {
+	type Err = Error;
+
+	fn from_str(query: &str) -> Result<Self, Error> {
+		let mut terms = Vec::new();
+		let mut chars = query.chars().peekable();
+		loop {
+			while chars.next_if(|c| c.is_whitespace()).is_some() {}
+			if chars.peek().is_none() {
+				break;
+			}
+			let negated = chars.next_if_eq(&'-').is_some();
+
+			// The key is whatever comes before a colon outside quotes.
+			let mut key = None;
+			let mut value = String::new();
+			let mut quoted = false;
+			let mut in_quotes = false;
+			while let Some(c) = chars.next_if(|c| in_quotes || !c.is_whitespace()) {
+				match c {
+					'"' => {
+						in_quotes = !in_quotes;
+						quoted = true;
+					}
+					':' if !in_quotes && !quoted && key.is_none() => {
+						key = Some(std::mem::take(&mut value));
+					}
+					_ => value.push(c),
+				}
+			}
+			if in_quotes {
+				return Err(Error::UnclosedQuote);
+			}
+
+			let filter = match key {
+				Some(key) => filter(&key.to_lowercase(), &value)?,
+				None if value.is_empty() => return Err(Error::EmptyTerm),
+				None => Filter::Text(value.to_lowercase()),
+			};
+			terms.push(Term { negated, filter });
+		}
+		Ok(Self(terms))
+	}
+}
+
+impl Filter {
+	/// Whether a card's content matches; `None` for deck filters.
+	fn matches(&self, content: &FlashCardContent) -> Option<bool> {
+		let mut items = content.items();
+		let mut sections = content.0.iter();
+		Some(match self {
+			Self::Lang(lang) => sections.any(|s| match s {
+				FlashCardSection::Lang(items) => lang.resolve(items).is_some(),
+				_ => false,
+			}),
+			Self::Has(Has::Audio) => items.any(|i| {
+				matches!(
+					i,
+					FlashCardItem::Pronunciation {
+						audio_url: Some(_),
+						..
+					}
+				)
+			}),
+			Self::Has(Has::Ipa) => items.any(|i| match i {
+				FlashCardItem::Pronunciation { ipa, .. } => !ipa.trim().is_empty(),
+				_ => false,
+			}),
+			Self::Has(Has::Image) | Self::Type(Type::Image) => {
+				items.any(|i| matches!(i, FlashCardItem::Image(_)))
+			}
+			Self::Has(Has::Example) | Self::Type(Type::Example) => {
+				items.any(|i| matches!(i, FlashCardItem::Example(_)))
+			}
+			Self::Type(Type::Title) => items.any(|i| matches!(i, FlashCardItem::Title(_))),
+			Self::Type(Type::Answer) => items.any(|i| matches!(i, FlashCardItem::Answer { .. })),
+			Self::Type(Type::Markdown) => {
+				items.any(|i| matches!(i, FlashCardItem::Markdown { .. }))
+			}
+			Self::Type(Type::Math) => items.any(|i| matches!(i, FlashCardItem::Math { .. })),
+			Self::Type(Type::Pronunciation) => {
+				items.any(|i| matches!(i, FlashCardItem::Pronunciation { .. }))
+			}
+			Self::Type(Type::FrontBack) => {
+				sections.any(|s| matches!(s, FlashCardSection::FrontBack { .. }))
+			}
+			Self::Type(Type::Lang) => sections.any(|s| matches!(s, FlashCardSection::Lang(_))),
+			Self::Type(Type::Cloze) => sections.any(|s| matches!(s, FlashCardSection::Cloze(_))),
+			Self::Type(Type::Choice) => {
+				sections.any(|s| matches!(s, FlashCardSection::MultipleChoice(_)))
+			}
+			Self::Type(Type::Separator) => {
+				sections.any(|s| matches!(s, FlashCardSection::Separator))
+			}
+			Self::Text(term) => text(content).contains(term.as_str()),
+			Self::Deck(_) => return None,
+		})
+	}
+}
+
+impl Query {
+	/// Whether a card's content matches every term, leaving out deck filters.
+	pub fn matches(&self, content: &FlashCardContent) -> bool {
+		self.0.iter().all(|term| {
+			term.filter
+				.matches(content)
+				.is_none_or(|matches| matches != term.negated)
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(query: &str) -> Result<Vec<Term>, Error> {
+		query.parse::<Query>().map(|query| query.0)
+	}
+
+	fn term(negated: bool, filter: Filter) -> Term {
+		Term { negated, filter }
+	}
+
+	fn card(json: serde_json::Value) -> FlashCardContent {
+		serde_json::from_value(json).unwrap()
+	}
+
+	#[test]
+	fn parses_terms() {
+		let deck = Uuid::new_v4();
+		assert_eq!(
+			parse(&format!(
+				"  lang:en-uk HAS:audio -deck:* deck:{deck} type:cloze Kot \"na macie\" -\"a:b\""
+			))
+			.unwrap(),
+			vec![
+				term(false, Filter::Lang("en-GB".parse().unwrap())),
+				term(false, Filter::Has(Has::Audio)),
+				term(true, Filter::Deck(InDeck::Any)),
+				term(false, Filter::Deck(InDeck::Deck(deck))),
+				term(false, Filter::Type(Type::Cloze)),
+				term(false, Filter::Text("kot".to_string())),
+				term(false, Filter::Text("na macie".to_string())),
+				term(true, Filter::Text("a:b".to_string())),
+			]
+		);
+		assert_eq!(
+			parse("lang:\"pl\" a\"b c\"d").unwrap(),
+			vec![
+				term(false, Filter::Lang("pl".parse().unwrap())),
+				term(false, Filter::Text("ab cd".to_string())),
+			]
+		);
+		assert_eq!(parse(" \t").unwrap(), Vec::new());
+	}
+
+	#[test]
+	fn rejects_bad_terms() {
+		assert!(matches!(parse("\"na macie"), Err(Error::UnclosedQuote)));
+		assert!(matches!(parse("kot -"), Err(Error::EmptyTerm)));
+		assert!(matches!(parse("\"\""), Err(Error::EmptyTerm)));
+		assert!(matches!(parse("color:red"), Err(Error::UnknownFilter(key)) if key == "color"));
+		assert!(matches!(parse("has:smell"), Err(Error::Value(..))));
+		assert!(matches!(parse("type:Cloze"), Err(Error::Value(..))));
+		assert!(matches!(parse("deck:mine"), Err(Error::Value(..))));
+		assert!(matches!(parse("lang:english"), Err(Error::Value(..))));
+	}
+
+	#[test]
+	fn collects_text() {
+		let content = card(serde_json::json!([
+			{"type": "Item", "content": {"title": "Kot"}},
+			{"type": "Item", "content": {"answer": {"text": "Cat", "alternatives": ["Puss"]}}},
+			{"type": "Lang", "content": {"pl": {"example": "Mam KOTA"}}},
+			{"type": "Cloze", "content": "{{c1::Siedzi::verb}} na macie"},
+			{"type": "MultipleChoice", "content": {"prompt": "Which?", "options": ["A", "B"]}},
+		]));
+		assert_eq!(
+			text(&content),
+			"kot\ncat\npuss\nmam kota\nsiedzi na macie\nwhich?\na\nb"
+		);
+	}
+
+	#[test]
+	fn matches_cards() {
+		let content = card(serde_json::json!([
+			{"type": "FrontBack", "content": {
+				"front": {"title": "Dog"},
+				"back": {"pronunciation": {"ipa": " ", "audioUrl": "https://a/b.mp3"}},
+			}},
+			{"type": "Lang", "content": {"en-GB": {"title": "Dog"}}},
+		]));
+		let matches = |query: &str| query.parse::<Query>().unwrap().matches(&content);
+		assert!(matches(""));
+		assert!(matches(
+			"dog has:audio type:frontback type:pronunciation lang:en"
+		));
+		assert!(matches("-has:ipa -type:image -lang:pl"));
+		assert!(!matches("has:ipa"));
+		assert!(!matches("-dog"));
+		assert!(!matches("dog cat"));
+		// Decks are up to the database.
+		assert!(matches("deck:* -deck:*"));
+	}
+}
@@ -0,0 +1,221 @@
+//! Cloze deletions: text with numbered gaps, written the way Anki does it.
+//!
+//! `Kot {{c1::siedzi}} na {{c2::macie::where?}}.` has two deletions. The number
+//! says which review a gap belongs to, so gaps sharing a number are hidden
+//! together. The optional part after a second `::` is a hint.
+
+use std::{
+	collections::BTreeSet,
+	fmt::{Display, Formatter},
+};
+
+use axum::http::StatusCode;
+use entity::custom::flash_card::{FlashCardContent, FlashCardSection};
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+const SEPARATOR: &str = "::";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Part<'a> {
+	Text(&'a str),
+	Deletion {
+		number: u32,
+		answer: &'a str,
+		hint: Option<&'a str>,
+	},
+}
+
+/// Malformed markup, with the character it starts at, counting from 1.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+	Unclosed(usize),
+	Unopened(usize),
+	Nested(usize),
+	Number(usize),
+	EmptyAnswer(usize),
+	NoDeletions,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Unclosed(at) => write!(f, "Cloze deletion at character {at} is not closed"),
+			Self::Unopened(at) => write!(f, "Unexpected `}}}}` at character {at}"),
+			Self::Nested(at) => write!(f, "Nested cloze deletion at character {at}"),
+			Self::Number(at) => write!(
+				f,
+				"Cloze deletion at character {at} should start with a number like `c1::`"
+			),
+			Self::EmptyAnswer(at) => write!(f, "Cloze deletion at character {at} is empty"),
+			Self::NoDeletions => f.write_str("Cloze text has no deletions"),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for (StatusCode, String) {
+	fn from(err: Error) -> Self {
+		(StatusCode::UNPROCESSABLE_ENTITY, err.to_string())
+	}
+}
+
+/// `c<number>`, with a number of at least 1.
+fn number(label: &str) -> Option<u32> {
+	let digits = label.strip_prefix('c')?;
+	if !digits.bytes().all(|b| b.is_ascii_digit()) {
+		return None;
+	}
+	digits.parse().ok().filter(|&n| n > 0)
+}
+
+pub fn parse(text: &str) -> Result<Vec<Part<'_>>, Error> {
+	let at = |byte: usize| text[..byte].chars().count() + 1;
+
+	let mut parts = Vec::new();
+	let mut offset = 0;
+	while offset < text.len() {
+		let rest = &text[offset..];
+		let open = rest.find(OPEN);
+		if let Some(close) = rest.find(CLOSE) {
+			if open.is_none_or(|open| close < open) {
+				return Err(Error::Unopened(at(offset + close)));
+			}
+		}
+		let Some(open) = open else {
+			parts.push(Part::Text(rest));
+			break;
+		};
+		if open > 0 {
+			parts.push(Part::Text(&rest[..open]));
+		}
+
+		let start = offset + open;
+		let body = &rest[open + OPEN.len()..];
+		let end = body.find(CLOSE).ok_or(Error::Unclosed(at(start)))?;
+		let inner = &body[..end];
+		if inner.contains(OPEN) {
+			return Err(Error::Nested(at(start)));
+		}
+
+		let (label, deletion) = inner
+			.split_once(SEPARATOR)
+			.ok_or(Error::Number(at(start)))?;
+		let number = number(label).ok_or(Error::Number(at(start)))?;
+		let (answer, hint) = match deletion.split_once(SEPARATOR) {
+			Some((answer, hint)) => (answer, Some(hint)),
+			None => (deletion, None),
+		};
+		if answer.trim().is_empty() {
+			return Err(Error::EmptyAnswer(at(start)));
+		}
+		parts.push(Part::Deletion {
+			number,
+			answer,
+			hint,
+		});
+
+		offset = start + OPEN.len() + end + CLOSE.len();
+	}
+
+	if !parts.iter().any(|p| matches!(p, Part::Deletion { .. })) {
+		return Err(Error::NoDeletions);
+	}
+	Ok(parts)
+}
+
+/// Deletion numbers used in the text, in order.
+pub fn numbers(parts: &[Part]) -> BTreeSet<u32> {
+	parts
+		.iter()
+		.filter_map(|part| match part {
+			Part::Deletion { number, .. } => Some(*number),
+			Part::Text(_) => None,
+		})
+		.collect()
+}
+
+/// The text with every gap filled in, or as it is if it doesn't parse.
+pub fn plain(text: &str) -> String {
+	match parse(text) {
+		Ok(parts) => parts
+			.into_iter()
+			.map(|part| match part {
+				Part::Text(text) => text,
+				Part::Deletion { answer, .. } => answer,
+			})
+			.collect(),
+		Err(_) => text.to_string(),
+	}
+}
+
+/// Checks every cloze section of a card.
+pub fn validate(content: &FlashCardContent) -> Result<(), Error> {
+	for section in &content.0 {
+		if let FlashCardSection::Cloze(text) = section {
+			parse(text)?;
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_deletions() {
+		let parts = parse("Kot {{c1::siedzi}} na {{c12::macie::where?}}.").unwrap();
+		assert_eq!(
+			parts,
+			vec![
+				Part::Text("Kot "),
+				Part::Deletion {
+					number: 1,
+					answer: "siedzi",
+					hint: None,
+				},
+				Part::Text(" na "),
+				Part::Deletion {
+					number: 12,
+					answer: "macie",
+					hint: Some("where?"),
+				},
+				Part::Text("."),
+			]
+		);
+		assert_eq!(numbers(&parts), BTreeSet::from([1, 12]));
+		assert_eq!(
+			numbers(&parse("{{c2::a}}{{c1::b}}{{c2::c}}").unwrap()),
+			BTreeSet::from([1, 2])
+		);
+	}
+
+	#[test]
+	fn rejects_malformed_markup() {
+		assert_eq!(parse("Kot"), Err(Error::NoDeletions));
+		assert_eq!(parse(""), Err(Error::NoDeletions));
+		assert_eq!(parse("żółw {{c1::x"), Err(Error::Unclosed(6)));
+		assert_eq!(parse("żółw }} {{c1::x}}"), Err(Error::Unopened(6)));
+		assert_eq!(parse("{{c1::a {{c2::b}} c}}"), Err(Error::Nested(1)));
+		for label in ["c0", "1", "c", "c+1", "x1"] {
+			assert_eq!(
+				parse(&format!("a {{{{{label}::b}}}}")),
+				Err(Error::Number(3)),
+				"{label}"
+			);
+		}
+		assert_eq!(parse("a {{c1}}"), Err(Error::Number(3)));
+		assert_eq!(parse("a {{c1:: ::hint}}"), Err(Error::EmptyAnswer(3)));
+	}
+
+	#[test]
+	fn fills_in_gaps() {
+		assert_eq!(
+			plain("Kot {{c1::siedzi}} na {{c2::macie::where?}}."),
+			"Kot siedzi na macie."
+		);
+		assert_eq!(plain("Kot {{c1::"), "Kot {{c1::");
+	}
+}
@@ -5,6 +5,12 @@ pub struct AppConfig {
 	pub public_url: String,
 	pub listen_addr: String,
 	pub db_url: String,
+	/// Directory uploaded media are stored in, unless they go to S3.
+	pub media_dir: String,
+	pub s3: Option<S3Config>,
+	/// eSpeak NG program to speak pronunciations nobody recorded with.
+	/// Without one, nothing is spoken.
+	pub espeak: Option<String>,
 	pub app_id: String,
 	pub app_fingerprints: Vec<String>,
 }
@@ -16,6 +22,9 @@ impl AppConfig {
 				.unwrap_or(String::from("http://localhost:3000")),
 			listen_addr: var("FLASHMIND_LISTEN_ADDR").unwrap_or(String::from("[::]:3000")),
 			db_url: var("FLASHMIND_DB_URL").expect("You must provide a database url."),
+			media_dir: var("FLASHMIND_MEDIA_DIR").unwrap_or(String::from("media")),
+			s3: S3Config::from_env(),
+			espeak: var("FLASHMIND_ESPEAK").ok(),
 			app_id: var("FLASHMIND_APP_ID").unwrap_or("io.github.m00nwtchr.flashmind".to_string()),
 			app_fingerprints: vars()
 				.filter(|(k, _)| k.starts_with("FLASHMIND_APP_FINGERPRINT"))
@@ -31,8 +40,40 @@ impl Default for AppConfig {
 			public_url: String::from("http://localhost:3000"),
 			listen_addr: String::from("[::]:3000"),
 			db_url: String::from("sqlite::memory:"),
+			media_dir: String::from("media"),
+			s3: None,
+			espeak: None,
 			app_id: "io.github.m00nwtchr.flashmind".to_string(),
 			app_fingerprints: Vec::new(),
 		}
 	}
 }
+
+/// An S3-compatible bucket to store media in.
+#[derive(Clone)]
+pub struct S3Config {
+	/// Where the service is, for anything other than AWS.
+	pub endpoint: Option<String>,
+	pub region: String,
+	pub bucket: String,
+	/// Keys to sign requests with, looked up like the AWS tools do if unset.
+	pub access_key: Option<String>,
+	pub secret_key: Option<String>,
+	/// Whether the bucket goes in the path rather than the host name, as
+	/// MinIO and most other stand-ins need.
+	pub path_style: bool,
+}
+
+impl S3Config {
+	/// The bucket set in the environment, if there is one.
+	pub fn from_env() -> Option<Self> {
+		Some(Self {
+			endpoint: var("FLASHMIND_S3_ENDPOINT").ok(),
+			region: var("FLASHMIND_S3_REGION").unwrap_or(String::from("us-east-1")),
+			bucket: var("FLASHMIND_S3_BUCKET").ok()?,
+			access_key: var("FLASHMIND_S3_ACCESS_KEY").ok(),
+			secret_key: var("FLASHMIND_S3_SECRET_KEY").ok(),
+			path_style: var("FLASHMIND_S3_PATH_STYLE").is_ok_and(|v| v == "true" || v == "1"),
+		})
+	}
+}
@@ -0,0 +1,302 @@
+//! Flashmind deck bundles (`.fmdeck`).
+//!
+//! A bundle is a zip archive holding:
+//! - `manifest.json`: the format name and version, and the media type of each
+//!   media file;
+//! - `deck.json`: the deck and its cards, with their original ids;
+//! - `media/`: images and recordings, referred to by cards as `media/<file>`.
+//!
+//! Media stored on the instance is packed into the bundle; links to other
+//! sites are kept as they are.
+
+use std::{
+	collections::BTreeMap,
+	fmt::{Display, Formatter},
+	io::{Cursor, Read, Write},
+};
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use entity::{
+	custom::flash_card::FlashCardContent,
+	deck, flash_card,
+	sea_orm_active_enums::{Kind, Share},
+};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use uuid::Uuid;
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+use crate::media::{self, data_url, extension, hex, parse_data_url};
+
+pub const FORMAT: &str = "flashmind-deck";
+/// Newest bundle version this instance writes and reads.
+pub const VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.json";
+const DECK: &str = "deck.json";
+const MEDIA_DIR: &str = "media/";
+/// Largest `manifest.json` or `deck.json` unpacked. Zip headers can claim any
+/// size, so this is counted while unpacking.
+const MAX_JSON_SIZE: u64 = 256 * 1024 * 1024;
+/// Largest total of media files unpacked, each of which is also held to
+/// [`media::MAX_SIZE`].
+const MAX_MEDIA_TOTAL: usize = 1024 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+	pub format: String,
+	pub version: u32,
+	pub exported_at: DateTime<Utc>,
+	/// Media type of each file under `media/`.
+	#[serde(default)]
+	pub media: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Card {
+	pub uid: Uuid,
+	pub share: Share,
+	pub content: FlashCardContent,
+}
+
+/// A card of a bundle that could not be imported.
+#[derive(Debug, Serialize)]
+pub struct Failure {
+	pub card: Uuid,
+	pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Deck {
+	pub uid: Uuid,
+	pub name: String,
+	pub kind: Kind,
+	pub share: Share,
+	pub cards: Vec<Card>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Zip(zip::result::ZipError),
+	Io(std::io::Error),
+	Json(serde_json::Error),
+	Version(u32),
+	Invalid(&'static str),
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Zip(err) => write!(f, "Invalid bundle: {err}"),
+			Self::Io(err) => err.fmt(f),
+			Self::Json(err) => write!(f, "Invalid bundle: {err}"),
+			Self::Version(version) => write!(
+				f,
+				"Bundle version {version} is newer than the supported version {VERSION}"
+			),
+			Self::Invalid(msg) => f.write_str(msg),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<zip::result::ZipError> for Error {
+	fn from(err: zip::result::ZipError) -> Self {
+		Self::Zip(err)
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(err: std::io::Error) -> Self {
+		Self::Io(err)
+	}
+}
+
+impl From<serde_json::Error> for Error {
+	fn from(err: serde_json::Error) -> Self {
+		Self::Json(err)
+	}
+}
+
+impl From<Error> for (StatusCode, String) {
+	fn from(err: Error) -> Self {
+		match err {
+			Error::Io(_) => crate::internal_error(err),
+			_ => (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()),
+		}
+	}
+}
+
+/// Writes a deck and its cards as a bundle.
+pub fn write(deck: &deck::Model, cards: &[flash_card::Model]) -> Result<Vec<u8>, Error> {
+	let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+	let options = FileOptions::default();
+
+	let mut media = BTreeMap::new();
+	let mut bundled = Deck {
+		uid: deck.uid,
+		name: deck.name.clone(),
+		kind: deck.kind,
+		share: deck.share,
+		cards: Vec::with_capacity(cards.len()),
+	};
+	for card in cards {
+		let mut content = card.content.clone();
+		for url in content.items_mut().filter_map(|i| i.media_url_mut()) {
+			let Some((mime, data)) = parse_data_url(url) else {
+				continue;
+			};
+			let name = format!(
+				"{MEDIA_DIR}{}.{}",
+				hex(&Sha1::digest(&data)),
+				extension(mime)
+			);
+			if !media.contains_key(&name) {
+				zip.start_file(&name, options)?;
+				zip.write_all(&data)?;
+				media.insert(name.clone(), mime.to_string());
+			}
+			*url = name;
+		}
+
+		bundled.cards.push(Card {
+			uid: card.uid,
+			share: card.share,
+			content,
+		});
+	}
+
+	zip.start_file(DECK, options)?;
+	serde_json::to_writer(&mut zip, &bundled)?;
+
+	zip.start_file(MANIFEST, options)?;
+	serde_json::to_writer_pretty(
+		&mut zip,
+		&Manifest {
+			format: FORMAT.to_string(),
+			version: VERSION,
+			exported_at: Utc::now(),
+			media,
+		},
+	)?;
+
+	Ok(zip.finish()?.into_inner())
+}
+
+/// Reads all of `file` unless it is longer than `limit`.
+fn read_at_most(file: impl Read, limit: u64, too_large: &'static str) -> Result<Vec<u8>, Error> {
+	let mut data = Vec::new();
+	file.take(limit + 1).read_to_end(&mut data)?;
+	if data.len() as u64 > limit {
+		return Err(Error::Invalid(too_large));
+	}
+	Ok(data)
+}
+
+/// Reads a bundle, with media inlined back into the cards.
+///
+/// Ids are as they were on the exporting instance.
+pub fn read(data: &[u8]) -> Result<Deck, Error> {
+	let mut zip = ZipArchive::new(Cursor::new(data))?;
+
+	let manifest: Manifest = match zip.by_name(MANIFEST) {
+		Ok(file) => {
+			serde_json::from_slice(&read_at_most(file, MAX_JSON_SIZE, "Manifest is too large")?)?
+		}
+		Err(_) => return Err(Error::Invalid("Not a flashmind deck bundle")),
+	};
+	if manifest.format != FORMAT {
+		return Err(Error::Invalid("Not a flashmind deck bundle"));
+	}
+	if manifest.version > VERSION {
+		return Err(Error::Version(manifest.version));
+	}
+
+	let mut deck: Deck = serde_json::from_slice(&read_at_most(
+		zip.by_name(DECK)?,
+		MAX_JSON_SIZE,
+		"Deck is too large",
+	)?)?;
+
+	let mut media_total = 0;
+	let mut files: BTreeMap<String, String> = BTreeMap::new();
+	for card in &mut deck.cards {
+		for url in card.content.items_mut().filter_map(|i| i.media_url_mut()) {
+			if !url.starts_with(MEDIA_DIR) {
+				continue;
+			}
+			if let Some(inlined) = files.get(url.as_str()) {
+				*url = inlined.clone();
+				continue;
+			}
+
+			let mime = manifest
+				.media
+				.get(url.as_str())
+				.ok_or(Error::Invalid("Bundle is missing a media file"))?;
+			let data = read_at_most(
+				zip.by_name(url)
+					.map_err(|_| Error::Invalid("Bundle is missing a media file"))?,
+				media::MAX_SIZE as u64,
+				"Bundle has a media file that is too large",
+			)?;
+			media_total += data.len();
+			if media_total > MAX_MEDIA_TOTAL {
+				return Err(Error::Invalid("Bundle has too much media"));
+			}
+
+			let inlined = data_url(mime, &data);
+			files.insert(std::mem::replace(url, inlined.clone()), inlined);
+		}
+	}
+
+	Ok(deck)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Cursor, Write};
+
+	use serde_json::json;
+	use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+	use super::{read, Error, DECK, FORMAT, MANIFEST, VERSION};
+	use crate::media;
+
+	#[test]
+	fn counts_what_it_unpacks() {
+		let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+		let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+		zip.start_file(MANIFEST, options).unwrap();
+		let manifest = json!({
+			"format": FORMAT,
+			"version": VERSION,
+			"exported_at": "2024-01-01T00:00:00Z",
+			"media": {"media/big.png": "image/png"},
+		});
+		zip.write_all(manifest.to_string().as_bytes()).unwrap();
+		zip.start_file(DECK, options).unwrap();
+		let deck = json!({
+			"uid": uuid::Uuid::nil(),
+			"name": "Big",
+			"kind": "Language",
+			"share": "Public",
+			"cards": [{
+				"uid": uuid::Uuid::nil(),
+				"share": "Public",
+				"content": [{"type": "Item", "content": {"image": "media/big.png"}}],
+			}],
+		});
+		zip.write_all(deck.to_string().as_bytes()).unwrap();
+		zip.start_file("media/big.png", options).unwrap();
+		zip.write_all(&vec![0; media::MAX_SIZE + 1]).unwrap();
+		let bundle = zip.finish().unwrap().into_inner();
+
+		assert!(matches!(
+			read(&bundle),
+			Err(Error::Invalid("Bundle has a media file that is too large"))
+		));
+	}
+}
@@ -0,0 +1,144 @@
+//! Uploaded pictures: re-encoding them without their metadata, and the WebP
+//! renditions cards can ask for instead.
+//!
+//! Decoding and encoding again is what strips EXIF data, GPS coordinates
+//! included. The orientation it records is applied to the pixels first.
+
+use std::io::Cursor;
+
+use entity::sea_orm_active_enums::MediaSize;
+use image::{
+	codecs::{jpeg::JpegEncoder, png::PngEncoder},
+	error::{EncodingError, ImageFormatHint},
+	imageops::FilterType,
+	DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageReader, Limits,
+};
+
+/// Longest side of a picture, the most WebP can hold.
+const MAX_DIMENSION: u32 = 16_383;
+
+/// Memory a picture may take up decoded.
+const MAX_ALLOC: u64 = 512 * 1024 * 1024;
+
+const JPEG_QUALITY: u8 = 90;
+const WEBP_QUALITY: f32 = 80.0;
+
+/// Whether pictures of a media type are processed. Animations and vector
+/// graphics are kept as they are.
+pub fn processable(mime: &str) -> bool {
+	matches!(mime, "image/jpeg" | "image/png" | "image/webp")
+}
+
+pub struct Encoded {
+	pub data: Vec<u8>,
+	pub mime: &'static str,
+	pub width: u32,
+	pub height: u32,
+}
+
+pub struct Processed {
+	/// The picture in its own format, without metadata.
+	pub original: Encoded,
+	pub variants: Vec<(MediaSize, Encoded)>,
+}
+
+/// Longest side of a variant, `None` for the full size.
+fn bound(size: MediaSize) -> Option<u32> {
+	match size {
+		MediaSize::Thumb => Some(256),
+		MediaSize::Card => Some(1024),
+		MediaSize::Full => None,
+	}
+}
+
+fn decode(data: &[u8]) -> Result<DynamicImage, ImageError> {
+	let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+	let mut limits = Limits::default();
+	limits.max_image_width = Some(MAX_DIMENSION);
+	limits.max_image_height = Some(MAX_DIMENSION);
+	limits.max_alloc = Some(MAX_ALLOC);
+	reader.limits(limits);
+
+	let mut decoder = reader.into_decoder()?;
+	let orientation = decoder.orientation()?;
+	let mut image = DynamicImage::from_decoder(decoder)?;
+	image.apply_orientation(orientation);
+	Ok(image)
+}
+
+fn encoded(image: &DynamicImage, mime: &'static str, data: Vec<u8>) -> Encoded {
+	Encoded {
+		data,
+		mime,
+		width: image.width(),
+		height: image.height(),
+	}
+}
+
+fn jpeg(image: &DynamicImage) -> Result<Encoded, ImageError> {
+	let mut data = Vec::new();
+	DynamicImage::ImageRgb8(image.to_rgb8())
+		.write_with_encoder(JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY))?;
+	Ok(encoded(image, "image/jpeg", data))
+}
+
+fn png(image: &DynamicImage) -> Result<Encoded, ImageError> {
+	let mut data = Vec::new();
+	image.write_with_encoder(PngEncoder::new(&mut data))?;
+	Ok(encoded(image, "image/png", data))
+}
+
+fn webp(image: &DynamicImage) -> Result<Encoded, ImageError> {
+	let (width, height) = (image.width(), image.height());
+	let alpha = image.color().has_alpha();
+	let pixels = if alpha {
+		image.to_rgba8().into_raw()
+	} else {
+		image.to_rgb8().into_raw()
+	};
+	let encoder = if alpha {
+		webp::Encoder::from_rgba(&pixels, width, height)
+	} else {
+		webp::Encoder::from_rgb(&pixels, width, height)
+	};
+	let data = encoder.encode_simple(false, WEBP_QUALITY).map_err(|err| {
+		ImageError::Encoding(EncodingError::new(
+			ImageFormatHint::Exact(ImageFormat::WebP),
+			format!("{err:?}"),
+		))
+	})?;
+	Ok(encoded(image, "image/webp", data.to_vec()))
+}
+
+/// Strips a picture of its metadata and renders its variants.
+pub fn process(data: &[u8], mime: &str) -> Result<Processed, ImageError> {
+	let image = decode(data)?;
+	let original = match mime {
+		"image/jpeg" => jpeg(&image)?,
+		"image/png" => png(&image)?,
+		_ => webp(&image)?,
+	};
+
+	// Largest first, so a picture smaller than a variant is only encoded once.
+	let mut variants: Vec<(MediaSize, Encoded)> = Vec::new();
+	for size in [MediaSize::Full, MediaSize::Card, MediaSize::Thumb] {
+		let resized = match bound(size) {
+			Some(bound) if image.width() > bound || image.height() > bound => {
+				image.resize(bound, bound, FilterType::Triangle)
+			}
+			_ => image.clone(),
+		};
+		let variant = match variants.last() {
+			Some((_, last)) if (last.width, last.height) == (resized.width(), resized.height()) => {
+				Encoded {
+					data: last.data.clone(),
+					..*last
+				}
+			}
+			_ => webp(&resized)?,
+		};
+		variants.push((size, variant));
+	}
+
+	Ok(Processed { original, variants })
+}
@@ -0,0 +1,385 @@
+//! Pronunciation items: checking their IPA and converting X-SAMPA to it.
+//!
+//! X-SAMPA spells IPA in ASCII, so that it can be typed on any keyboard. A
+//! transcription without any other characters is taken to be X-SAMPA; for the
+//! ASCII letters IPA uses, both mean the same.
+
+use std::fmt::{Display, Formatter};
+
+use axum::http::StatusCode;
+use entity::custom::flash_card::{FlashCardContent, FlashCardItem};
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest transcription, in characters.
+pub const MAX_LENGTH: usize = 500;
+
+/// Letters, suprasegmentals, tones and diacritics of the IPA chart, along with
+/// the delimiters and spaces transcriptions are written with.
+const SYMBOLS: &str = concat!(
+	"abcdefghijklmnopqrstuvwxyz",
+	"ɐɑɒæɓʙβɔɕçɗɖðəɘɚɛɜɝɞɟʄɡɠɢʛɦɧħɥʜɨɪʝɭɬɫɮʟɱɯɰŋɳɲɴøɵɸθœɶʘɹɺɾɻʀʁɽʂʃʈʉʊʋⱱʌɣɤʍχʎʏʑʐʒʔʡʕʢ",
+	"ǀǁǂǃʦʣʧʤʨʥʩʪʫᵻᵿ",
+	"ˈˌːˑ.|‖‿↗↘ꜛꜜ˥˦˧˨˩",
+	"ʰʱʷʲˠˤⁿˡᵊᶿˣʼ˞",
+	"\u{0300}\u{0301}\u{0302}\u{0303}\u{0304}\u{0306}\u{0308}\u{030A}\u{030B}\u{030C}\u{030D}",
+	"\u{030F}\u{0311}\u{0318}\u{0319}\u{031A}\u{031C}\u{031D}\u{031E}\u{031F}\u{0320}\u{0324}",
+	"\u{0325}\u{0329}\u{032A}\u{032C}\u{032F}\u{0330}\u{0334}\u{0339}\u{033A}\u{033B}\u{033C}",
+	"\u{033D}\u{035C}\u{0361}\u{1DC4}\u{1DC5}\u{1DC8}",
+	" /[]()",
+);
+
+/// X-SAMPA symbols that aren't the same in IPA, and their IPA.
+const XSAMPA: &[(&str, &str)] = &[
+	("b_<", "ɓ"),
+	("d`", "ɖ"),
+	("d_<", "ɗ"),
+	("g", "ɡ"),
+	("g_<", "ɠ"),
+	("h\\", "ɦ"),
+	("j\\", "ʝ"),
+	("l`", "ɭ"),
+	("l\\", "ɺ"),
+	("n`", "ɳ"),
+	("p\\", "ɸ"),
+	("r`", "ɽ"),
+	("r\\", "ɹ"),
+	("r\\`", "ɻ"),
+	("s`", "ʂ"),
+	("s\\", "ɕ"),
+	("t`", "ʈ"),
+	("v\\", "ʋ"),
+	("x\\", "ɧ"),
+	("z`", "ʐ"),
+	("z\\", "ʑ"),
+	("A", "ɑ"),
+	("B", "β"),
+	("B\\", "ʙ"),
+	("C", "ç"),
+	("D", "ð"),
+	("E", "ɛ"),
+	("F", "ɱ"),
+	("G", "ɣ"),
+	("G\\", "ɢ"),
+	("G\\_<", "ʛ"),
+	("H", "ɥ"),
+	("H\\", "ʜ"),
+	("I", "ɪ"),
+	("I\\", "ᵻ"),
+	("J", "ɲ"),
+	("J\\", "ɟ"),
+	("J\\_<", "ʄ"),
+	("K", "ɬ"),
+	("K\\", "ɮ"),
+	("L", "ʎ"),
+	("L\\", "ʟ"),
+	("M", "ɯ"),
+	("M\\", "ɰ"),
+	("N", "ŋ"),
+	("N\\", "ɴ"),
+	("O", "ɔ"),
+	("O\\", "ʘ"),
+	("P", "ʋ"),
+	("Q", "ɒ"),
+	("R", "ʁ"),
+	("R\\", "ʀ"),
+	("S", "ʃ"),
+	("T", "θ"),
+	("U", "ʊ"),
+	("U\\", "ᵿ"),
+	("V", "ʌ"),
+	("W", "ʍ"),
+	("X", "χ"),
+	("X\\", "ħ"),
+	("Y", "ʏ"),
+	("Z", "ʒ"),
+	("\"", "ˈ"),
+	("%", "ˌ"),
+	("'", "ʲ"),
+	(":", "ː"),
+	(":\\", "ˑ"),
+	("@", "ə"),
+	("@\\", "ɘ"),
+	("@`", "ɚ"),
+	("{", "æ"),
+	("}", "ʉ"),
+	("1", "ɨ"),
+	("2", "ø"),
+	("3", "ɜ"),
+	("3\\", "ɞ"),
+	("4", "ɾ"),
+	("5", "ɫ"),
+	("6", "ɐ"),
+	("7", "ɤ"),
+	("8", "ɵ"),
+	("9", "œ"),
+	("&", "ɶ"),
+	("?", "ʔ"),
+	("?\\", "ʕ"),
+	("<\\", "ʢ"),
+	(">\\", "ʡ"),
+	("^", "ꜛ"),
+	("!", "ꜜ"),
+	("!\\", "ǃ"),
+	("|\\", "ǀ"),
+	("||", "‖"),
+	("|\\|\\", "ǁ"),
+	("=\\", "ǂ"),
+	("-\\", "‿"),
+	// A separator between symbols that would otherwise read as one.
+	("-", ""),
+	("`", "˞"),
+	("~", "\u{0303}"),
+	("=", "\u{0329}"),
+	("_", "\u{0361}"),
+	("_\"", "\u{0308}"),
+	("_+", "\u{031F}"),
+	("_-", "\u{0320}"),
+	("_/", "\u{030C}"),
+	("_0", "\u{0325}"),
+	("_=", "\u{0329}"),
+	("_>", "ʼ"),
+	("_?\\", "ˤ"),
+	("_\\", "\u{0302}"),
+	("_^", "\u{032F}"),
+	("_}", "\u{031A}"),
+	("_~", "\u{0303}"),
+	("_A", "\u{0318}"),
+	("_a", "\u{033A}"),
+	("_B", "\u{030F}"),
+	("_B_L", "\u{1DC5}"),
+	("_c", "\u{031C}"),
+	("_d", "\u{032A}"),
+	("_e", "\u{0334}"),
+	("_F", "\u{0302}"),
+	("_G", "ˠ"),
+	("_H", "\u{0301}"),
+	("_H_T", "\u{1DC4}"),
+	("_h", "ʰ"),
+	("_j", "ʲ"),
+	("_k", "\u{0330}"),
+	("_L", "\u{0300}"),
+	("_l", "ˡ"),
+	("_M", "\u{0304}"),
+	("_m", "\u{033B}"),
+	("_N", "\u{033C}"),
+	("_n", "ⁿ"),
+	("_O", "\u{0339}"),
+	("_o", "\u{031E}"),
+	("_q", "\u{0319}"),
+	("_R", "\u{030C}"),
+	("_R_F", "\u{1DC8}"),
+	("_r", "\u{031D}"),
+	("_T", "\u{030B}"),
+	("_t", "\u{0324}"),
+	("_v", "\u{032C}"),
+	("_w", "ʷ"),
+	("_X", "\u{0306}"),
+	("_x", "\u{033D}"),
+];
+
+/// Longest X-SAMPA symbol, in bytes.
+const LONGEST: usize = 4;
+
+#[derive(Debug)]
+pub enum Error {
+	TooLong,
+	Invalid(Vec<char>),
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::TooLong => write!(
+				f,
+				"Transcriptions can't be longer than {MAX_LENGTH} characters"
+			),
+			Self::Invalid(chars) => {
+				f.write_str("Not IPA or X-SAMPA:")?;
+				for c in chars {
+					write!(f, " \"{c}\" (U+{:04X})", u32::from(*c))?;
+				}
+				Ok(())
+			}
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for (StatusCode, String) {
+	fn from(err: Error) -> Self {
+		(StatusCode::UNPROCESSABLE_ENTITY, err.to_string())
+	}
+}
+
+/// IPA spelled by X-SAMPA `text`. Anything that isn't X-SAMPA is kept.
+pub fn from_xsampa(text: &str) -> String {
+	let mut ipa = String::with_capacity(text.len());
+	let mut rest = text;
+	while let Some(c) = rest.chars().next() {
+		let symbol = (1..=LONGEST.min(rest.len())).rev().find_map(|len| {
+			let symbol = rest.get(..len)?;
+			XSAMPA
+				.iter()
+				.find(|(xsampa, _)| *xsampa == symbol)
+				.map(|(_, ipa)| (len, *ipa))
+		});
+		match symbol {
+			Some((len, symbol)) => {
+				ipa.push_str(symbol);
+				rest = &rest[len..];
+			}
+			None => {
+				ipa.push(c);
+				rest = &rest[c.len_utf8()..];
+			}
+		}
+	}
+	ipa
+}
+
+/// IPA of a transcription typed in IPA or, when it is all ASCII, X-SAMPA.
+pub fn convert(text: &str) -> String {
+	if text.is_ascii() {
+		from_xsampa(text)
+	} else {
+		text.to_string()
+	}
+}
+
+/// Characters of `ipa` that aren't IPA, each once.
+///
+/// Letters with diacritics count as IPA whether they come precomposed or not.
+pub fn invalid(ipa: &str) -> Vec<char> {
+	let mut invalid = Vec::new();
+	for c in ipa.chars() {
+		if !SYMBOLS.contains(c)
+			&& !std::iter::once(c).nfd().all(|c| SYMBOLS.contains(c))
+			&& !invalid.contains(&c)
+		{
+			invalid.push(c);
+		}
+	}
+	invalid
+}
+
+/// IPA of a transcription, checked.
+pub fn transcribe(text: &str) -> Result<String, Error> {
+	if text.chars().count() > MAX_LENGTH {
+		return Err(Error::TooLong);
+	}
+	let ipa = convert(text.trim());
+	let invalid = invalid(&ipa);
+	if invalid.is_empty() {
+		Ok(ipa)
+	} else {
+		Err(Error::Invalid(invalid))
+	}
+}
+
+/// Converts the X-SAMPA transcriptions of a card to IPA and checks them all.
+pub fn normalize(content: &mut FlashCardContent) -> Result<(), Error> {
+	for item in content.items_mut() {
+		if let FlashCardItem::Pronunciation { ipa, .. } = item {
+			*ipa = transcribe(ipa)?;
+		}
+	}
+	Ok(())
+}
+
+/// Like [`normalize`], but leaves the transcriptions `old` already has as they
+/// are, so that cards saved before transcriptions were checked stay editable.
+pub fn normalize_changed(
+	content: &mut FlashCardContent,
+	old: &FlashCardContent,
+) -> Result<(), Error> {
+	let kept: Vec<&str> = old
+		.items()
+		.filter_map(|item| match item {
+			FlashCardItem::Pronunciation { ipa, .. } => Some(ipa.as_str()),
+			_ => None,
+		})
+		.collect();
+	for item in content.items_mut() {
+		if let FlashCardItem::Pronunciation { ipa, .. } = item {
+			if !kept.contains(&ipa.as_str()) {
+				*ipa = transcribe(ipa)?;
+			}
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use entity::custom::flash_card::FlashCardSection;
+
+	use super::*;
+
+	fn card(ipa: &[&str]) -> FlashCardContent {
+		FlashCardContent(
+			ipa.iter()
+				.map(|ipa| {
+					FlashCardSection::Item(FlashCardItem::Pronunciation {
+						ipa: ipa.to_string(),
+						audio_url: None,
+					})
+				})
+				.collect(),
+		)
+	}
+
+	#[test]
+	fn converts_xsampa() {
+		for (xsampa, ipa) in [
+			("h@\"l@U", "həˈləʊ"),
+			("kot", "kot"),
+			("r\\`", "ɻ"),
+			("r\\", "ɹ"),
+			("t_hE_~", "tʰɛ\u{0303}"),
+			("t-S", "tʃ"),
+			("|\\|\\ ||", "ǁ ‖"),
+			("[\"kOt]", "[ˈkɔt]"),
+		] {
+			assert_eq!(from_xsampa(xsampa), ipa, "{xsampa}");
+		}
+		assert_eq!(convert("hə'ləʊ"), "hə'ləʊ");
+		assert_eq!(convert("h@'l@U"), "həʲləʊ");
+	}
+
+	#[test]
+	fn finds_invalid_characters() {
+		assert_eq!(invalid("/ˈkɔt/ [tʰɛ̃]"), Vec::<char>::new());
+		// Precomposed letters count as their letter and diacritic.
+		assert_eq!(invalid("ä ẽ"), Vec::<char>::new());
+		assert_eq!(invalid("hə'lo'ł"), ['\'', 'ł']);
+		assert_eq!(invalid("KOT"), ['K', 'O', 'T']);
+	}
+
+	#[test]
+	fn transcribes() {
+		assert_eq!(transcribe("  h@\"l@U ").unwrap(), "həˈləʊ");
+		assert!(matches!(
+			transcribe("hə'ləʊ"),
+			Err(Error::Invalid(chars)) if chars == ['\'']
+		));
+		assert!(matches!(
+			transcribe(&"a".repeat(MAX_LENGTH + 1)),
+			Err(Error::TooLong)
+		));
+	}
+
+	#[test]
+	fn normalizes_changed_transcriptions() {
+		let mut content = card(&["k{t", "kot"]);
+		normalize(&mut content).unwrap();
+		assert_eq!(content, card(&["kæt", "kot"]));
+
+		let old = card(&["hə'ləʊ"]);
+		let mut content = card(&["hə'ləʊ", "d{g"]);
+		normalize_changed(&mut content, &old).unwrap();
+		assert_eq!(content, card(&["hə'ləʊ", "dæɡ"]));
+		let mut content = card(&["hə'lo"]);
+		assert!(normalize_changed(&mut content, &old).is_err());
+	}
+}
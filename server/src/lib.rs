@@ -1,11 +1,29 @@
 use axum::http::StatusCode;
 
+pub mod anki;
+pub mod answer;
 pub mod app;
+pub mod card_query;
+pub mod cloze;
 pub mod config;
 pub mod db;
+pub mod fmdeck;
+pub mod images;
+pub mod ipa;
+pub mod markdown;
+pub mod math;
+pub mod media;
 pub mod oidc;
+pub mod quiz;
+pub mod render;
 pub mod route;
+pub mod scheduler;
 pub mod session;
+pub mod storage;
+pub mod tabular;
+#[cfg(test)]
+mod testing;
+pub mod tts;
 
 pub mod prelude {
 	pub use crate::{app::app, config::AppConfig};
@@ -0,0 +1,130 @@
+//! Markdown items: keeping their source safe to show to other users, and
+//! rendering it to HTML.
+//!
+//! Tables and strikethrough are supported on top of CommonMark. Raw HTML isn't;
+//! it is escaped when a card is saved, so it shows up as text.
+
+use std::fmt::{Display, Formatter};
+
+use axum::http::StatusCode;
+use entity::custom::flash_card::{FlashCardContent, FlashCardItem};
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag};
+
+/// Longest Markdown source, in characters.
+pub const MAX_LENGTH: usize = 10_000;
+
+/// Times raw HTML is escaped before giving up, as escaping can turn what
+/// followed it into HTML of its own.
+const PASSES: usize = 4;
+
+const SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+#[derive(Debug)]
+pub enum Error {
+	TooLong,
+	Html,
+	Link(String),
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::TooLong => write!(f, "Markdown can't be longer than {MAX_LENGTH} characters"),
+			Self::Html => f.write_str("Markdown can't contain HTML"),
+			Self::Link(url) => write!(
+				f,
+				"Link `{url}` has to be relative or use one of {}",
+				SCHEMES.join(", ")
+			),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for (StatusCode, String) {
+	fn from(err: Error) -> Self {
+		(StatusCode::UNPROCESSABLE_ENTITY, err.to_string())
+	}
+}
+
+fn parser(source: &str) -> Parser<'_> {
+	Parser::new_ext(
+		source,
+		Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+	)
+}
+
+/// `source` with a backslash before every `<` of raw HTML, or `None` if it has
+/// none.
+fn escape_html(source: &str) -> Option<String> {
+	let mut escaped = String::with_capacity(source.len());
+	let mut last = 0;
+	for (event, range) in parser(source).into_offset_iter() {
+		if !matches!(event, Event::Html(_) | Event::InlineHtml(_)) || range.start < last {
+			continue;
+		}
+		escaped.push_str(&source[last..range.start]);
+		escaped.push_str(&source[range.clone()].replace('<', "\\<"));
+		last = range.end;
+	}
+	if last == 0 {
+		return None;
+	}
+	escaped.push_str(&source[last..]);
+	Some(escaped)
+}
+
+fn check_url(url: &str) -> Result<(), Error> {
+	let scheme = url
+		.split_once(':')
+		.map(|(scheme, _)| scheme)
+		.filter(|scheme| !scheme.contains(['/', '?', '#']));
+	match scheme {
+		Some(scheme) if !SCHEMES.contains(&scheme.to_lowercase().as_str()) => {
+			Err(Error::Link(url.to_string()))
+		}
+		_ => Ok(()),
+	}
+}
+
+/// Where the links and images of Markdown point.
+pub fn urls(source: &str) -> impl Iterator<Item = CowStr<'_>> {
+	parser(source).filter_map(|event| match event {
+		Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => Some(dest_url),
+		_ => None,
+	})
+}
+
+/// Escapes raw HTML in every Markdown item of a card and checks their links.
+pub fn sanitize(content: &mut FlashCardContent) -> Result<(), Error> {
+	for item in content.items_mut() {
+		let FlashCardItem::Markdown { source, html } = item else {
+			continue;
+		};
+		*html = None;
+		if source.chars().count() > MAX_LENGTH {
+			return Err(Error::TooLong);
+		}
+
+		let mut passes = 0;
+		while let Some(escaped) = escape_html(source) {
+			passes += 1;
+			if passes > PASSES {
+				return Err(Error::Html);
+			}
+			*source = escaped;
+		}
+		for url in urls(source) {
+			check_url(&url)?;
+		}
+	}
+	Ok(())
+}
+
+/// Markdown rendered to HTML, with anything unsafe left out.
+pub fn html(source: &str) -> String {
+	let mut html = String::new();
+	pulldown_cmark::html::push_html(&mut html, parser(source));
+	ammonia::clean(&html)
+}
@@ -0,0 +1,160 @@
+//! Math items: checking their TeX and rendering it to MathML.
+
+use std::fmt::{Display, Formatter};
+
+use axum::http::StatusCode;
+use entity::custom::flash_card::{FlashCardContent, FlashCardItem};
+use pulldown_latex::{
+	config::DisplayMode, mathml::push_mathml, Parser, ParserError, RenderConfig, Storage,
+};
+
+/// Longest formula, in characters.
+pub const MAX_LENGTH: usize = 2_000;
+
+/// Most parser events a formula may expand to, so that macros can't make it
+/// arbitrarily expensive to render.
+const MAX_EVENTS: usize = 20_000;
+
+#[derive(Debug)]
+pub enum Error {
+	TooLong,
+	TooComplex,
+	Invalid(String),
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::TooLong => write!(f, "Formulas can't be longer than {MAX_LENGTH} characters"),
+			Self::TooComplex => f.write_str("Formula is too complex"),
+			Self::Invalid(reason) => write!(f, "Invalid formula: {reason}"),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for (StatusCode, String) {
+	fn from(err: Error) -> Self {
+		(StatusCode::UNPROCESSABLE_ENTITY, err.to_string())
+	}
+}
+
+impl From<ParserError> for Error {
+	fn from(err: ParserError) -> Self {
+		// The first line is the reason, the rest points at where it happened.
+		let message = err.to_string();
+		let reason = message.lines().next().unwrap_or_default();
+		Self::Invalid(
+			reason
+				.strip_prefix("parsing error: ")
+				.unwrap_or(reason)
+				.to_string(),
+		)
+	}
+}
+
+fn check(source: &str) -> Result<(), Error> {
+	if source.chars().count() > MAX_LENGTH {
+		return Err(Error::TooLong);
+	}
+	let storage = Storage::new();
+	for (i, event) in Parser::new(source, &storage).enumerate() {
+		if i >= MAX_EVENTS {
+			return Err(Error::TooComplex);
+		}
+		event?;
+	}
+	Ok(())
+}
+
+/// Checks every math item of a card.
+pub fn validate(content: &FlashCardContent) -> Result<(), Error> {
+	for item in content.items() {
+		if let FlashCardItem::Math { source, .. } = item {
+			check(source)?;
+		}
+	}
+	Ok(())
+}
+
+/// Elements and attributes the renderer writes. Anything else comes from
+/// commands like `\operatorname` that it doesn't escape the argument of.
+const TAGS: [&str; 25] = [
+	"math",
+	"semantics",
+	"mrow",
+	"mi",
+	"mn",
+	"mo",
+	"mtext",
+	"mspace",
+	"mfrac",
+	"mroot",
+	"msqrt",
+	"msub",
+	"msup",
+	"msubsup",
+	"mover",
+	"munder",
+	"munderover",
+	"mtable",
+	"mtr",
+	"mtd",
+	"merror",
+	"mpadded",
+	"mphantom",
+	"mstyle",
+	"menclose",
+];
+const ATTRIBUTES: [&str; 18] = [
+	"display",
+	"mathvariant",
+	"stretchy",
+	"largeop",
+	"movablelimits",
+	"symmetric",
+	"form",
+	"linethickness",
+	"minsize",
+	"maxsize",
+	"lspace",
+	"rspace",
+	"width",
+	"height",
+	"depth",
+	"displaystyle",
+	"scriptlevel",
+	"class",
+];
+const STYLES: [&str; 3] = ["color", "background-color", "border"];
+
+fn clean(mathml: &str) -> String {
+	ammonia::Builder::empty()
+		.add_tags(TAGS)
+		.add_generic_attributes(ATTRIBUTES)
+		.add_generic_attributes(["style"])
+		.filter_style_properties(STYLES.into())
+		.clean(mathml)
+		.to_string()
+}
+
+/// A formula rendered to MathML.
+///
+/// The source isn't added as an annotation, as the renderer doesn't escape it
+/// either.
+pub fn mathml(source: &str, block: bool) -> String {
+	let storage = Storage::new();
+	let config = RenderConfig {
+		display_mode: if block {
+			DisplayMode::Block
+		} else {
+			DisplayMode::Inline
+		},
+		..Default::default()
+	};
+	let mut mathml = String::new();
+	// Writing to a string can't fail.
+	let _ = push_mathml(&mut mathml, Parser::new(source, &storage), config);
+	clean(&mathml)
+}
@@ -0,0 +1,610 @@
+//! Helpers for the images and recordings cards refer to, and the files users
+//! upload for them.
+//!
+//! Uploaded files are kept in a [`Storage`] under the hash of their contents,
+//! with a `media` row for each user that uploaded them. Cards link to them by
+//! [`path`], and `card_media` records which media each card uses. Media no
+//! card uses are deleted by [`collect_garbage`] after a while, and their
+//! contents with the last of them.
+
+use std::{
+	collections::{hash_map::Entry, HashMap, HashSet},
+	fmt::{Display, Formatter},
+	time::Duration,
+};
+
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use image::ImageError;
+use sea_orm::{
+	sea_query::Query, ActiveValue::Set, ColumnTrait, Condition, ConnectionTrait,
+	DatabaseConnection, DbErr, EntityTrait, QueryFilter, QuerySelect, Select, SqlErr,
+};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{images, markdown, storage::Storage};
+use entity::{
+	card_media,
+	custom::flash_card::{FlashCardContent, FlashCardItem},
+	flash_card, media, media_variant,
+	prelude::*,
+	sea_orm_active_enums::Share,
+};
+
+/// Largest file that can be uploaded, in bytes.
+pub const MAX_SIZE: usize = 16 * 1024 * 1024;
+
+/// Where uploaded files are served.
+const PATH: &str = "/api/media/";
+
+/// Media nobody uses are kept this long after being uploaded, so there is
+/// time to save the card they were uploaded for.
+const GRACE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often unused media are looked for.
+const GC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Guesses a media type from a file name, for the media types cards can show.
+pub fn mime_type(name: &str) -> Option<&'static str> {
+	let ext = name.rsplit_once('.')?.1.to_ascii_lowercase();
+	Some(match ext.as_str() {
+		"jpg" | "jpeg" => "image/jpeg",
+		"png" => "image/png",
+		"gif" => "image/gif",
+		"webp" => "image/webp",
+		"svg" => "image/svg+xml",
+		"mp3" => "audio/mpeg",
+		"ogg" | "oga" => "audio/ogg",
+		"opus" => "audio/opus",
+		"wav" => "audio/wav",
+		"m4a" => "audio/mp4",
+		"flac" => "audio/flac",
+		_ => return None,
+	})
+}
+
+/// File extension for one of the media types [`mime_type`] knows.
+pub fn extension(mime: &str) -> &'static str {
+	match mime {
+		"image/jpeg" => "jpg",
+		"image/png" => "png",
+		"image/gif" => "gif",
+		"image/webp" => "webp",
+		"image/svg+xml" => "svg",
+		"audio/mpeg" => "mp3",
+		"audio/ogg" => "ogg",
+		"audio/opus" => "opus",
+		"audio/wav" => "wav",
+		"audio/mp4" => "m4a",
+		"audio/flac" => "flac",
+		_ => "bin",
+	}
+}
+
+pub fn data_url(mime: &str, data: &[u8]) -> String {
+	format!("data:{mime};base64,{}", STANDARD.encode(data))
+}
+
+/// Splits a base64 `data:` URL into its media type and contents.
+pub fn parse_data_url(url: &str) -> Option<(&str, Vec<u8>)> {
+	let (mime, data) = url.strip_prefix("data:")?.split_once(";base64,")?;
+	Some((mime, STANDARD.decode(data).ok()?))
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+	use std::fmt::Write;
+
+	bytes.iter().fold(String::new(), |mut s, b| {
+		let _ = write!(s, "{b:02x}");
+		s
+	})
+}
+
+pub fn hash(data: &[u8]) -> String {
+	hex(&Sha256::digest(data))
+}
+
+/// The media type of a file going by its first bytes, for the media types
+/// cards can show.
+pub fn sniff(data: &[u8]) -> Option<&'static str> {
+	let at = |offset: usize, magic: &[u8]| data.get(offset..offset + magic.len()) == Some(magic);
+	Some(if at(0, b"\xFF\xD8\xFF") {
+		"image/jpeg"
+	} else if at(0, b"\x89PNG\r\n\x1A\n") {
+		"image/png"
+	} else if at(0, b"GIF87a") || at(0, b"GIF89a") {
+		"image/gif"
+	} else if at(0, b"RIFF") && at(8, b"WEBP") {
+		"image/webp"
+	} else if at(0, b"RIFF") && at(8, b"WAVE") {
+		"audio/wav"
+	} else if at(0, b"OggS") && at(28, b"OpusHead") {
+		"audio/opus"
+	} else if at(0, b"OggS") {
+		"audio/ogg"
+	} else if at(0, b"fLaC") {
+		"audio/flac"
+	} else if at(4, b"ftyp") {
+		"audio/mp4"
+	} else if at(0, b"ID3") || matches!(data, [0xFF, b, ..] if b & 0xE0 == 0xE0) {
+		"audio/mpeg"
+	} else if is_svg(data) {
+		"image/svg+xml"
+	} else {
+		return None;
+	})
+}
+
+fn is_svg(data: &[u8]) -> bool {
+	let start = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+	let start = start.trim_start_matches('\u{feff}').trim_start();
+	start.starts_with('<') && start.contains("<svg")
+}
+
+/// Path cards link to an uploaded file by.
+pub fn path(uid: Uuid) -> String {
+	format!("{PATH}{uid}")
+}
+
+/// The uploaded file a link points to, if it does. Links may ask for a
+/// variant of it.
+pub fn linked(url: &str) -> Option<Uuid> {
+	let url = url.strip_prefix(PATH)?;
+	let uid = url.split_once('?').map_or(url, |(uid, _)| uid);
+	Uuid::try_parse(uid).ok()
+}
+
+/// Uploaded files a card shows or links to.
+pub fn used(content: &FlashCardContent) -> HashSet<Uuid> {
+	let mut used = HashSet::new();
+	for item in content.items() {
+		if let FlashCardItem::Markdown { source, .. } = item {
+			used.extend(markdown::urls(source).filter_map(|url| linked(&url)));
+		} else if let Some(uid) = item.media_url().and_then(|url| linked(url)) {
+			used.insert(uid);
+		}
+	}
+	used
+}
+
+#[derive(Debug)]
+pub enum Error {
+	TooLarge,
+	Unsupported,
+	/// A card uses a file that doesn't exist, or that the user can't see.
+	Unknown(Uuid),
+	Image(ImageError),
+	Db(DbErr),
+	Storage(std::io::Error),
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::TooLarge => write!(
+				f,
+				"Files can't be larger than {} MiB",
+				MAX_SIZE / 1024 / 1024
+			),
+			Self::Unsupported => f.write_str("Only images and audio can be uploaded"),
+			Self::Unknown(uid) => write!(f, "There is no media {uid}"),
+			Self::Image(err) => write!(f, "Invalid image: {err}"),
+			Self::Db(err) => err.fmt(f),
+			Self::Storage(err) => err.fmt(f),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<DbErr> for Error {
+	fn from(err: DbErr) -> Self {
+		Self::Db(err)
+	}
+}
+
+impl From<ImageError> for Error {
+	fn from(err: ImageError) -> Self {
+		Self::Image(err)
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(err: std::io::Error) -> Self {
+		Self::Storage(err)
+	}
+}
+
+impl From<Error> for (StatusCode, String) {
+	fn from(err: Error) -> Self {
+		match err {
+			Error::TooLarge => (StatusCode::PAYLOAD_TOO_LARGE, err.to_string()),
+			Error::Unsupported => (StatusCode::UNSUPPORTED_MEDIA_TYPE, err.to_string()),
+			Error::Unknown(_) | Error::Image(_) => {
+				(StatusCode::UNPROCESSABLE_ENTITY, err.to_string())
+			}
+			Error::Db(_) | Error::Storage(_) => crate::internal_error(err),
+		}
+	}
+}
+
+/// Media the user can see: those they uploaded, and those used by cards they
+/// can see.
+pub fn visible(user: u32) -> Condition {
+	let cards = Query::select()
+		.column(flash_card::Column::Uid)
+		.from(FlashCard)
+		.cond_where(
+			flash_card::Column::Share
+				.eq(Share::Public)
+				.or(flash_card::Column::Creator.eq(user)),
+		)
+		.to_owned();
+	let used = Query::select()
+		.column(card_media::Column::Media)
+		.from(CardMedia)
+		.and_where(card_media::Column::Card.in_subquery(cards))
+		.to_owned();
+	Condition::any()
+		.add(media::Column::Owner.eq(user))
+		.add(media::Column::Uid.in_subquery(used))
+}
+
+/// The media of `owner` with contents hashing to `hash`.
+fn uploaded(owner: u32, hash: &str) -> Select<Media> {
+	Media::find()
+		.filter(media::Column::Owner.eq(owner))
+		.filter(media::Column::Hash.eq(hash))
+}
+
+/// Stores a file for `owner`, returning its media and whether it is new. A
+/// file they uploaded before gives the same media again.
+///
+/// Pictures are stored without their metadata, along with their
+/// [`images`] variants.
+pub async fn store(
+	db: &impl ConnectionTrait,
+	storage: &dyn Storage,
+	owner: u32,
+	data: &[u8],
+) -> Result<(media::Model, bool), Error> {
+	if data.len() > MAX_SIZE {
+		return Err(Error::TooLarge);
+	}
+	let mime = sniff(data).ok_or(Error::Unsupported)?;
+	let processed = if images::processable(mime) {
+		let data = data.to_vec();
+		let processed = tokio::task::spawn_blocking(move || images::process(&data, mime))
+			.await
+			.map_err(std::io::Error::other)??;
+		Some(processed)
+	} else {
+		None
+	};
+	let (data, mime) = match &processed {
+		Some(processed) => (processed.original.data.as_slice(), processed.original.mime),
+		None => (data, mime),
+	};
+	let hash = hash(data);
+
+	if let Some(media) = uploaded(owner, &hash).one(db).await? {
+		return Ok((media, false));
+	}
+
+	let media = media::Model {
+		uid: Uuid::new_v4(),
+		owner,
+		hash,
+		mime: mime.to_string(),
+		size: length(data)?,
+		width: processed.as_ref().map(|p| p.original.width),
+		height: processed.as_ref().map(|p| p.original.height),
+		created: Utc::now(),
+	};
+	let variants: Vec<_> = processed
+		.iter()
+		.flat_map(|p| &p.variants)
+		.map(|(size, variant)| (*size, variant, self::hash(&variant.data)))
+		.collect();
+
+	// The rows go in first, as garbage collection leaves the contents of new
+	// media alone.
+	let inserted = Media::insert(media::ActiveModel {
+		uid: Set(media.uid),
+		owner: Set(media.owner),
+		hash: Set(media.hash.clone()),
+		mime: Set(media.mime.clone()),
+		size: Set(media.size),
+		width: Set(media.width),
+		height: Set(media.height),
+		created: Set(media.created),
+	})
+	.exec(db)
+	.await;
+	match inserted {
+		Ok(_) => {}
+		// The same file uploaded at the same time went in first. Its variants
+		// belong to it, so none of this upload's are stored either.
+		Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+			return match uploaded(owner, &media.hash).lock_shared().one(db).await? {
+				Some(media) => Ok((media, false)),
+				None => Err(err.into()),
+			};
+		}
+		Err(err) => return Err(err.into()),
+	}
+	if !variants.is_empty() {
+		let rows = variants
+			.iter()
+			.map(|(size, variant, hash)| {
+				Ok(media_variant::ActiveModel {
+					media: Set(media.uid),
+					variant: Set(*size),
+					hash: Set(hash.clone()),
+					mime: Set(variant.mime.to_string()),
+					size: Set(length(&variant.data)?),
+					width: Set(variant.width),
+					height: Set(variant.height),
+				})
+			})
+			.collect::<Result<Vec<_>, Error>>()?;
+		MediaVariant::insert_many(rows).exec(db).await?;
+	}
+
+	let mut put = storage.put(&media.hash, data).await;
+	for (i, (_, variant, hash)) in variants.iter().enumerate() {
+		if put.is_err() {
+			break;
+		}
+		// Small pictures have the same contents at every size.
+		if variants[..i].iter().all(|(_, _, earlier)| earlier != hash) {
+			put = storage.put(hash, &variant.data).await;
+		}
+	}
+	if let Err(err) = put {
+		Media::delete_by_id(media.uid).exec(db).await?;
+		return Err(err.into());
+	}
+	Ok((media, true))
+}
+
+fn length(data: &[u8]) -> Result<u32, Error> {
+	u32::try_from(data.len()).map_err(|_| Error::TooLarge)
+}
+
+/// Moves files inlined into cards as `data:` URLs into storage, linking to
+/// them instead. Files that can't be uploaded stay inline.
+pub async fn upload_inlined(
+	db: &impl ConnectionTrait,
+	storage: &dyn Storage,
+	owner: u32,
+	cards: &mut [flash_card::Model],
+) -> Result<(), Error> {
+	for card in cards {
+		for item in card.content.items_mut() {
+			let Some(url) = item.media_url_mut() else {
+				continue;
+			};
+			let Some((_, data)) = parse_data_url(url) else {
+				continue;
+			};
+			match store(db, storage, owner, &data).await {
+				Ok((media, _)) => *url = path(media.uid),
+				Err(Error::TooLarge | Error::Unsupported | Error::Image(_)) => {}
+				Err(err) => return Err(err),
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Records which uploaded files the cards use, replacing what they used
+/// before.
+///
+/// Cards can use files `user` can see, so that copies of other users' cards
+/// keep working.
+pub async fn link<'a>(
+	db: &impl ConnectionTrait,
+	user: u32,
+	cards: impl IntoIterator<Item = &'a flash_card::Model>,
+) -> Result<(), Error> {
+	let uses: Vec<(Uuid, HashSet<Uuid>)> = cards
+		.into_iter()
+		.map(|card| (card.uid, used(&card.content)))
+		.collect();
+	let wanted: HashSet<Uuid> = uses.iter().flat_map(|(_, used)| used).copied().collect();
+
+	let found: HashSet<Uuid> = if wanted.is_empty() {
+		HashSet::new()
+	} else {
+		Media::find()
+			.select_only()
+			.column(media::Column::Uid)
+			.filter(media::Column::Uid.is_in(wanted.iter().copied()))
+			.filter(visible(user))
+			.into_tuple()
+			.all(db)
+			.await?
+			.into_iter()
+			.collect()
+	};
+	if let Some(uid) = wanted.difference(&found).next() {
+		return Err(Error::Unknown(*uid));
+	}
+
+	for chunk in uses.chunks(1000) {
+		CardMedia::delete_many()
+			.filter(card_media::Column::Card.is_in(chunk.iter().map(|(card, _)| *card)))
+			.exec(db)
+			.await?;
+	}
+	let links: Vec<_> = uses
+		.iter()
+		.flat_map(|(card, used)| {
+			used.iter().map(|media| card_media::ActiveModel {
+				card: Set(*card),
+				media: Set(*media),
+			})
+		})
+		.collect();
+	for chunk in links.chunks(1000) {
+		CardMedia::insert_many(chunk.iter().cloned())
+			.exec(db)
+			.await?;
+	}
+	Ok(())
+}
+
+/// Inlines the uploaded files cards show as `data:` URLs, for formats that
+/// carry files along with the cards.
+pub async fn inline(
+	db: &impl ConnectionTrait,
+	storage: &dyn Storage,
+	cards: &mut [flash_card::Model],
+) -> Result<(), Error> {
+	let wanted: HashSet<Uuid> = cards
+		.iter()
+		.flat_map(|card| card.content.items())
+		.filter_map(|item| linked(item.media_url()?))
+		.collect();
+	if wanted.is_empty() {
+		return Ok(());
+	}
+	let media: HashMap<Uuid, media::Model> = Media::find()
+		.filter(media::Column::Uid.is_in(wanted))
+		.all(db)
+		.await?
+		.into_iter()
+		.map(|media| (media.uid, media))
+		.collect();
+
+	let mut inlined: HashMap<Uuid, String> = HashMap::new();
+	for card in cards {
+		for item in card.content.items_mut() {
+			let Some(url) = item.media_url_mut() else {
+				continue;
+			};
+			let Some(media) = linked(url).and_then(|uid| media.get(&uid)) else {
+				continue;
+			};
+			let inline = match inlined.entry(media.uid) {
+				Entry::Occupied(entry) => entry.into_mut(),
+				Entry::Vacant(entry) => {
+					// Missing contents leave the link as it is.
+					let Some(data) = storage.get(&media.hash).await? else {
+						continue;
+					};
+					entry.insert(data_url(&media.mime, &data))
+				}
+			};
+			url.clone_from(inline);
+		}
+	}
+	Ok(())
+}
+
+/// Deletes media no card uses that are older than `grace`, and the contents
+/// nothing else has the hash of. Returns how many were deleted.
+pub async fn collect_garbage(
+	db: &DatabaseConnection,
+	storage: &dyn Storage,
+	grace: Duration,
+) -> Result<usize, Error> {
+	let unused = || {
+		media::Column::Uid.not_in_subquery(
+			Query::select()
+				.column(card_media::Column::Media)
+				.from(CardMedia)
+				.to_owned(),
+		)
+	};
+	let cutoff = Utc::now() - grace;
+	let stale = Media::find()
+		.filter(media::Column::Created.lt(cutoff))
+		.filter(unused())
+		.all(db)
+		.await?;
+
+	let mut deleted = 0;
+	for media in stale {
+		let variants = MediaVariant::find()
+			.filter(media_variant::Column::Media.eq(media.uid))
+			.all(db)
+			.await?;
+
+		// A card may have started using it in the meantime. Its variants go
+		// with it.
+		let result = Media::delete_many()
+			.filter(media::Column::Uid.eq(media.uid))
+			.filter(unused())
+			.exec(db)
+			.await?;
+		if result.rows_affected == 0 {
+			continue;
+		}
+		deleted += 1;
+
+		let hashes: HashSet<&str> = std::iter::once(media.hash.as_str())
+			.chain(variants.iter().map(|variant| variant.hash.as_str()))
+			.collect();
+		for hash in hashes {
+			delete_unused(db, storage, hash).await?;
+		}
+	}
+	Ok(deleted)
+}
+
+/// Deletes the contents stored under `hash` if no media has them.
+///
+/// [`store`] puts contents after inserting their row, so a row that turns up
+/// once they are deleted may have had them put just before. They are read
+/// first and put back then; being named by their hash, they are the same.
+async fn delete_unused(
+	db: &DatabaseConnection,
+	storage: &dyn Storage,
+	hash: &str,
+) -> Result<(), Error> {
+	if stored(db, hash).await? {
+		return Ok(());
+	}
+	let Some(data) = storage.get(hash).await? else {
+		return Ok(());
+	};
+	storage.delete(hash).await?;
+	if stored(db, hash).await? {
+		storage.put(hash, &data).await?;
+	}
+	Ok(())
+}
+
+/// Whether any media or variant still has the contents stored under `hash`.
+async fn stored(db: &DatabaseConnection, hash: &str) -> Result<bool, DbErr> {
+	let media = Media::find()
+		.filter(media::Column::Hash.eq(hash))
+		.one(db)
+		.await?;
+	if media.is_some() {
+		return Ok(true);
+	}
+	let variant = MediaVariant::find()
+		.filter(media_variant::Column::Hash.eq(hash))
+		.one(db)
+		.await?;
+	Ok(variant.is_some())
+}
+
+/// Collects garbage every [`GC_INTERVAL`], for as long as the server runs.
+pub async fn collect_garbage_periodically(
+	db: DatabaseConnection,
+	storage: std::sync::Arc<dyn Storage>,
+) {
+	let mut interval = tokio::time::interval(GC_INTERVAL);
+	loop {
+		interval.tick().await;
+		if let Err(err) = collect_garbage(&db, storage.as_ref(), GRACE).await {
+			tracing::warn!("Collecting unused media failed: {err}");
+		}
+	}
+}
@@ -0,0 +1,204 @@
+//! Multiple choice questions: checking them, keeping their answers from anyone
+//! but the card's creator, and grading the options picked.
+
+use std::{
+	collections::HashSet,
+	fmt::{Display, Formatter},
+};
+
+use axum::http::StatusCode;
+use entity::{
+	custom::flash_card::{FlashCardContent, FlashCardItem, FlashCardSection, MultipleChoice},
+	flash_card,
+};
+use rand::seq::{IteratorRandom, SliceRandom};
+use serde::Serialize;
+
+#[derive(Debug)]
+pub enum Error {
+	EmptyOption,
+	Duplicate(String),
+	NoCorrect,
+	Index(usize),
+	TooFewOptions,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::EmptyOption => f.write_str("Multiple choice options can't be empty"),
+			Self::Duplicate(option) => write!(f, "Option `{option}` is given twice"),
+			Self::NoCorrect => f.write_str("A multiple choice question needs a right option"),
+			Self::Index(i) => write!(f, "There is no option {i} to be right"),
+			Self::TooFewOptions => {
+				f.write_str("A multiple choice question needs at least two options")
+			}
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for (StatusCode, String) {
+	fn from(err: Error) -> Self {
+		(StatusCode::UNPROCESSABLE_ENTITY, err.to_string())
+	}
+}
+
+fn key(option: &str) -> String {
+	option.trim().to_lowercase()
+}
+
+/// Checks every multiple choice section of a card.
+pub fn validate(content: &FlashCardContent) -> Result<(), Error> {
+	for section in &content.0 {
+		let FlashCardSection::MultipleChoice(question) = section else {
+			continue;
+		};
+
+		let mut seen = HashSet::new();
+		for option in &question.options {
+			if option.trim().is_empty() {
+				return Err(Error::EmptyOption);
+			}
+			if !seen.insert(key(option)) {
+				return Err(Error::Duplicate(option.clone()));
+			}
+		}
+		if question.correct.is_empty() {
+			return Err(Error::NoCorrect);
+		}
+		if let Some(&i) = question
+			.correct
+			.iter()
+			.find(|&&i| i >= question.options.len())
+		{
+			return Err(Error::Index(i));
+		}
+		if question.options.len() < 2 && question.distractors == 0 {
+			return Err(Error::TooFewOptions);
+		}
+	}
+	Ok(())
+}
+
+/// Leaves out the right options of a card `user` didn't create.
+pub fn redact(card: &mut flash_card::Model, user: u32) {
+	if card.creator == user {
+		return;
+	}
+	for section in &mut card.content.0 {
+		if let FlashCardSection::MultipleChoice(question) = section {
+			question.correct.clear();
+		}
+	}
+}
+
+/// Content of `card` for `user` to keep a copy of. The questions of a card they
+/// didn't create are left out: their right options aren't shared, and they
+/// can't be graded without them.
+pub fn copyable(card: &flash_card::Model, user: u32) -> FlashCardContent {
+	let mut content = card.content.clone();
+	if card.creator != user {
+		content
+			.0
+			.retain(|section| !matches!(section, FlashCardSection::MultipleChoice(_)));
+	}
+	content
+}
+
+pub fn right_options(question: &MultipleChoice) -> impl Iterator<Item = &String> {
+	question
+		.correct
+		.iter()
+		.filter_map(|&i| question.options.get(i))
+}
+
+/// What a card is answered with: the right options of its questions and the
+/// backs of its front/back sections.
+fn answers(content: &FlashCardContent) -> impl Iterator<Item = &str> {
+	content.0.iter().flat_map(|section| match section {
+		FlashCardSection::MultipleChoice(question) => {
+			right_options(question).map(String::as_str).collect()
+		}
+		FlashCardSection::FrontBack {
+			back: FlashCardItem::Title(text) | FlashCardItem::Answer { text, .. },
+			..
+		} => vec![text.as_str()],
+		_ => Vec::new(),
+	})
+}
+
+/// Adds wrong options to the questions of `cards`, taken from the answers of
+/// the other cards in `deck`, and shuffles the options.
+///
+/// Has to run before [`redact`], as the right options move.
+pub fn add_distractors(cards: &mut [flash_card::Model], deck: &[flash_card::Model]) {
+	let mut rng = rand::thread_rng();
+	for card in cards {
+		let pool: Vec<&str> = deck
+			.iter()
+			.filter(|other| other.uid != card.uid)
+			.flat_map(|other| answers(&other.content))
+			.collect();
+
+		for section in &mut card.content.0 {
+			let FlashCardSection::MultipleChoice(question) = section else {
+				continue;
+			};
+			if question.distractors == 0 {
+				continue;
+			}
+
+			let mut taken: HashSet<String> = question.options.iter().map(|o| key(o)).collect();
+			let candidates: Vec<&str> = pool
+				.iter()
+				.copied()
+				.filter(|answer| taken.insert(key(answer)))
+				.collect();
+			let mut options: Vec<(String, bool)> = question
+				.options
+				.drain(..)
+				.enumerate()
+				.map(|(i, option)| (option, question.correct.contains(&i)))
+				.collect();
+			options.extend(
+				candidates
+					.into_iter()
+					.choose_multiple(&mut rng, question.distractors as usize)
+					.into_iter()
+					.map(|answer| (answer.to_string(), false)),
+			);
+			options.shuffle(&mut rng);
+
+			question.correct = options
+				.iter()
+				.enumerate()
+				.filter(|(_, (_, right))| *right)
+				.map(|(i, _)| i)
+				.collect();
+			question.options = options.into_iter().map(|(option, _)| option).collect();
+		}
+	}
+}
+
+#[derive(Serialize)]
+pub struct Graded {
+	pub correct: bool,
+	/// The right options, now that the choice is made.
+	pub answers: Vec<String>,
+}
+
+/// Grades the options picked, by their text, so that distractors and shuffled
+/// options grade the same.
+pub fn grade(question: &MultipleChoice, chosen: &[String]) -> Graded {
+	let answers: Vec<String> = right_options(question).cloned().collect();
+	let right: HashSet<String> = answers.iter().map(|a| key(a)).collect();
+	let picked: HashSet<String> = chosen.iter().map(|c| key(c)).collect();
+
+	Graded {
+		// A question without right options can't be answered right.
+		correct: !right.is_empty() && right == picked,
+		answers,
+	}
+}
@@ -0,0 +1,34 @@
+//! Rendering formatted items on the server, for clients that would rather not.
+
+use entity::{custom::flash_card::FlashCardItem, flash_card};
+use serde::Deserialize;
+
+use crate::{markdown, math};
+
+/// Whether to send Markdown and math items rendered, with `?html=true`.
+#[derive(Clone, Copy, Default, Deserialize)]
+pub struct Render {
+	#[serde(default)]
+	pub html: bool,
+}
+
+impl Render {
+	/// Fills in the HTML of the Markdown items of a card and the MathML of its
+	/// math items, if asked to.
+	pub fn apply(self, card: &mut flash_card::Model) {
+		if !self.html {
+			return;
+		}
+		for item in card.content.items_mut() {
+			match item {
+				FlashCardItem::Markdown { source, html } => *html = Some(markdown::html(source)),
+				FlashCardItem::Math {
+					source,
+					block,
+					mathml,
+				} => *mathml = Some(math::mathml(source, *block)),
+				_ => {}
+			}
+		}
+	}
+}
@@ -1,19 +1,49 @@
+use std::{collections::HashSet, sync::Arc};
+
 use axum::{
-	extract::{Path, State},
-	http::{header::LOCATION, StatusCode},
+	body::{Body, Bytes},
+	extract::{DefaultBodyLimit, Path, Query, State},
+	http::{
+		header::{CONTENT_DISPOSITION, CONTENT_TYPE, LOCATION},
+		StatusCode,
+	},
 	middleware,
-	response::IntoResponse,
+	response::{IntoResponse, Response},
 	routing::{delete, get, patch, post, put},
-	Extension, Json, Router,
+	BoxError, Extension, Json, Router,
 };
+use chrono::Utc;
+use futures::{stream, StreamExt};
 use sea_orm::{
-	ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter,
+	sea_query::{Expr, OnConflict},
+	ActiveValue::Set,
+	ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter,
+	QueryOrder, QuerySelect, TransactionTrait,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{app::AppState, internal_error, session};
-use entity::{deck, deck_cards, flash_card, prelude::*, sea_orm_active_enums::Share, user};
+use super::{flash_card::CardSort, page};
+use crate::{
+	anki,
+	app::AppState,
+	cloze, fmdeck, internal_error, ipa, markdown, math, media, quiz,
+	render::Render,
+	session,
+	storage::Storage,
+	tabular,
+	tts::{self, Tts},
+};
+use entity::{
+	custom::flash_card::FlashCardContent,
+	deck, deck_cards, flash_card, followed_decks,
+	prelude::*,
+	sea_orm_active_enums::{Kind, Share},
+	user,
+};
+
+const MAX_IMPORT_SIZE: usize = 256 * 1024 * 1024;
+const EXPORT_PAGE_SIZE: u64 = 500;
 
 async fn create(
 	State(conn): State<DatabaseConnection>,
@@ -22,6 +52,8 @@ async fn create(
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
 	deck.uid = Uuid::new_v4();
 	deck.creator = user.id;
+	deck.created = Utc::now();
+	deck.updated = deck.created;
 
 	Deck::insert(deck::ActiveModel {
 		uid: Set(deck.uid),
@@ -29,6 +61,8 @@ async fn create(
 		creator: Set(deck.creator),
 		kind: Set(deck.kind),
 		share: Set(deck.share),
+		created: Set(deck.created),
+		updated: Set(deck.updated),
 	})
 	.exec(&conn)
 	.await
@@ -41,12 +75,133 @@ async fn create(
 	))
 }
 
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeckSort {
+	Name,
+	#[default]
+	Created,
+	Updated,
+}
+
+impl DeckSort {
+	fn column(self) -> deck::Column {
+		match self {
+			Self::Name => deck::Column::Name,
+			Self::Created => deck::Column::Created,
+			Self::Updated => deck::Column::Updated,
+		}
+	}
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+	#[serde(default)]
+	include_followed: bool,
+	#[serde(default)]
+	limit: Option<u64>,
+	#[serde(default)]
+	after: Option<page::Cursor>,
+	#[serde(default)]
+	sort: DeckSort,
+	#[serde(default)]
+	order: page::Order,
+	#[serde(default)]
+	share: Option<Share>,
+	#[serde(default)]
+	kind: Option<Kind>,
+}
+
+#[derive(Serialize)]
+pub struct ListedDeck {
+	#[serde(flatten)]
+	deck: deck::Model,
+	owned: bool,
+	followed: bool,
+}
+
+/// Lists the user's decks, and with `include_followed` also the decks they
+/// follow, each flagged, a page at a time.
 async fn all(
 	State(db): State<DatabaseConnection>,
 	Extension(user): Extension<user::Model>,
+	Query(query): Query<ListQuery>,
+) -> Result<Response, (StatusCode, String)> {
+	let mut select = Deck::find();
+	if let Some(share) = query.share {
+		select = select.filter(deck::Column::Share.eq(share));
+	}
+	if let Some(kind) = query.kind {
+		select = select.filter(deck::Column::Kind.eq(kind));
+	}
+
+	if !query.include_followed {
+		let decks = page::fetch(
+			&db,
+			select.filter(deck::Column::Creator.eq(user.id)),
+			query.sort.column(),
+			deck::Column::Uid,
+			query.order,
+			query.after,
+			query.limit,
+		)
+		.await?;
+
+		return Ok((decks.headers(), Json(decks.rows)).into_response());
+	}
+
+	let followed: HashSet<Uuid> = FollowedDecks::find()
+		.filter(followed_decks::Column::User.eq(user.id))
+		.all(&db)
+		.await
+		.map_err(internal_error)?
+		.into_iter()
+		.map(|f| f.deck)
+		.collect();
+
+	let decks = page::fetch(
+		&db,
+		select.filter(
+			deck::Column::Creator.eq(user.id).or(deck::Column::Share
+				.eq(Share::Public)
+				.and(deck::Column::Uid.is_in(followed.clone()))),
+		),
+		query.sort.column(),
+		deck::Column::Uid,
+		query.order,
+		query.after,
+		query.limit,
+	)
+	.await?;
+
+	Ok((
+		decks.headers(),
+		Json(
+			decks
+				.rows
+				.into_iter()
+				.map(|deck| ListedDeck {
+					owned: deck.creator == user.id,
+					followed: followed.contains(&deck.uid),
+					deck,
+				})
+				.collect::<Vec<_>>(),
+		),
+	)
+		.into_response())
+}
+
+async fn followed(
+	State(db): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-	let decks = Deck::find()
-		.filter(deck::Column::Creator.eq(user.id))
+	let decks = user
+		.find_related(Deck)
+		.filter(
+			deck::Column::Share.eq(Share::Public).or(deck::Column::Share
+				.eq(Share::Private)
+				.and(deck::Column::Creator.eq(user.id))),
+		)
 		.all(&db)
 		.await
 		.map_err(internal_error)?;
@@ -54,6 +209,55 @@ async fn all(
 	Ok(Json(decks))
 }
 
+async fn follow(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+	let Some(deck) = Deck::find_by_id(uid)
+		.filter(
+			deck::Column::Share.eq(Share::Public).or(deck::Column::Share
+				.eq(Share::Private)
+				.and(deck::Column::Creator.eq(user.id))),
+		)
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+	else {
+		return Err((StatusCode::NOT_FOUND, "Not found".to_string()));
+	};
+
+	FollowedDecks::insert(followed_decks::ActiveModel {
+		user: Set(user.id),
+		deck: Set(deck.uid),
+	})
+	// Following twice is fine; rewriting a key column is a no-op update on both
+	// MySQL and SQLite, unlike `do_nothing`.
+	.on_conflict(
+		OnConflict::columns([followed_decks::Column::User, followed_decks::Column::Deck])
+			.update_column(followed_decks::Column::User)
+			.to_owned(),
+	)
+	.exec_without_returning(&conn)
+	.await
+	.map_err(internal_error)?;
+	Ok(StatusCode::NO_CONTENT)
+}
+
+async fn unfollow(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+	FollowedDecks::delete_many()
+		.filter(followed_decks::Column::User.eq(user.id))
+		.filter(followed_decks::Column::Deck.eq(uid))
+		.exec(&conn)
+		.await
+		.map_err(internal_error)?;
+	Ok(StatusCode::NO_CONTENT)
+}
+
 async fn get_one(
 	State(db): State<DatabaseConnection>,
 	Extension(user): Extension<user::Model>,
@@ -102,6 +306,8 @@ async fn update(
 		creator: deck.creator,
 		kind: Set(body.kind),
 		share: Set(body.share),
+		created: deck.created,
+		updated: Set(Utc::now()),
 	})
 	.exec(&conn)
 	.await
@@ -125,10 +331,37 @@ async fn delete_deck(
 	Ok(StatusCode::NO_CONTENT)
 }
 
+#[derive(Deserialize)]
+pub struct CardsQuery {
+	#[serde(default)]
+	limit: Option<u64>,
+	#[serde(default)]
+	after: Option<page::Cursor>,
+	#[serde(default)]
+	sort: CardSort,
+	#[serde(default)]
+	order: page::Order,
+	#[serde(default)]
+	share: Option<Share>,
+}
+
+/// Marks a deck as updated, for changes to its cards.
+async fn touch(db: &impl ConnectionTrait, uid: Uuid) -> Result<(), (StatusCode, String)> {
+	Deck::update_many()
+		.col_expr(deck::Column::Updated, Expr::value(Utc::now()))
+		.filter(deck::Column::Uid.eq(uid))
+		.exec(db)
+		.await
+		.map_err(internal_error)?;
+	Ok(())
+}
+
 async fn get_cards(
 	State(conn): State<DatabaseConnection>,
 	Extension(user): Extension<user::Model>,
 	Path(uid): Path<Uuid>,
+	Query(query): Query<CardsQuery>,
+	Query(render): Query<Render>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
 	let Some(deck) = Deck::find_by_id(uid)
 		.filter(
@@ -143,19 +376,32 @@ async fn get_cards(
 		return Err((StatusCode::NOT_FOUND, "Not found".to_string()));
 	};
 
-	let cards = deck
-		.find_related(FlashCard)
-		.filter(
-			flash_card::Column::Share
-				.eq(Share::Public)
-				.or(flash_card::Column::Share
-					.eq(Share::Private)
-					.and(flash_card::Column::Creator.eq(user.id))),
-		)
-		.all(&conn)
-		.await
-		.map_err(internal_error)?;
-	Ok((StatusCode::OK, Json(cards)))
+	let mut select = deck.find_related(FlashCard).filter(
+		flash_card::Column::Share
+			.eq(Share::Public)
+			.or(flash_card::Column::Share
+				.eq(Share::Private)
+				.and(flash_card::Column::Creator.eq(user.id))),
+	);
+	if let Some(share) = query.share {
+		select = select.filter(flash_card::Column::Share.eq(share));
+	}
+
+	let mut cards = page::fetch(
+		&conn,
+		select,
+		query.sort.column(),
+		flash_card::Column::Uid,
+		query.order,
+		query.after,
+		query.limit,
+	)
+	.await?;
+	for card in &mut cards.rows {
+		quiz::redact(card, user.id);
+		render.apply(card);
+	}
+	Ok((StatusCode::OK, cards.headers(), Json(cards.rows)))
 }
 
 async fn add_cards(
@@ -186,6 +432,7 @@ async fn add_cards(
 	.exec(&conn)
 	.await
 	.map_err(internal_error)?;
+	touch(&conn, deck.uid).await?;
 	Ok(StatusCode::NO_CONTENT)
 }
 
@@ -232,7 +479,9 @@ async fn update_cards(
 			.map_err(internal_error)?;
 	}
 
-	let cards = deck
+	touch(&conn, deck.uid).await?;
+
+	let mut cards = deck
 		.find_related(FlashCard)
 		.filter(
 			flash_card::Column::Share
@@ -244,18 +493,834 @@ async fn update_cards(
 		.all(&conn)
 		.await
 		.map_err(internal_error)?;
+	for card in &mut cards {
+		quiz::redact(card, user.id);
+	}
 	Ok((StatusCode::OK, Json(cards)))
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+	Apkg,
+	Fmdeck,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+	format: ImportFormat,
+	#[serde(default)]
+	name: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum ImportFailure {
+	Note(anki::import::Failure),
+	Card(fmdeck::Failure),
+}
+
+#[derive(Serialize)]
+pub struct ImportReport {
+	deck: deck::Model,
+	imported: usize,
+	failures: Vec<ImportFailure>,
+}
+
+/// Checks the content of an imported card, as it would be checked if it were
+/// saved through the API.
+fn check_content(content: &mut FlashCardContent) -> Result<(), (StatusCode, String)> {
+	cloze::validate(content)?;
+	quiz::validate(content)?;
+	markdown::sanitize(content)?;
+	math::validate(content)?;
+	ipa::normalize(content)?;
+	Ok(())
+}
+
+/// Creates a new deck from an uploaded package.
+async fn import(
+	State(conn): State<DatabaseConnection>,
+	State(storage): State<Arc<dyn Storage>>,
+	State(tts): State<Arc<dyn Tts>>,
+	Extension(user): Extension<user::Model>,
+	Query(query): Query<ImportQuery>,
+	body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	// Everything gets fresh ids, so importing the same file twice, or into the
+	// instance it came from, can't collide.
+	let now = Utc::now();
+	let (mut deck, mut cards, failures): (_, Vec<flash_card::Model>, _) = match query.format {
+		ImportFormat::Apkg => {
+			let imported = anki::import::read(body).await?;
+			let deck = deck::Model {
+				uid: Uuid::new_v4(),
+				name: imported.name,
+				creator: user.id,
+				kind: imported.kind,
+				share: Share::Private,
+				created: now,
+				updated: now,
+			};
+			let cards = imported
+				.cards
+				.into_iter()
+				.map(|content| flash_card::Model {
+					uid: Uuid::new_v4(),
+					creator: user.id,
+					share: Share::Private,
+					content,
+					created: now,
+					updated: now,
+				})
+				.collect();
+			let failures = imported
+				.failures
+				.into_iter()
+				.map(ImportFailure::Note)
+				.collect();
+			(deck, cards, failures)
+		}
+		ImportFormat::Fmdeck => {
+			let mut bundle = tokio::task::spawn_blocking(move || fmdeck::read(&body))
+				.await
+				.map_err(internal_error)??;
+			let mut failures = Vec::new();
+			bundle
+				.cards
+				.retain_mut(|card| match check_content(&mut card.content) {
+					Ok(()) => true,
+					Err((_, error)) => {
+						failures.push(ImportFailure::Card(fmdeck::Failure {
+							card: card.uid,
+							error,
+						}));
+						false
+					}
+				});
+			let deck = deck::Model {
+				uid: Uuid::new_v4(),
+				name: bundle.name,
+				creator: user.id,
+				kind: bundle.kind,
+				share: bundle.share,
+				created: now,
+				updated: now,
+			};
+			let cards = bundle
+				.cards
+				.into_iter()
+				.map(|card| flash_card::Model {
+					uid: Uuid::new_v4(),
+					creator: user.id,
+					share: card.share,
+					content: card.content,
+					created: now,
+					updated: now,
+				})
+				.collect();
+			(deck, cards, failures)
+		}
+	};
+	if let Some(name) = query.name {
+		deck.name = name;
+	}
+	media::upload_inlined(&conn, storage.as_ref(), user.id, &mut cards).await?;
+
+	let txn = conn.begin().await.map_err(internal_error)?;
+	Deck::insert(deck::ActiveModel {
+		uid: Set(deck.uid),
+		name: Set(deck.name.clone()),
+		creator: Set(deck.creator),
+		kind: Set(deck.kind),
+		share: Set(deck.share),
+		created: Set(deck.created),
+		updated: Set(deck.updated),
+	})
+	.exec(&txn)
+	.await
+	.map_err(internal_error)?;
+
+	for chunk in cards.chunks(1000) {
+		FlashCard::insert_many(chunk.iter().map(|card| flash_card::ActiveModel {
+			uid: Set(card.uid),
+			creator: Set(card.creator),
+			share: Set(card.share),
+			content: Set(card.content.clone()),
+			created: Set(card.created),
+			updated: Set(card.updated),
+		}))
+		.exec(&txn)
+		.await
+		.map_err(internal_error)?;
+
+		DeckCards::insert_many(chunk.iter().map(|card| deck_cards::ActiveModel {
+			card: Set(card.uid),
+			deck: Set(deck.uid),
+		}))
+		.exec(&txn)
+		.await
+		.map_err(internal_error)?;
+	}
+	media::link(&txn, user.id, &cards).await?;
+	txn.commit().await.map_err(internal_error)?;
+	tts::record_later(
+		conn,
+		storage,
+		tts,
+		cards.iter().map(|card| card.uid).collect(),
+	);
+
+	Ok((
+		StatusCode::CREATED,
+		[(LOCATION, deck.uid.to_string())],
+		Json(ImportReport {
+			deck,
+			imported: cards.len(),
+			failures,
+		}),
+	))
+}
+
+#[derive(Deserialize)]
+pub struct RowImportQuery {
+	format: tabular::Format,
+	/// Comma-separated column kinds, see [`tabular::columns`].
+	columns: String,
+	#[serde(default)]
+	header: bool,
+	#[serde(default)]
+	dry_run: bool,
+}
+
+#[derive(Serialize)]
+pub struct RowImportReport {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	cards: Option<Vec<FlashCardContent>>,
+	imported: usize,
+	failures: Vec<tabular::import::Failure>,
+}
+
+/// Adds a card to a deck for each row of an uploaded CSV or TSV file.
+///
+/// With `dry_run` set the parsed cards are returned instead of being saved.
+async fn import_rows(
+	State(conn): State<DatabaseConnection>,
+	State(storage): State<Arc<dyn Storage>>,
+	State(tts): State<Arc<dyn Tts>>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+	Query(query): Query<RowImportQuery>,
+	body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let Some(deck) = Deck::find_by_id(uid)
+		.filter(deck::Column::Creator.eq(user.id))
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+	else {
+		return Err((StatusCode::NOT_FOUND, "Not found".to_string()));
+	};
+
+	let columns = tabular::columns(&query.columns)?;
+	let (format, header) = (query.format, query.header);
+	let imported =
+		tokio::task::spawn_blocking(move || tabular::import::read(&body, format, &columns, header))
+			.await
+			.map_err(internal_error)??;
+
+	if query.dry_run {
+		return Ok(Json(RowImportReport {
+			imported: 0,
+			cards: Some(imported.cards),
+			failures: imported.failures,
+		}));
+	}
+
+	let now = Utc::now();
+	let mut cards: Vec<flash_card::Model> = imported
+		.cards
+		.into_iter()
+		.map(|content| flash_card::Model {
+			uid: Uuid::new_v4(),
+			creator: user.id,
+			share: Share::Private,
+			content,
+			created: now,
+			updated: now,
+		})
+		.collect();
+	media::upload_inlined(&conn, storage.as_ref(), user.id, &mut cards).await?;
+
+	let txn = conn.begin().await.map_err(internal_error)?;
+	for chunk in cards.chunks(1000) {
+		FlashCard::insert_many(chunk.iter().map(|card| flash_card::ActiveModel {
+			uid: Set(card.uid),
+			creator: Set(card.creator),
+			share: Set(card.share),
+			content: Set(card.content.clone()),
+			created: Set(card.created),
+			updated: Set(card.updated),
+		}))
+		.exec(&txn)
+		.await
+		.map_err(internal_error)?;
+
+		DeckCards::insert_many(chunk.iter().map(|card| deck_cards::ActiveModel {
+			card: Set(card.uid),
+			deck: Set(deck.uid),
+		}))
+		.exec(&txn)
+		.await
+		.map_err(internal_error)?;
+	}
+	media::link(&txn, user.id, &cards).await?;
+	touch(&txn, deck.uid).await?;
+	txn.commit().await.map_err(internal_error)?;
+	tts::record_later(
+		conn,
+		storage,
+		tts,
+		cards.iter().map(|card| card.uid).collect(),
+	);
+
+	Ok(Json(RowImportReport {
+		cards: None,
+		imported: cards.len(),
+		failures: imported.failures,
+	}))
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+	Apkg,
+	Fmdeck,
+	Csv,
+	Tsv,
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+	format: ExportFormat,
+	/// Comma-separated column kinds, required for CSV and TSV.
+	#[serde(default)]
+	columns: Option<String>,
+	/// Whether CSV and TSV start with a row of column names, on by default.
+	#[serde(default)]
+	header: Option<bool>,
+}
+
+/// Downloads a deck, with the cards the user can see.
+///
+/// CSV and TSV are streamed a page of cards at a time, with a last column
+/// noting what didn't fit the chosen columns.
+async fn export(
+	State(conn): State<DatabaseConnection>,
+	State(storage): State<Arc<dyn Storage>>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+	Query(query): Query<ExportQuery>,
+) -> Result<Response, (StatusCode, String)> {
+	let deck = Deck::find_by_id(uid)
+		.filter(
+			deck::Column::Share.eq(Share::Public).or(deck::Column::Share
+				.eq(Share::Private)
+				.and(deck::Column::Creator.eq(user.id))),
+		)
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+		.ok_or_else(|| (StatusCode::NOT_FOUND, "Not found".to_string()))?;
+
+	let visible = flash_card::Column::Share
+		.eq(Share::Public)
+		.or(flash_card::Column::Share
+			.eq(Share::Private)
+			.and(flash_card::Column::Creator.eq(user.id)));
+
+	let file_name: String = deck
+		.name
+		.chars()
+		.filter(|c| !c.is_control() && !matches!(c, '"' | '\\' | '/'))
+		.collect();
+	let headers = |mime: &str, ext: &str| {
+		[
+			(CONTENT_TYPE, mime.to_string()),
+			(
+				CONTENT_DISPOSITION,
+				format!("attachment; filename=\"{file_name}.{ext}\""),
+			),
+		]
+	};
+
+	let format = match query.format {
+		ExportFormat::Apkg => {
+			let mut cards = deck
+				.find_related(FlashCard)
+				.filter(visible)
+				.all(&conn)
+				.await
+				.map_err(internal_error)?;
+			media::inline(&conn, storage.as_ref(), &mut cards).await?;
+			for card in &mut cards {
+				quiz::redact(card, user.id);
+			}
+
+			// Nothing the user sent can make writing fail.
+			let data = anki::export::write(&deck, &cards)
+				.await
+				.map_err(internal_error)?;
+			return Ok((headers("application/apkg", "apkg"), data).into_response());
+		}
+		ExportFormat::Fmdeck => {
+			let mut cards = deck
+				.find_related(FlashCard)
+				.filter(visible)
+				.all(&conn)
+				.await
+				.map_err(internal_error)?;
+			media::inline(&conn, storage.as_ref(), &mut cards).await?;
+			for card in &mut cards {
+				quiz::redact(card, user.id);
+			}
+
+			let data = fmdeck::write(&deck, &cards).map_err(internal_error)?;
+			return Ok((headers("application/zip", "fmdeck"), data).into_response());
+		}
+		ExportFormat::Csv => tabular::Format::Csv,
+		ExportFormat::Tsv => tabular::Format::Tsv,
+	};
+
+	let columns = tabular::columns(query.columns.as_deref().ok_or((
+		StatusCode::BAD_REQUEST,
+		"Columns are required for this format".to_string(),
+	))?)?;
+
+	let header = query
+		.header
+		.unwrap_or(true)
+		.then(|| tabular::export::write(format, &[tabular::export::header(&columns)]))
+		.transpose()
+		.map_err(internal_error)?
+		.map(Bytes::from);
+
+	let user = user.id;
+	// Keyset pagination on the card id, `None` once the last page was sent.
+	let rows = stream::try_unfold(Some(None), move |after: Option<Option<Uuid>>| {
+		let conn = conn.clone();
+		let deck = deck.clone();
+		let visible = visible.clone();
+		let columns = columns.clone();
+		async move {
+			let Some(after) = after else {
+				return Ok(None);
+			};
+
+			let mut select = deck
+				.find_related(FlashCard)
+				.filter(visible)
+				.order_by_asc(flash_card::Column::Uid)
+				.limit(EXPORT_PAGE_SIZE);
+			if let Some(after) = after {
+				select = select.filter(flash_card::Column::Uid.gt(after));
+			}
+			let mut cards = select.all(&conn).await.map_err(BoxError::from)?;
+			if cards.is_empty() {
+				return Ok(None);
+			}
+			for card in &mut cards {
+				quiz::redact(card, user);
+			}
+
+			let rows: Vec<_> = cards
+				.iter()
+				.map(|card| tabular::export::row(&card.content, &columns))
+				.collect();
+			let data = tabular::export::write(format, &rows).map_err(BoxError::from)?;
+
+			let next =
+				(cards.len() as u64 == EXPORT_PAGE_SIZE).then(|| cards.last().map(|c| c.uid));
+			Ok::<_, BoxError>(Some((Bytes::from(data), next)))
+		}
+	});
+
+	Ok((
+		headers(format.mime_type(), format.extension()),
+		Body::from_stream(stream::iter(header.map(Ok)).chain(rows)),
+	)
+		.into_response())
+}
+
 pub fn router() -> Router<AppState> {
 	Router::new()
 		.route("/", post(create))
 		.route("/", get(all))
+		.route("/followed", get(followed))
+		.route(
+			"/import",
+			post(import).layer(DefaultBodyLimit::max(MAX_IMPORT_SIZE)),
+		)
 		.route("/:id", get(get_one))
 		.route("/:id", put(update))
 		.route("/:id", delete(delete_deck))
+		.route(
+			"/:id/import",
+			post(import_rows).layer(DefaultBodyLimit::max(MAX_IMPORT_SIZE)),
+		)
+		.route("/:id/export", get(export))
+		.route("/:id/follow", post(follow))
+		.route("/:id/follow", delete(unfollow))
 		.route("/:id/cards", get(get_cards))
 		.route("/:id/cards", put(add_cards))
 		.route("/:id/cards", patch(update_cards))
 		.route_layer(middleware::from_fn(session::auth))
 }
+
+#[cfg(test)]
+mod tests {
+	use axum::http::Request;
+	use serde_json::{json, Value};
+	use uuid::Uuid;
+
+	use crate::testing::{titled, uid, TestApp, OTHER, USER};
+
+	fn uids(list: &Value) -> Vec<Uuid> {
+		list.as_array().unwrap().iter().map(uid).collect()
+	}
+
+	fn names(list: &Value) -> Vec<String> {
+		list.as_array()
+			.unwrap()
+			.iter()
+			.map(|deck| deck["name"].as_str().unwrap().to_string())
+			.collect()
+	}
+
+	/// Contents of the cards of a deck, sorted.
+	async fn contents(app: &TestApp, deck: Uuid) -> Vec<String> {
+		let cards = app
+			.call("GET", &format!("/api/deck/{deck}/cards"), None)
+			.await
+			.json();
+		let mut contents: Vec<String> = cards
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|card| card["content"].to_string())
+			.collect();
+		contents.sort();
+		contents
+	}
+
+	#[tokio::test]
+	async fn round_trips_anki_packages() {
+		let app = TestApp::new().await;
+		let audio = uid(&app
+			.upload("/api/media", &b"fLaC not really"[..])
+			.await
+			.json());
+		let lang = json!([
+			{"type": "Lang", "content": {"de": {"title": "Katze"}, "en-uk": {"title": "cat"}}},
+			{"type": "Item", "content": {"pronunciation": {
+				"ipa": "ˈkat͡sə",
+				"audioUrl": format!("/api/media/{audio}"),
+			}}},
+		]);
+		let front_back = json!([
+			{"type": "FrontBack", "content": {"front": {"title": "Hund"}, "back": {"title": "dog"}}},
+			{"type": "Item", "content": {"example": "Der Hund bellt."}},
+		]);
+		let deck = app
+			.deck(USER, "Tiere", "Private", &[lang, front_back])
+			.await;
+
+		let reply = app
+			.call("GET", &format!("/api/deck/{deck}/export?format=apkg"), None)
+			.await;
+		assert_eq!(reply.status, 200);
+		assert_eq!(reply.header("content-type"), Some("application/apkg"));
+		let reply = app.upload("/api/deck/import?format=apkg", reply.body).await;
+		assert_eq!(reply.status, 201, "{:?}", reply.body);
+		let report = reply.json();
+		assert_eq!(report["imported"], 2);
+		assert_eq!(report["failures"], json!([]));
+		assert_eq!(report["deck"]["name"], "Tiere");
+		assert_eq!(report["deck"]["kind"], "Language");
+
+		// The recording is the one uploaded before, being the same file.
+		let imported = uid(&report["deck"]);
+		assert_ne!(imported, deck);
+		assert_eq!(contents(&app, imported).await, contents(&app, deck).await);
+
+		let reply = app
+			.upload("/api/deck/import?format=apkg", &b"not a zip"[..])
+			.await;
+		assert_eq!(reply.status, 422);
+	}
+
+	#[tokio::test]
+	async fn round_trips_bundles() {
+		let app = TestApp::new().await;
+		let audio = uid(&app
+			.upload("/api/media", &b"fLaC not really"[..])
+			.await
+			.json());
+		let lang = json!([
+			{"type": "Lang", "content": {"de": {"title": "Katze"}, "en-uk": {"title": "cat"}}},
+			{"type": "Item", "content": {"pronunciation": {
+				"ipa": "ˈkat͡sə",
+				"audioUrl": format!("/api/media/{audio}"),
+			}}},
+		]);
+		let deck = app
+			.deck(USER, "Tiere", "Public", &[lang, titled("Hund")])
+			.await;
+
+		let reply = app
+			.call(
+				"GET",
+				&format!("/api/deck/{deck}/export?format=fmdeck"),
+				None,
+			)
+			.await;
+		assert_eq!(reply.status, 200);
+		assert_eq!(reply.header("content-type"), Some("application/zip"));
+		let reply = app
+			.upload(
+				"/api/deck/import?format=fmdeck&name=Meine%20Tiere",
+				reply.body,
+			)
+			.await;
+		assert_eq!(reply.status, 201, "{:?}", reply.body);
+		let report = reply.json();
+		assert_eq!(report["imported"], 2);
+		assert_eq!(report["failures"], json!([]));
+		assert_eq!(report["deck"]["name"], "Meine Tiere");
+		assert_eq!(report["deck"]["kind"], "Language");
+		// Unlike Anki packages, bundles keep how things were shared.
+		assert_eq!(report["deck"]["share"], "Public");
+
+		let imported = uid(&report["deck"]);
+		assert_ne!(imported, deck);
+		assert_eq!(contents(&app, imported).await, contents(&app, deck).await);
+		let cards = app
+			.call("GET", &format!("/api/deck/{imported}/cards"), None)
+			.await
+			.json();
+		for card in cards.as_array().unwrap() {
+			assert_eq!(card["share"], "Public");
+		}
+
+		let reply = app
+			.upload("/api/deck/import?format=fmdeck", &b"not a zip"[..])
+			.await;
+		assert_eq!(reply.status, 422);
+	}
+
+	#[tokio::test]
+	async fn imports_rows() {
+		let app = TestApp::new().await;
+		let deck = app.deck(USER, "Zwierzęta", "Private", &[]).await;
+		let csv = "Polish,English,Example,Notes\nkot,cat,Kot śpi.,\npies,dog,,\n,,,nothing\n,,,\n";
+		let import =
+			format!("/api/deck/{deck}/import?format=csv&columns=pl,en,example,skip&header=true");
+
+		let reply = app.upload(&format!("{import}&dry_run=true"), csv).await;
+		assert_eq!(reply.status, 200, "{:?}", reply.body);
+		let report = reply.json();
+		assert_eq!(report["imported"], 0);
+		assert_eq!(report["cards"].as_array().unwrap().len(), 2);
+		assert_eq!(
+			report["cards"][0],
+			json!([
+				{"type": "Lang", "content": {"pl": {"title": "kot"}, "en": {"title": "cat"}}},
+				{"type": "Item", "content": {"example": "Kot śpi."}},
+			])
+		);
+		assert_eq!(
+			report["failures"],
+			json!([{"row": 4, "error": "Row has no content"}])
+		);
+		assert_eq!(contents(&app, deck).await.len(), 0);
+
+		let reply = app.upload(&import, csv).await;
+		assert_eq!(reply.status, 200, "{:?}", reply.body);
+		assert_eq!(reply.json()["imported"], 2);
+		assert_eq!(reply.json().get("cards"), None);
+		assert_eq!(contents(&app, deck).await.len(), 2);
+
+		let reply = app
+			.send(OTHER, Request::post(&import).body(csv.into()).unwrap())
+			.await;
+		assert_eq!(reply.status, 404);
+	}
+
+	#[tokio::test]
+	async fn exports_rows() {
+		let app = TestApp::new().await;
+		let kot = json!([
+			{"type": "Lang", "content": {"pl": {"title": "kot"}, "en": {"title": "cat"}}},
+			{"type": "Item", "content": {"example": "Kot śpi."}},
+		]);
+		let pies = json!([
+			{"type": "Lang", "content": {"pl": {"title": "pies"}, "en": {"title": "dog"}}},
+		]);
+		let deck = app.deck(USER, "Zwierzęta", "Private", &[kot, pies]).await;
+
+		let reply = app
+			.call(
+				"GET",
+				&format!("/api/deck/{deck}/export?format=csv&columns=pl,en"),
+				None,
+			)
+			.await;
+		assert_eq!(reply.status, 200);
+		assert_eq!(
+			reply.header("content-type"),
+			Some("text/csv; charset=utf-8")
+		);
+		let text = String::from_utf8(reply.body.to_vec()).unwrap();
+		let mut lines: Vec<&str> = text.lines().collect();
+		assert_eq!(lines.remove(0), "pl,en,notes");
+		lines.sort_unstable();
+		assert_eq!(lines, ["kot,cat,example dropped", "pies,dog,"]);
+
+		let reply = app
+			.call(
+				"GET",
+				&format!("/api/deck/{deck}/export?format=tsv&columns=en,skip,pl&header=false"),
+				None,
+			)
+			.await;
+		let text = String::from_utf8(reply.body.to_vec()).unwrap();
+		let mut lines: Vec<&str> = text.lines().collect();
+		lines.sort_unstable();
+		assert_eq!(lines, ["cat\t\tkot\texample dropped", "dog\t\tpies\t"]);
+
+		let reply = app
+			.call("GET", &format!("/api/deck/{deck}/export?format=csv"), None)
+			.await;
+		assert_eq!(reply.status, 400);
+		let reply = app
+			.call("GET", &format!("/api/deck/{deck}/export?format=csv"), None)
+			.await;
+		assert_eq!(reply.status, 400);
+		let reply = app
+			.call_as(
+				OTHER,
+				"GET",
+				&format!("/api/deck/{deck}/export?format=csv&columns=pl"),
+				None,
+			)
+			.await;
+		assert_eq!(reply.status, 404);
+	}
+
+	#[tokio::test]
+	async fn pages_through_decks() {
+		let app = TestApp::new().await;
+		for name in ["b", "d", "a", "e", "c"] {
+			app.deck(USER, name, "Private", &[]).await;
+		}
+		app.deck(OTHER, "f", "Public", &[]).await;
+
+		let mut seen = Vec::new();
+		let mut uri = "/api/deck?sort=name&limit=2".to_string();
+		loop {
+			let reply = app.call("GET", &uri, None).await;
+			assert_eq!(reply.status, 200);
+			seen.extend(names(&reply.json()));
+			let Some(next) = reply.header("next-cursor") else {
+				break;
+			};
+			if seen == ["a", "b"] {
+				// Decks added before the cursor don't shift later pages.
+				app.deck(USER, "0", "Private", &[]).await;
+			}
+			uri = format!("/api/deck?sort=name&limit=2&after={next}");
+		}
+		assert_eq!(seen, ["a", "b", "c", "d", "e"]);
+
+		let reply = app.call("GET", "/api/deck?sort=name&limit=2", None).await;
+		let next = reply.header("next-cursor").unwrap();
+		let reply = app
+			.call("GET", &format!("/api/deck?sort=created&after={next}"), None)
+			.await;
+		assert_eq!(reply.status, 400);
+
+		let reply = app
+			.call("GET", "/api/deck?sort=name&order=desc&limit=3", None)
+			.await;
+		assert_eq!(names(&reply.json()), ["e", "d", "c"]);
+	}
+
+	#[tokio::test]
+	async fn follows_public_decks() {
+		let app = TestApp::new().await;
+		let own = app.deck(USER, "Own", "Private", &[]).await;
+		let public = app.deck(OTHER, "Public", "Public", &[]).await;
+		let private = app.deck(OTHER, "Private", "Private", &[]).await;
+
+		let follow = format!("/api/deck/{public}/follow");
+		assert_eq!(app.call("POST", &follow, None).await.status, 204);
+		assert_eq!(app.call("POST", &follow, None).await.status, 204);
+		let reply = app
+			.call("POST", &format!("/api/deck/{private}/follow"), None)
+			.await;
+		assert_eq!(reply.status, 404);
+		let reply = app
+			.call(
+				"POST",
+				&format!("/api/deck/{}/follow", Uuid::new_v4()),
+				None,
+			)
+			.await;
+		assert_eq!(reply.status, 404);
+
+		let followed = app.call("GET", "/api/deck/followed", None).await.json();
+		assert_eq!(uids(&followed), [public]);
+		let listed = app
+			.call("GET", "/api/deck?include_followed=true&sort=name", None)
+			.await
+			.json();
+		assert_eq!(uids(&listed), [own, public]);
+		assert_eq!(listed[0]["owned"], true);
+		assert_eq!(listed[0]["followed"], false);
+		assert_eq!(listed[1]["owned"], false);
+		assert_eq!(listed[1]["followed"], true);
+		let owned = app.call("GET", "/api/deck", None).await.json();
+		assert_eq!(uids(&owned), [own]);
+
+		assert_eq!(app.call("DELETE", &follow, None).await.status, 204);
+		assert_eq!(app.call("DELETE", &follow, None).await.status, 204);
+		let followed = app.call("GET", "/api/deck/followed", None).await.json();
+		assert_eq!(uids(&followed), []);
+	}
+
+	#[tokio::test]
+	async fn followed_decks_made_private_are_hidden() {
+		let app = TestApp::new().await;
+		let deck = app.deck(OTHER, "Public", "Public", &[]).await;
+		app.call("POST", &format!("/api/deck/{deck}/follow"), None)
+			.await;
+
+		let reply = app
+			.call_as(
+				OTHER,
+				"PUT",
+				&format!("/api/deck/{deck}"),
+				Some(json!({"name": "Public", "kind": "Language", "share": "Private"})),
+			)
+			.await;
+		assert!(reply.status.is_success(), "{:?}", reply.body);
+
+		let followed = app.call("GET", "/api/deck/followed", None).await.json();
+		assert_eq!(uids(&followed), []);
+		let listed = app
+			.call("GET", "/api/deck?include_followed=true", None)
+			.await
+			.json();
+		assert_eq!(uids(&listed), []);
+	}
+}
@@ -1,34 +1,82 @@
+use std::sync::Arc;
+
 use axum::{
-	extract::{Path, State},
+	extract::{Path, Query, State},
 	http::{header::LOCATION, StatusCode},
 	middleware,
 	response::IntoResponse,
 	routing::{delete, get, post, put},
 	Extension, Json, Router,
 };
-use sea_orm::{ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use chrono::Utc;
+use sea_orm::{
+	sea_query, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+	TransactionTrait,
+};
+use serde::Deserialize;
 use uuid::Uuid;
 
-use crate::{app::AppState, internal_error, session};
-use entity::{flash_card, prelude::*, sea_orm_active_enums::Share, user};
+use super::{page, search};
+use crate::{
+	answer::{self, Matching},
+	app::AppState,
+	card_query::{self, Filter, InDeck},
+	cloze, internal_error, ipa, markdown, math, media, quiz,
+	render::Render,
+	session,
+	storage::Storage,
+	tts::{self, Tts},
+};
+use entity::{
+	custom::{flash_card::FlashCardSection, lang::Language},
+	deck_cards, flash_card,
+	prelude::*,
+	sea_orm_active_enums::Share,
+	user,
+};
+
+/// Cards loaded at a time while searching.
+const SEARCH_BATCH: u64 = 500;
 
 async fn create(
 	State(conn): State<DatabaseConnection>,
+	State(storage): State<Arc<dyn Storage>>,
+	State(tts): State<Arc<dyn Tts>>,
 	Extension(user): Extension<user::Model>,
 	Json(mut body): Json<flash_card::Model>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
+	cloze::validate(&body.content)?;
+	quiz::validate(&body.content)?;
+	markdown::sanitize(&mut body.content)?;
+	math::validate(&body.content)?;
+	ipa::normalize(&mut body.content)?;
+	media::upload_inlined(
+		&conn,
+		storage.as_ref(),
+		user.id,
+		std::slice::from_mut(&mut body),
+	)
+	.await?;
 	body.uid = Uuid::new_v4();
 	body.creator = user.id;
+	body.created = Utc::now();
+	body.updated = body.created;
 
+	let txn = conn.begin().await.map_err(internal_error)?;
 	FlashCard::insert(flash_card::ActiveModel {
 		uid: Set(body.uid),
 		creator: Set(body.creator),
 		share: Set(body.share),
 		content: Set(body.content.clone()),
+		created: Set(body.created),
+		updated: Set(body.updated),
 	})
-	.exec(&conn)
+	.exec(&txn)
 	.await
 	.map_err(internal_error)?;
+	media::link(&txn, user.id, [&body]).await?;
+	txn.commit().await.map_err(internal_error)?;
+	tts::record_later(conn, storage, tts, vec![body.uid]);
 
 	Ok((
 		StatusCode::CREATED,
@@ -37,29 +85,201 @@ async fn create(
 	))
 }
 
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CardSort {
+	#[default]
+	Created,
+	Updated,
+}
+
+impl CardSort {
+	pub fn column(self) -> flash_card::Column {
+		match self {
+			Self::Created => flash_card::Column::Created,
+			Self::Updated => flash_card::Column::Updated,
+		}
+	}
+}
+
+/// Cards in the deck, or in any deck.
+fn cards_in(deck: Option<Uuid>) -> sea_query::SelectStatement {
+	let mut select = sea_query::Query::select();
+	select.column(deck_cards::Column::Card).from(DeckCards);
+	if let Some(deck) = deck {
+		select.and_where(deck_cards::Column::Deck.eq(deck));
+	}
+	select
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+	#[serde(default)]
+	limit: Option<u64>,
+	#[serde(default)]
+	after: Option<page::Cursor>,
+	#[serde(default)]
+	sort: CardSort,
+	#[serde(default)]
+	order: page::Order,
+	#[serde(default)]
+	share: Option<Share>,
+	/// Only cards in this deck.
+	#[serde(default)]
+	deck: Option<Uuid>,
+	/// Only cards that are in no deck.
+	#[serde(default)]
+	orphaned: bool,
+}
+
+/// Lists the user's cards, a page at a time.
 async fn all(
 	State(db): State<DatabaseConnection>,
 	Extension(user): Extension<user::Model>,
+	Query(query): Query<ListQuery>,
+	Query(render): Query<Render>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-	let flashcards = FlashCard::find()
-		.filter(flash_card::Column::Creator.eq(user.id))
-		.all(&db)
-		.await
-		.map_err(internal_error)?;
+	let mut select = FlashCard::find().filter(flash_card::Column::Creator.eq(user.id));
+	if let Some(share) = query.share {
+		select = select.filter(flash_card::Column::Share.eq(share));
+	}
+	if let Some(deck) = query.deck {
+		select = select.filter(flash_card::Column::Uid.in_subquery(cards_in(Some(deck))));
+	}
+	if query.orphaned {
+		select = select.filter(flash_card::Column::Uid.not_in_subquery(cards_in(None)));
+	}
 
-	Ok(Json(flashcards))
+	let mut flashcards = page::fetch(
+		&db,
+		select,
+		query.sort.column(),
+		flash_card::Column::Uid,
+		query.order,
+		query.after,
+		query.limit,
+	)
+	.await?;
+	for card in &mut flashcards.rows {
+		render.apply(card);
+	}
+
+	Ok((flashcards.headers(), Json(flashcards.rows)))
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+	q: String,
+	#[serde(default)]
+	limit: Option<u64>,
+	#[serde(default)]
+	after: Option<page::Cursor>,
+	#[serde(default)]
+	sort: CardSort,
+	#[serde(default)]
+	order: page::Order,
+}
+
+/// Searches the user's cards with the [`card_query`] language, a page at a
+/// time.
+async fn search(
+	State(db): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Query(params): Query<SearchQuery>,
+	Query(render): Query<Render>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let query: card_query::Query = params.q.parse()?;
+
+	// Deck filters are decided here. Content filters are only narrowed down,
+	// as LIKE also sees JSON keys and escapes and SQLite only lowercases ASCII,
+	// and matched exactly on the loaded cards.
+	let mut select = FlashCard::find().filter(flash_card::Column::Creator.eq(user.id));
+	for term in &query.0 {
+		let condition = match &term.filter {
+			Filter::Deck(in_deck) => {
+				let cards = cards_in(match in_deck {
+					InDeck::Any => None,
+					InDeck::Deck(deck) => Some(*deck),
+				});
+				if term.negated {
+					flash_card::Column::Uid.not_in_subquery(cards)
+				} else {
+					flash_card::Column::Uid.in_subquery(cards)
+				}
+			}
+			Filter::Text(text)
+				if !term.negated && text.is_ascii() && !text.contains(['"', '\\']) =>
+			{
+				search::content_like(search::like(text))
+			}
+			Filter::Lang(lang) if !term.negated => {
+				search::content_like(search::language_like(lang))
+			}
+			_ => continue,
+		};
+		select = select.filter(condition);
+	}
+
+	let limit = params
+		.limit
+		.unwrap_or(page::DEFAULT_LIMIT)
+		.clamp(1, page::MAX_LIMIT);
+	let mut found = Vec::new();
+	let mut next = None;
+	let mut after = params.after;
+	'scan: loop {
+		let batch = page::fetch(
+			&db,
+			select.clone(),
+			params.sort.column(),
+			flash_card::Column::Uid,
+			params.order,
+			after,
+			Some(SEARCH_BATCH),
+		)
+		.await?;
+		after = batch.next;
+
+		for card in batch.rows {
+			if query.matches(&card.content) {
+				found.push(card);
+				if found.len() as u64 >= limit {
+					next = found.last().map(|card| {
+						page::Cursor::of::<FlashCard>(
+							card,
+							params.sort.column(),
+							flash_card::Column::Uid,
+						)
+					});
+					break 'scan;
+				}
+			}
+		}
+		if after.is_none() {
+			break;
+		}
+	}
+	let next = next.transpose()?;
+	for card in &mut found {
+		render.apply(card);
+	}
+
+	Ok((page::headers(next.as_ref()), Json(found)))
 }
 
 async fn get_one(
 	State(db): State<DatabaseConnection>,
 	Extension(user): Extension<user::Model>,
 	Path(uuid): Path<Uuid>,
+	Query(render): Query<Render>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-	let flashcard = FlashCard::find_by_id(uuid)
+	let mut flashcard = FlashCard::find_by_id(uuid)
 		.one(&db)
 		.await
 		.map_err(internal_error)?
 		.ok_or_else(|| (StatusCode::NOT_FOUND, "Not found".to_string()))?;
+	quiz::redact(&mut flashcard, user.id);
+	render.apply(&mut flashcard);
 
 	match flashcard.share {
 		Share::Public => Ok(Json(flashcard)),
@@ -73,24 +293,145 @@ async fn get_one(
 	}
 }
 
+#[derive(Deserialize)]
+pub struct Choice {
+	/// Index of the question among the card's sections, the first question by
+	/// default.
+	#[serde(default)]
+	section: Option<usize>,
+	choices: Vec<String>,
+}
+
+/// Grades the options picked for a multiple choice question.
+async fn choose(
+	State(db): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uuid): Path<Uuid>,
+	Json(body): Json<Choice>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let card = FlashCard::find_by_id(uuid)
+		.filter(
+			flash_card::Column::Share
+				.eq(Share::Public)
+				.or(flash_card::Column::Creator.eq(user.id)),
+		)
+		.one(&db)
+		.await
+		.map_err(internal_error)?
+		.ok_or_else(|| (StatusCode::NOT_FOUND, "Not found".to_string()))?;
+
+	let question = match body.section {
+		Some(i) => card.content.0.get(i),
+		None => card
+			.content
+			.0
+			.iter()
+			.find(|s| matches!(s, FlashCardSection::MultipleChoice(_))),
+	};
+	let Some(FlashCardSection::MultipleChoice(question)) = question else {
+		return Err((
+			StatusCode::UNPROCESSABLE_ENTITY,
+			"Not a multiple choice question".to_string(),
+		));
+	};
+
+	Ok(Json(quiz::grade(question, &body.choices)))
+}
+
+#[derive(Deserialize)]
+pub struct Typed {
+	/// Index of the section answered, the first one with an answer by default.
+	#[serde(default)]
+	section: Option<usize>,
+	/// Language of the answer, picking the side of a `Lang` section the way
+	/// [`Language::resolve`] does. Also sets
+	/// how loose matching folds characters.
+	#[serde(default)]
+	lang: Option<Language>,
+	text: String,
+	#[serde(default)]
+	matching: Matching,
+	/// Typos fuzzy matching lets through, one per four characters by default.
+	#[serde(default)]
+	typos: Option<usize>,
+}
+
+/// Checks an answer typed for a card.
+async fn check_answer(
+	State(db): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uuid): Path<Uuid>,
+	Json(body): Json<Typed>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let card = FlashCard::find_by_id(uuid)
+		.filter(
+			flash_card::Column::Share
+				.eq(Share::Public)
+				.or(flash_card::Column::Creator.eq(user.id)),
+		)
+		.one(&db)
+		.await
+		.map_err(internal_error)?
+		.ok_or_else(|| (StatusCode::NOT_FOUND, "Not found".to_string()))?;
+
+	let accepted = answer::expected(&card.content, body.section, body.lang.as_ref())?;
+	let checked = answer::check(
+		&accepted,
+		&body.text,
+		body.matching,
+		body.typos,
+		body.lang.as_ref(),
+	)?;
+	Ok(Json(checked))
+}
+
 async fn update(
 	State(conn): State<DatabaseConnection>,
+	State(storage): State<Arc<dyn Storage>>,
+	State(tts): State<Arc<dyn Tts>>,
 	Extension(user): Extension<user::Model>,
 	Path(uuid): Path<Uuid>,
-	Json(body): Json<flash_card::Model>,
+	Json(mut body): Json<flash_card::Model>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let Some(old) = FlashCard::find_by_id(uuid)
+		.filter(flash_card::Column::Creator.eq(user.id))
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+	else {
+		return Err((StatusCode::NOT_FOUND, "Not found".to_string()));
+	};
+
+	cloze::validate(&body.content)?;
+	quiz::validate(&body.content)?;
+	markdown::sanitize(&mut body.content)?;
+	math::validate(&body.content)?;
+	ipa::normalize_changed(&mut body.content, &old.content)?;
+	media::upload_inlined(
+		&conn,
+		storage.as_ref(),
+		user.id,
+		std::slice::from_mut(&mut body),
+	)
+	.await?;
+	body.uid = uuid;
 	let flashcard = flash_card::ActiveModel {
 		uid: Set(uuid),
 		share: Set(body.share),
 		content: Set(body.content.clone()),
+		updated: Set(Utc::now()),
 		..Default::default()
 	};
 
+	let txn = conn.begin().await.map_err(internal_error)?;
 	FlashCard::update(flashcard)
 		.filter(flash_card::Column::Creator.eq(user.id))
-		.exec(&conn)
+		.exec(&txn)
 		.await
 		.map_err(internal_error)?;
+	media::link(&txn, user.id, [&body]).await?;
+	txn.commit().await.map_err(internal_error)?;
+	tts::record_later(conn, storage, tts, vec![body.uid]);
 	Ok(StatusCode::NO_CONTENT)
 }
 
@@ -120,8 +461,266 @@ pub fn router() -> Router<AppState> {
 	Router::new()
 		.route("/", post(create))
 		.route("/", get(all))
+		.route("/search", get(search))
 		.route("/:id", get(get_one))
 		.route("/:id", put(update))
 		.route("/:id", delete(delete_card))
+		.route("/:id/choice", post(choose))
+		.route("/:id/answer", post(check_answer))
 		.route_layer(middleware::from_fn(session::auth))
 }
+
+#[cfg(test)]
+mod tests {
+	use std::{os::unix::fs::PermissionsExt, path::Path, time::Duration};
+
+	use serde_json::{json, Value};
+	use tempfile::TempDir;
+	use uuid::Uuid;
+
+	use crate::testing::{titled, uid, Reply, TestApp, OTHER, USER};
+
+	/// Stands in for eSpeak NG, with Polish and English voices. What it says is
+	/// logged to `spoken`.
+	const ESPEAK: &str = r#"#!/bin/sh
+if [ "$1" = --voices ]; then
+	echo 'Pty Language       Age/Gender VoiceName          File                 Other Languages'
+	echo ' 5  pl              --/M      Polish             zlw/pl'
+	echo ' 5  en              --/M      English_(Great_Britain) gmw/en'
+	exit
+fi
+text=$(cat)
+echo "$3 $text" >> "$(dirname "$0")/spoken"
+printf 'fLaC %s' "$text"
+"#;
+
+	fn uids(list: &Value) -> Vec<Uuid> {
+		list.as_array().unwrap().iter().map(uid).collect()
+	}
+
+	fn item(item: Value) -> Value {
+		json!([{"type": "Item", "content": item}])
+	}
+
+	fn espeak(dir: &Path) -> String {
+		let program = dir.join("espeak-ng");
+		std::fs::write(&program, ESPEAK).unwrap();
+		std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+		program.display().to_string()
+	}
+
+	/// The content of a card once its terms were recorded in the background.
+	async fn recorded(app: &TestApp, card: Uuid) -> Value {
+		for _ in 0..100 {
+			let reply = app
+				.call("GET", &format!("/api/flashcard/{card}"), None)
+				.await;
+			let content = &reply.json()["content"];
+			if content.to_string().contains("audioUrl") {
+				return content.clone();
+			}
+			tokio::time::sleep(Duration::from_millis(50)).await;
+		}
+		panic!("Card {card} was never recorded");
+	}
+
+	async fn save(app: &TestApp, method: &str, uri: &str, content: Value) -> Reply {
+		let card = json!({
+			"uid": Uuid::nil(),
+			"creator": USER,
+			"share": "Public",
+			"content": content,
+		});
+		app.call(method, uri, Some(card)).await
+	}
+
+	#[tokio::test]
+	async fn lists_cards_by_deck_and_share() {
+		let app = TestApp::new().await;
+		let deck = app.deck(USER, "Animals", "Private", &[titled("kot")]).await;
+		let in_deck = uids(
+			&app.call("GET", &format!("/api/deck/{deck}/cards"), None)
+				.await
+				.json(),
+		);
+		let loose = app.card(USER, "Public", titled("pies")).await;
+		app.card(OTHER, "Public", titled("ryba")).await;
+
+		let all = app.call("GET", "/api/flashcard", None).await.json();
+		assert_eq!(uids(&all), [in_deck[0], loose]);
+		let listed = app
+			.call("GET", &format!("/api/flashcard?deck={deck}"), None)
+			.await
+			.json();
+		assert_eq!(uids(&listed), in_deck);
+		let listed = app
+			.call("GET", "/api/flashcard?orphaned=true", None)
+			.await
+			.json();
+		assert_eq!(uids(&listed), [loose]);
+		let listed = app
+			.call("GET", "/api/flashcard?share=Public&order=desc", None)
+			.await
+			.json();
+		assert_eq!(uids(&listed), [loose]);
+	}
+
+	#[tokio::test]
+	async fn pages_through_cards() {
+		let app = TestApp::new().await;
+		let mut cards = Vec::new();
+		for title in ["a", "b", "c"] {
+			cards.push(app.card(USER, "Private", titled(title)).await);
+		}
+
+		let first = app.call("GET", "/api/flashcard?limit=2", None).await;
+		assert_eq!(uids(&first.json()), cards[..2]);
+		let next = first.header("next-cursor").unwrap();
+		let second = app
+			.call("GET", &format!("/api/flashcard?limit=2&after={next}"), None)
+			.await;
+		assert_eq!(uids(&second.json()), cards[2..]);
+		assert_eq!(second.header("next-cursor"), None);
+
+		let reply = app.call("GET", "/api/flashcard?after=garbage", None).await;
+		assert_eq!(reply.status, 400);
+	}
+
+	#[tokio::test]
+	async fn markdown_is_made_safe_to_share() {
+		let app = TestApp::new().await;
+		let card = app
+			.card(
+				USER,
+				"Public",
+				item(json!({"markdown": {
+					"source": "**kot** <script>alert(1)</script>",
+					"html": "<script>alert(2)</script>",
+				}})),
+			)
+			.await;
+
+		let uri = format!("/api/flashcard/{card}");
+		let plain = app.call_as(OTHER, "GET", &uri, None).await.json();
+		assert_eq!(
+			plain["content"],
+			item(json!({"markdown": {"source": "**kot** \\<script>alert(1)\\</script>"}}))
+		);
+		let rendered = app
+			.call_as(OTHER, "GET", &format!("{uri}?html=true"), None)
+			.await
+			.json();
+		let html = rendered["content"][0]["content"]["markdown"]["html"]
+			.as_str()
+			.unwrap();
+		assert!(html.contains("<strong>kot</strong>"), "{html}");
+		assert!(!html.contains("<script"), "{html}");
+
+		let link = item(json!({"markdown": {"source": "[kot](javascript:alert(1))"}}));
+		let reply = save(&app, "POST", "/api/flashcard", link.clone()).await;
+		assert_eq!(reply.status, 422);
+		let reply = save(&app, "PUT", &uri, link).await;
+		assert_eq!(reply.status, 422);
+		let long = item(json!({"markdown": {"source": "a".repeat(10_001)}}));
+		let reply = save(&app, "POST", "/api/flashcard", long).await;
+		assert_eq!(reply.status, 422);
+	}
+
+	#[tokio::test]
+	async fn formulas_are_checked_and_rendered() {
+		let app = TestApp::new().await;
+		let formula = item(json!({"math": {"source": "\\frac{a}{b}", "block": true}}));
+		let card = app.card(USER, "Private", formula.clone()).await;
+
+		let uri = format!("/api/flashcard/{card}");
+		let plain = app.call("GET", &uri, None).await.json();
+		assert_eq!(plain["content"], formula);
+		let rendered = app
+			.call("GET", &format!("{uri}?html=true"), None)
+			.await
+			.json();
+		let mathml = rendered["content"][0]["content"]["math"]["mathml"]
+			.as_str()
+			.unwrap();
+		assert!(mathml.contains("<mfrac>"), "{mathml}");
+		assert!(mathml.contains("display=\"block\""), "{mathml}");
+
+		let broken = item(json!({"math": {"source": "\\frac{a"}}));
+		let reply = save(&app, "POST", "/api/flashcard", broken.clone()).await;
+		assert_eq!(reply.status, 422);
+		assert!(
+			String::from_utf8_lossy(&reply.body).starts_with("Invalid formula"),
+			"{:?}",
+			reply.body
+		);
+		let reply = save(&app, "PUT", &uri, broken).await;
+		assert_eq!(reply.status, 422);
+		let long = item(json!({"math": {"source": "x".repeat(2_001)}}));
+		let reply = save(&app, "POST", "/api/flashcard", long).await;
+		assert_eq!(reply.status, 422);
+	}
+
+	#[tokio::test]
+	async fn terms_are_spoken_once() {
+		let dir = TempDir::new().unwrap();
+		let program = espeak(dir.path());
+		let app = TestApp::with_config(|config| config.espeak = Some(program)).await;
+		let spoken = || std::fs::read_to_string(dir.path().join("spoken")).unwrap();
+
+		let lang = |terms: Value| json!([{"type": "Lang", "content": terms}]);
+		let card = app
+			.card(
+				USER,
+				"Public",
+				lang(json!({"pl": {"title": "kot"}, "en": {"title": "cat"}})),
+			)
+			.await;
+		let content = recorded(&app, card).await;
+		let audio = |content: &Value, lang: &str| {
+			content[1]["content"][lang]["pronunciation"]["audioUrl"]
+				.as_str()
+				.unwrap()
+				.to_string()
+		};
+		let kot = audio(&content, "pl");
+		assert_eq!(app.call("GET", &kot, None).await.body, "fLaC kot");
+		assert_eq!(
+			app.call("GET", &audio(&content, "en"), None).await.body,
+			"fLaC cat"
+		);
+		let mut said: Vec<String> = spoken().lines().map(String::from).collect();
+		said.sort();
+		assert_eq!(said, ["en cat", "pl kot"]);
+
+		// Terms said before are not spoken again, whoever's cards they are on.
+		// Languages without a voice are left as they are.
+		let card = app
+			.card(
+				USER,
+				"Private",
+				lang(json!({"pl": {"title": "kot"}, "de": {"title": "Katze"}})),
+			)
+			.await;
+		let content = recorded(&app, card).await;
+		assert_eq!(audio(&content, "pl"), kot);
+		assert!(content[1]["content"].get("de").is_none());
+		let reply = app
+			.call_as(
+				OTHER,
+				"POST",
+				"/api/flashcard",
+				Some(json!({
+					"uid": Uuid::nil(),
+					"creator": OTHER,
+					"share": "Public",
+					"content": lang(json!({"pl": {"title": "kot"}})),
+				})),
+			)
+			.await;
+		let content = recorded(&app, uid(&reply.json())).await;
+		let theirs = audio(&content, "pl");
+		assert_ne!(theirs, kot, "media are per user");
+		assert_eq!(app.call("GET", &theirs, None).await.body, "fLaC kot");
+		assert_eq!(spoken().lines().count(), 2);
+	}
+}
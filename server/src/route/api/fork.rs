@@ -0,0 +1,588 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+	extract::{Path, State},
+	http::{header::LOCATION, StatusCode},
+	middleware,
+	response::IntoResponse,
+	routing::{get, post},
+	Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+	ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, JoinType, ModelTrait,
+	QueryFilter, QueryOrder, QuerySelect, RelationTrait, TransactionTrait,
+};
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use uuid::Uuid;
+
+use crate::{
+	app::AppState,
+	internal_error,
+	media::{self, hex},
+	quiz, session,
+};
+use entity::{
+	card_fork, custom::flash_card::FlashCardContent, deck, deck_cards, deck_fork, flash_card,
+	prelude::*, sea_orm_active_enums::Share, user,
+};
+
+/// Hash of a card's content, to tell whether it changed.
+///
+/// Goes through `serde_json::Value`, whose maps are sorted, so `Lang` sections
+/// hash the same whatever order their languages are in.
+fn content_hash(content: &FlashCardContent) -> String {
+	let json = serde_json::to_value(content)
+		.map(|v| v.to_string())
+		.unwrap_or_default();
+	hex(&Sha1::digest(json.as_bytes()))
+}
+
+/// Copies a deck and its cards into the user's library, keeping track of
+/// where they came from.
+async fn fork(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let Some(source) = Deck::find_by_id(uid)
+		.filter(
+			deck::Column::Share.eq(Share::Public).or(deck::Column::Share
+				.eq(Share::Private)
+				.and(deck::Column::Creator.eq(user.id))),
+		)
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+	else {
+		return Err((StatusCode::NOT_FOUND, "Not found".to_string()));
+	};
+
+	let cards = source
+		.find_related(FlashCard)
+		.filter(
+			flash_card::Column::Share
+				.eq(Share::Public)
+				.or(flash_card::Column::Share
+					.eq(Share::Private)
+					.and(flash_card::Column::Creator.eq(user.id))),
+		)
+		.all(&conn)
+		.await
+		.map_err(internal_error)?;
+
+	let now = Utc::now();
+	let deck = deck::Model {
+		uid: Uuid::new_v4(),
+		name: source.name.clone(),
+		creator: user.id,
+		kind: source.kind,
+		share: Share::Private,
+		created: now,
+		updated: now,
+	};
+	// Cards that are nothing but someone else's questions have nothing to copy.
+	let copies: Vec<(flash_card::Model, &flash_card::Model)> = cards
+		.iter()
+		.filter_map(|card| {
+			let content = quiz::copyable(card, user.id);
+			(!content.0.is_empty()).then(|| {
+				(
+					flash_card::Model {
+						uid: Uuid::new_v4(),
+						creator: user.id,
+						share: Share::Private,
+						content,
+						created: now,
+						updated: now,
+					},
+					card,
+				)
+			})
+		})
+		.collect();
+
+	let txn = conn.begin().await.map_err(internal_error)?;
+	Deck::insert(deck::ActiveModel {
+		uid: Set(deck.uid),
+		name: Set(deck.name.clone()),
+		creator: Set(deck.creator),
+		kind: Set(deck.kind),
+		share: Set(deck.share),
+		created: Set(deck.created),
+		updated: Set(deck.updated),
+	})
+	.exec(&txn)
+	.await
+	.map_err(internal_error)?;
+
+	DeckFork::insert(deck_fork::ActiveModel {
+		deck: Set(deck.uid),
+		source: Set(Some(source.uid)),
+		created: Set(now),
+	})
+	.exec(&txn)
+	.await
+	.map_err(internal_error)?;
+
+	for chunk in copies.chunks(1000) {
+		FlashCard::insert_many(chunk.iter().map(|(card, _)| flash_card::ActiveModel {
+			uid: Set(card.uid),
+			creator: Set(card.creator),
+			share: Set(card.share),
+			content: Set(card.content.clone()),
+			created: Set(card.created),
+			updated: Set(card.updated),
+		}))
+		.exec(&txn)
+		.await
+		.map_err(internal_error)?;
+
+		DeckCards::insert_many(chunk.iter().map(|(card, _)| deck_cards::ActiveModel {
+			card: Set(card.uid),
+			deck: Set(deck.uid),
+		}))
+		.exec(&txn)
+		.await
+		.map_err(internal_error)?;
+
+		CardFork::insert_many(chunk.iter().map(|(card, source)| card_fork::ActiveModel {
+			card: Set(card.uid),
+			source: Set(Some(source.uid)),
+			source_hash: Set(content_hash(&source.content)),
+		}))
+		.exec(&txn)
+		.await
+		.map_err(internal_error)?;
+	}
+	media::link(&txn, user.id, copies.iter().map(|(card, _)| card)).await?;
+	txn.commit().await.map_err(internal_error)?;
+
+	Ok((
+		StatusCode::CREATED,
+		[(LOCATION, deck.uid.to_string())],
+		Json(deck),
+	))
+}
+
+#[derive(Serialize)]
+pub struct Fork {
+	deck: Uuid,
+	user: u32,
+	display: Option<String>,
+	created: DateTime<Utc>,
+}
+
+/// Lists who forked one of the user's decks.
+async fn forks(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let Some(deck) = Deck::find_by_id(uid)
+		.filter(deck::Column::Creator.eq(user.id))
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+	else {
+		return Err((StatusCode::NOT_FOUND, "Not found".to_string()));
+	};
+
+	let forks = DeckFork::find()
+		.filter(deck_fork::Column::Source.eq(deck.uid))
+		.order_by_asc(deck_fork::Column::Created)
+		.join(JoinType::InnerJoin, deck_fork::Relation::Deck2.def())
+		.select_also(Deck)
+		.all(&conn)
+		.await
+		.map_err(internal_error)?;
+
+	let creators: HashSet<u32> = forks
+		.iter()
+		.filter_map(|(_, d)| Some(d.as_ref()?.creator))
+		.collect();
+	let users: HashMap<u32, user::Model> = User::find()
+		.filter(user::Column::Id.is_in(creators))
+		.all(&conn)
+		.await
+		.map_err(internal_error)?
+		.into_iter()
+		.map(|u| (u.id, u))
+		.collect();
+
+	Ok(Json(
+		forks
+			.into_iter()
+			.filter_map(|(fork, deck)| {
+				let deck = deck?;
+				Some(Fork {
+					deck: fork.deck,
+					user: deck.creator,
+					display: users.get(&deck.creator).and_then(|u| u.display.clone()),
+					created: fork.created,
+				})
+			})
+			.collect::<Vec<_>>(),
+	))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+	/// The source card's content differs from when it was forked.
+	Changed,
+	/// The source card was deleted, or is no longer visible.
+	Deleted,
+}
+
+#[derive(Serialize)]
+pub struct ChangedCard {
+	card: Uuid,
+	source: Option<Uuid>,
+	change: Change,
+}
+
+#[derive(Serialize)]
+pub struct Provenance {
+	/// `None` if the source deck was deleted.
+	source: Option<Uuid>,
+	created: DateTime<Utc>,
+	changed: Vec<ChangedCard>,
+	/// Cards of the source deck added since the fork.
+	added: Vec<Uuid>,
+}
+
+/// Says where a forked deck came from and how its source changed since.
+async fn source(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let Some(deck) = Deck::find_by_id(uid)
+		.filter(deck::Column::Creator.eq(user.id))
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+	else {
+		return Err((StatusCode::NOT_FOUND, "Not found".to_string()));
+	};
+	let Some(fork) = DeckFork::find_by_id(deck.uid)
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+	else {
+		return Err((StatusCode::NOT_FOUND, "Not a fork".to_string()));
+	};
+
+	let card_ids: Vec<Uuid> = deck
+		.find_related(FlashCard)
+		.all(&conn)
+		.await
+		.map_err(internal_error)?
+		.into_iter()
+		.map(|c| c.uid)
+		.collect();
+	let links = CardFork::find()
+		.filter(card_fork::Column::Card.is_in(card_ids))
+		.all(&conn)
+		.await
+		.map_err(internal_error)?;
+
+	let visible = flash_card::Column::Share
+		.eq(Share::Public)
+		.or(flash_card::Column::Share
+			.eq(Share::Private)
+			.and(flash_card::Column::Creator.eq(user.id)));
+	let sources: HashMap<Uuid, flash_card::Model> = FlashCard::find()
+		.filter(flash_card::Column::Uid.is_in(links.iter().filter_map(|l| l.source)))
+		.filter(visible.clone())
+		.all(&conn)
+		.await
+		.map_err(internal_error)?
+		.into_iter()
+		.map(|c| (c.uid, c))
+		.collect();
+
+	let changed = links
+		.iter()
+		.filter_map(|link| {
+			let change = match link.source.and_then(|s| sources.get(&s)) {
+				None => Change::Deleted,
+				Some(source) if content_hash(&source.content) != link.source_hash => {
+					Change::Changed
+				}
+				Some(_) => return None,
+			};
+			Some(ChangedCard {
+				card: link.card,
+				source: link.source,
+				change,
+			})
+		})
+		.collect();
+
+	let added = match fork.source {
+		Some(source) => {
+			let known: HashSet<Uuid> = links.iter().filter_map(|l| l.source).collect();
+			DeckCards::find()
+				.filter(deck_cards::Column::Deck.eq(source))
+				.find_also_related(FlashCard)
+				.filter(visible)
+				.all(&conn)
+				.await
+				.map_err(internal_error)?
+				.into_iter()
+				.map(|(link, _)| link.card)
+				.filter(|card| !known.contains(card))
+				.collect()
+		}
+		None => Vec::new(),
+	};
+
+	Ok(Json(Provenance {
+		source: fork.source,
+		created: fork.created,
+		changed,
+		added,
+	}))
+}
+
+pub fn router() -> Router<AppState> {
+	Router::new()
+		.route("/:id/fork", post(fork))
+		.route("/:id/forks", get(forks))
+		.route("/:id/source", get(source))
+		.route_layer(middleware::from_fn(session::auth))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use serde_json::json;
+	use uuid::Uuid;
+
+	use crate::testing::{titled, uid, TestApp, OTHER, USER};
+
+	/// The cards of a deck by their titles.
+	async fn by_title(app: &TestApp, user: u32, deck: Uuid) -> HashMap<String, Uuid> {
+		let cards = app
+			.call_as(user, "GET", &format!("/api/deck/{deck}/cards"), None)
+			.await
+			.json();
+		cards
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|card| {
+				let title = card["content"][0]["content"]["title"].as_str().unwrap();
+				(title.to_string(), uid(card))
+			})
+			.collect()
+	}
+
+	#[tokio::test]
+	async fn forks_keep_track_of_their_source() {
+		let app = TestApp::new().await;
+		let source = app
+			.deck(OTHER, "Animals", "Public", &[titled("kot"), titled("pies")])
+			.await;
+		let hidden = app.card(OTHER, "Private", titled("mysz")).await;
+		app.call_as(
+			OTHER,
+			"PATCH",
+			&format!("/api/deck/{source}/cards"),
+			Some(json!({"add": [hidden]})),
+		)
+		.await;
+
+		let reply = app
+			.call("POST", &format!("/api/deck/{source}/fork"), None)
+			.await;
+		assert_eq!(reply.status, 201);
+		let fork = reply.json();
+		assert_eq!(fork["creator"], USER);
+		assert_eq!(fork["share"], "Private");
+		assert_eq!(fork["name"], "Animals");
+		let fork = uid(&fork);
+		let copies = by_title(&app, USER, fork).await;
+		assert_eq!(copies.len(), 2, "{copies:?}");
+
+		let provenance = format!("/api/deck/{fork}/source");
+		let reply = app.call("GET", &provenance, None).await.json();
+		assert_eq!(reply["source"], source.to_string());
+		assert_eq!(reply["changed"], json!([]));
+		assert_eq!(reply["added"], json!([]));
+
+		let originals = by_title(&app, OTHER, source).await;
+		let reply = app
+			.call_as(
+				OTHER,
+				"PUT",
+				&format!("/api/flashcard/{}", originals["kot"]),
+				Some(json!({"share": "Public", "content": titled("kot!")})),
+			)
+			.await;
+		assert!(reply.status.is_success(), "{:?}", reply.body);
+		app.call_as(
+			OTHER,
+			"PATCH",
+			&format!("/api/deck/{source}/cards"),
+			Some(json!({"remove": [originals["pies"]]})),
+		)
+		.await;
+		let reply = app
+			.call_as(
+				OTHER,
+				"DELETE",
+				&format!("/api/flashcard/{}", originals["pies"]),
+				None,
+			)
+			.await;
+		assert!(reply.status.is_success(), "{:?}", reply.body);
+		let added = app.card(OTHER, "Public", titled("ryba")).await;
+		app.call_as(
+			OTHER,
+			"PATCH",
+			&format!("/api/deck/{source}/cards"),
+			Some(json!({"add": [added]})),
+		)
+		.await;
+
+		let reply = app.call("GET", &provenance, None).await.json();
+		let mut changed: Vec<(String, String)> = reply["changed"]
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|c| {
+				(
+					c["card"].as_str().unwrap().to_string(),
+					c["change"].as_str().unwrap().to_string(),
+				)
+			})
+			.collect();
+		changed.sort_by_key(|(_, change)| change.clone());
+		assert_eq!(
+			changed,
+			[
+				(copies["kot"].to_string(), "changed".to_string()),
+				(copies["pies"].to_string(), "deleted".to_string()),
+			]
+		);
+		assert_eq!(reply["added"], json!([added]));
+
+		let forks = app
+			.call_as(OTHER, "GET", &format!("/api/deck/{source}/forks"), None)
+			.await
+			.json();
+		assert_eq!(forks.as_array().unwrap().len(), 1);
+		assert_eq!(forks[0]["deck"], fork.to_string());
+		assert_eq!(forks[0]["user"], USER);
+		let reply = app
+			.call("GET", &format!("/api/deck/{source}/forks"), None)
+			.await;
+		assert_eq!(reply.status, 404);
+		let reply = app
+			.call_as(OTHER, "GET", &format!("/api/deck/{source}/source"), None)
+			.await;
+		assert_eq!(reply.status, 404);
+	}
+
+	#[tokio::test]
+	async fn forks_leave_out_questions_they_cant_grade() {
+		let app = TestApp::new().await;
+		let question = json!({"type": "MultipleChoice", "content": {
+			"prompt": "Kot?",
+			"options": ["cat", "dog"],
+			"correct": [0],
+		}});
+		let title = json!({"type": "Item", "content": {"title": "Kot"}});
+		let source = app
+			.deck(
+				OTHER,
+				"Quiz",
+				"Public",
+				&[json!([title, question]), json!([question])],
+			)
+			.await;
+
+		let reply = app
+			.call("POST", &format!("/api/deck/{source}/fork"), None)
+			.await;
+		assert_eq!(reply.status, 201);
+		let fork = uid(&reply.json());
+		let cards = app
+			.call("GET", &format!("/api/deck/{fork}/cards"), None)
+			.await
+			.json();
+		assert_eq!(cards.as_array().unwrap().len(), 1);
+		assert_eq!(cards[0]["content"], json!([title]));
+		let card = uid(&cards[0]);
+
+		// The copy can be edited and studied.
+		let reply = app
+			.call(
+				"PUT",
+				&format!("/api/flashcard/{card}"),
+				Some(cards[0].clone()),
+			)
+			.await;
+		assert_eq!(reply.status, 204);
+		let reply = app
+			.call("POST", "/api/study", Some(json!({ "deck": fork })))
+			.await;
+		let session = uid(&reply.json());
+		let next = app
+			.call("GET", &format!("/api/study/{session}/next"), None)
+			.await
+			.json();
+		assert_eq!(next["card"]["uid"], card.to_string());
+		let reply = app
+			.call(
+				"POST",
+				&format!("/api/study/{session}/answer"),
+				Some(json!({"card": card, "grade": "Good"})),
+			)
+			.await;
+		assert_eq!(reply.status, 200);
+		let reply = app
+			.call(
+				"POST",
+				&format!("/api/flashcard/{card}/choice"),
+				Some(json!({"choices": []})),
+			)
+			.await;
+		assert_eq!(reply.status, 422);
+	}
+
+	#[tokio::test]
+	async fn forks_of_own_decks_keep_questions() {
+		let app = TestApp::new().await;
+		let question = json!([{"type": "MultipleChoice", "content": {
+			"prompt": "Kot?",
+			"options": ["cat", "dog"],
+			"correct": [0],
+		}}]);
+		let source = app.deck(0, "Quiz", "Private", &[question]).await;
+		let fork = uid(&app
+			.call("POST", &format!("/api/deck/{source}/fork"), None)
+			.await
+			.json());
+		let cards = app
+			.call("GET", &format!("/api/deck/{fork}/cards"), None)
+			.await
+			.json();
+		assert_eq!(cards[0]["content"][0]["content"]["correct"], json!([0]));
+
+		let choice = format!("/api/flashcard/{}/choice", uid(&cards[0]));
+		let reply = app
+			.call("POST", &choice, Some(json!({"choices": ["cat"]})))
+			.await;
+		assert_eq!(reply.json()["correct"], true);
+		let reply = app
+			.call("POST", &choice, Some(json!({"choices": []})))
+			.await;
+		assert_eq!(reply.json()["correct"], false);
+	}
+}
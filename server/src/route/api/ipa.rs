@@ -0,0 +1,35 @@
+use axum::{http::StatusCode, middleware, routing::post, Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::{app::AppState, ipa, session};
+
+#[derive(Deserialize)]
+struct ConvertBody {
+	text: String,
+}
+
+#[derive(Serialize)]
+struct Converted {
+	ipa: String,
+	/// Characters that aren't IPA, which saving the transcription would reject.
+	invalid: Vec<char>,
+}
+
+/// Converts a transcription typed in X-SAMPA to IPA the way saving a card
+/// does, for the editor to preview.
+async fn convert(Json(body): Json<ConvertBody>) -> Result<Json<Converted>, (StatusCode, String)> {
+	if body.text.chars().count() > ipa::MAX_LENGTH {
+		return Err(ipa::Error::TooLong.into());
+	}
+	let converted = ipa::convert(body.text.trim());
+	Ok(Json(Converted {
+		invalid: ipa::invalid(&converted),
+		ipa: converted,
+	}))
+}
+
+pub fn router() -> Router<AppState> {
+	Router::new()
+		.route("/convert", post(convert))
+		.route_layer(middleware::from_fn(session::auth))
+}
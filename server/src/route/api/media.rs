@@ -0,0 +1,457 @@
+use std::sync::Arc;
+
+use axum::{
+	body::Bytes,
+	extract::{DefaultBodyLimit, Path, Query, State},
+	http::{
+		header::{
+			CACHE_CONTROL, CONTENT_SECURITY_POLICY, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LOCATION,
+			X_CONTENT_TYPE_OPTIONS,
+		},
+		HeaderMap, StatusCode,
+	},
+	middleware,
+	response::{IntoResponse, Response},
+	routing::{get, post},
+	Extension, Json, Router,
+};
+use sea_orm::{DatabaseConnection, EntityTrait, QueryFilter};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+	app::AppState,
+	internal_error, media, session,
+	storage::{self, Storage},
+};
+use entity::{prelude::*, sea_orm_active_enums::MediaSize, user};
+
+const SVG: &str = "image/svg+xml";
+
+/// Uploads an image or recording for the user's cards to link to.
+///
+/// The type is told from the contents. Uploading a file again gives back the
+/// media it was first uploaded as.
+async fn upload(
+	State(conn): State<DatabaseConnection>,
+	State(storage): State<Arc<dyn Storage>>,
+	Extension(user): Extension<user::Model>,
+	body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let (media, new) = media::store(&conn, storage.as_ref(), user.id, &body).await?;
+
+	Ok((
+		if new {
+			StatusCode::CREATED
+		} else {
+			StatusCode::OK
+		},
+		[(LOCATION, media::path(media.uid))],
+		Json(media),
+	))
+}
+
+#[derive(Deserialize)]
+pub struct DownloadQuery {
+	/// A WebP rendition of a picture rather than the picture itself. Media
+	/// without one are sent as they are.
+	#[serde(default)]
+	size: Option<MediaSize>,
+}
+
+/// Downloads media the user uploaded, or that a card they can see uses.
+///
+/// Storage that hands out its own download URLs is redirected to.
+async fn download(
+	State(conn): State<DatabaseConnection>,
+	State(storage): State<Arc<dyn Storage>>,
+	Extension(user): Extension<user::Model>,
+	Path(uuid): Path<Uuid>,
+	Query(query): Query<DownloadQuery>,
+	headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+	let media = Media::find_by_id(uuid)
+		.filter(media::visible(user.id))
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+		.ok_or_else(|| (StatusCode::NOT_FOUND, "Not found".to_string()))?;
+
+	let variant = match query.size {
+		Some(size) => MediaVariant::find_by_id((media.uid, size))
+			.one(&conn)
+			.await
+			.map_err(internal_error)?,
+		None => None,
+	};
+	let (hash, mime) = match variant {
+		Some(variant) => (variant.hash, variant.mime),
+		None => (media.hash, media.mime),
+	};
+
+	// The contents of a media never change.
+	let etag = format!("\"{hash}\"");
+	let cache = [
+		(ETAG, etag.clone()),
+		(
+			CACHE_CONTROL,
+			"private, max-age=31536000, immutable".to_string(),
+		),
+	];
+	if headers
+		.get(IF_NONE_MATCH)
+		.is_some_and(|tag| tag.as_bytes() == etag.as_bytes())
+	{
+		return Ok((StatusCode::NOT_MODIFIED, cache).into_response());
+	}
+
+	// SVGs go through the server for the headers below, which pre-signed URLs
+	// can't set.
+	let url = match mime.as_str() {
+		SVG => None,
+		_ => storage.url(&hash, &mime).await.map_err(internal_error)?,
+	};
+	if let Some(url) = url {
+		// Cached for less time than the URL is valid for.
+		let max_age = storage::URL_EXPIRY / 2;
+		return Ok((
+			StatusCode::TEMPORARY_REDIRECT,
+			[
+				(LOCATION, url),
+				(CACHE_CONTROL, format!("private, max-age={max_age}")),
+			],
+		)
+			.into_response());
+	}
+
+	let data = storage
+		.get(&hash)
+		.await
+		.map_err(internal_error)?
+		.ok_or_else(|| (StatusCode::NOT_FOUND, "Not found".to_string()))?;
+
+	// SVGs can carry scripts, which mustn't run when one is opened directly.
+	Ok((
+		[
+			(CONTENT_TYPE, mime),
+			(X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+			(CONTENT_SECURITY_POLICY, "sandbox".to_string()),
+		],
+		cache,
+		data,
+	)
+		.into_response())
+}
+
+pub fn router() -> Router<AppState> {
+	Router::new()
+		.route(
+			"/",
+			post(upload).layer(DefaultBodyLimit::max(media::MAX_SIZE)),
+		)
+		.route("/:id", get(download))
+		.route_layer(middleware::from_fn(session::auth))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		collections::HashMap,
+		sync::{Arc, Mutex},
+		time::Duration,
+	};
+
+	use axum::{
+		body::Bytes,
+		extract::{Path, State},
+		http::{Request, StatusCode},
+		routing::get,
+		Router,
+	};
+	use chrono::Utc;
+	use entity::{media, prelude::*};
+	use sea_orm::{ActiveValue::Set, EntityTrait, TransactionTrait};
+	use serde_json::{json, Value};
+	use uuid::Uuid;
+
+	use crate::{
+		config::S3Config,
+		media::{collect_garbage, hash},
+		storage::{Storage, S3},
+		testing::{uid, TestApp, OTHER, USER},
+	};
+
+	type Objects = Arc<Mutex<HashMap<String, Bytes>>>;
+
+	/// A bucket on a stand-in for S3 that takes any signature, and the objects
+	/// in it.
+	async fn bucket() -> (S3Config, Objects) {
+		let objects = Objects::default();
+		let router = Router::new()
+			.route(
+				"/bucket/:key",
+				get(
+					|State(objects): State<Objects>, Path(key): Path<String>| async move {
+						let object = objects.lock().unwrap().get(&key).cloned();
+						object.ok_or(StatusCode::NOT_FOUND)
+					},
+				)
+				.put(
+					|State(objects): State<Objects>, Path(key): Path<String>, data: Bytes| async move {
+						objects.lock().unwrap().insert(key, data);
+					},
+				)
+				.delete(
+					|State(objects): State<Objects>, Path(key): Path<String>| async move {
+						objects.lock().unwrap().remove(&key);
+						StatusCode::NO_CONTENT
+					},
+				),
+			)
+			.with_state(objects.clone());
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let endpoint = format!("http://{}", listener.local_addr().unwrap());
+		tokio::spawn(async move { axum::serve(listener, router).await });
+
+		let config = S3Config {
+			endpoint: Some(endpoint),
+			region: "us-east-1".to_string(),
+			bucket: "bucket".to_string(),
+			access_key: Some("key".to_string()),
+			secret_key: Some("secret".to_string()),
+			path_style: true,
+		};
+		(config, objects)
+	}
+
+	const FLAC: &[u8] = b"fLaC not really";
+
+	/// A 1200×600 JPEG with EXIF data giving away where it was taken.
+	fn photo() -> Vec<u8> {
+		let mut jpeg = Vec::new();
+		image::RgbImage::from_fn(1200, 600, |x, _| image::Rgb([(x % 256) as u8, 0, 0]))
+			.write_to(
+				&mut std::io::Cursor::new(&mut jpeg),
+				image::ImageFormat::Jpeg,
+			)
+			.unwrap();
+
+		let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\0\0\0\0\0".to_vec();
+		exif.extend_from_slice(b"GPS 52.2297 N 21.0122 E");
+		let length = (exif.len() + 2) as u16;
+		let mut segment = vec![0xff, 0xe1];
+		segment.extend_from_slice(&length.to_be_bytes());
+		segment.extend_from_slice(&exif);
+		jpeg.splice(2..2, segment);
+		jpeg
+	}
+
+	fn contains(data: &[u8], part: &[u8]) -> bool {
+		data.windows(part.len()).any(|window| window == part)
+	}
+
+	/// Content of a card playing `media`.
+	fn recording(media: Uuid) -> Value {
+		json!([{"type": "Item", "content": {"pronunciation": {
+			"ipa": "kot",
+			"audioUrl": format!("/api/media/{media}"),
+		}}}])
+	}
+
+	#[tokio::test]
+	async fn uploads_are_kept_once_per_user() {
+		let app = TestApp::new().await;
+
+		let reply = app.upload("/api/media", FLAC).await;
+		assert_eq!(reply.status, 201);
+		let first = uid(&reply.json());
+		assert_eq!(reply.json()["mime"], "audio/flac");
+		let again = app.upload("/api/media", FLAC).await;
+		assert_eq!(again.status, 200);
+		assert_eq!(uid(&again.json()), first);
+
+		let reply = app.call("GET", &format!("/api/media/{first}"), None).await;
+		assert_eq!(reply.status, 200);
+		assert_eq!(reply.body, FLAC);
+
+		let reply = app
+			.call_as(OTHER, "GET", &format!("/api/media/{first}"), None)
+			.await;
+		assert_eq!(reply.status, 404);
+		let reply = app
+			.send(
+				OTHER,
+				Request::post("/api/media").body(FLAC.into()).unwrap(),
+			)
+			.await;
+		assert_eq!(reply.status, 201);
+		assert_ne!(uid(&reply.json()), first);
+
+		let reply = app.upload("/api/media", &b"not media"[..]).await;
+		assert_eq!(reply.status, 415);
+	}
+
+	#[tokio::test]
+	async fn pictures_lose_their_metadata_and_get_variants() {
+		let app = TestApp::new().await;
+		let photo = photo();
+		assert!(contains(&photo, b"GPS"));
+
+		let reply = app.upload("/api/media", photo).await;
+		assert_eq!(reply.status, 201, "{:?}", reply.body);
+		let media = reply.json();
+		assert_eq!(media["mime"], "image/jpeg");
+		assert_eq!(
+			(&media["width"], &media["height"]),
+			(&json!(1200), &json!(600))
+		);
+
+		let uri = format!("/api/media/{}", uid(&media));
+		let original = app.call("GET", &uri, None).await;
+		assert_eq!(original.header("content-type"), Some("image/jpeg"));
+		assert!(!contains(&original.body, b"GPS"));
+
+		for (size, width, height) in [
+			("Full", 1200, 600),
+			("Card", 1024, 512),
+			("Thumb", 256, 128),
+		] {
+			let reply = app.call("GET", &format!("{uri}?size={size}"), None).await;
+			assert_eq!(reply.status, 200);
+			assert_eq!(reply.header("content-type"), Some("image/webp"));
+			let variant = image::load_from_memory(&reply.body).unwrap();
+			assert_eq!(
+				(variant.width(), variant.height()),
+				(width, height),
+				"{size}"
+			);
+		}
+
+		// Recordings have no variants, so they are sent as they are.
+		let recording = uid(&app.upload("/api/media", FLAC).await.json());
+		let reply = app
+			.call("GET", &format!("/api/media/{recording}?size=Thumb"), None)
+			.await;
+		assert_eq!(reply.body, FLAC);
+		let reply = app.call("GET", &format!("{uri}?size=Huge"), None).await;
+		assert_eq!(reply.status, 400);
+	}
+
+	#[tokio::test]
+	async fn media_of_public_cards_can_be_seen() {
+		let app = TestApp::new().await;
+		let media = uid(&app.upload("/api/media", FLAC).await.json());
+		app.card(USER, "Private", recording(media)).await;
+		let uri = format!("/api/media/{media}");
+		assert_eq!(app.call_as(OTHER, "GET", &uri, None).await.status, 404);
+		app.card(USER, "Public", recording(media)).await;
+		assert_eq!(app.call_as(OTHER, "GET", &uri, None).await.status, 200);
+	}
+
+	#[tokio::test]
+	async fn unused_media_are_collected() {
+		let app = TestApp::new().await;
+		let used = uid(&app.upload("/api/media", FLAC).await.json());
+		let unused = uid(&app.upload("/api/media", &b"fLaC unused"[..]).await.json());
+		let card = app.card(USER, "Private", recording(used)).await;
+
+		let storage = app.storage();
+		let deleted = collect_garbage(&app.db, &storage, Duration::from_secs(60))
+			.await
+			.unwrap();
+		assert_eq!(deleted, 0, "new media are left alone");
+		let deleted = collect_garbage(&app.db, &storage, Duration::ZERO)
+			.await
+			.unwrap();
+		assert_eq!(deleted, 1);
+		let (used, unused) = (format!("/api/media/{used}"), format!("/api/media/{unused}"));
+		let get = |uri| app.call("GET", uri, None);
+		assert_eq!(get(&used).await.status, 200);
+		assert_eq!(get(&unused).await.status, 404);
+
+		let reply = app
+			.call("DELETE", &format!("/api/flashcard/{card}"), None)
+			.await;
+		assert!(reply.status.is_success());
+		let deleted = collect_garbage(&app.db, &storage, Duration::ZERO)
+			.await
+			.unwrap();
+		assert_eq!(deleted, 1);
+		assert_eq!(get(&used).await.status, 404);
+		assert!(storage.get(&hash(FLAC)).await.unwrap().is_none());
+	}
+
+	#[tokio::test]
+	async fn media_can_be_kept_in_s3() {
+		let (config, objects) = bucket().await;
+		let app = TestApp::with_config(|app| app.s3 = Some(config.clone())).await;
+		let stored = |data: &[u8]| objects.lock().unwrap().get(&hash(data)).cloned();
+
+		let reply = app.upload("/api/media", FLAC).await;
+		assert_eq!(reply.status, 201, "{:?}", reply.body);
+		let recording = uid(&reply.json());
+		assert_eq!(stored(FLAC).unwrap(), FLAC);
+
+		// Downloads go to the bucket, as the type the media was uploaded as.
+		let reply = app
+			.call("GET", &format!("/api/media/{recording}"), None)
+			.await;
+		assert_eq!(reply.status, 307);
+		let url = reply.header("location").unwrap();
+		let object = format!(
+			"{}/bucket/{}?",
+			config.endpoint.as_ref().unwrap(),
+			hash(FLAC)
+		);
+		assert!(url.starts_with(&object), "{url}");
+		assert!(url.contains("X-Amz-Signature="), "{url}");
+		assert!(url.contains("response-content-type=audio%2Fflac"), "{url}");
+
+		// Except for SVGs, which need headers the bucket can't be made to send.
+		let svg = &b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"[..];
+		let drawing = uid(&app.upload("/api/media", svg).await.json());
+		let reply = app
+			.call("GET", &format!("/api/media/{drawing}"), None)
+			.await;
+		assert_eq!(reply.status, 200);
+		assert_eq!(reply.header("content-security-policy"), Some("sandbox"));
+		assert_eq!(reply.body, svg);
+
+		let deleted = collect_garbage(&app.db, &S3::new(&config).unwrap(), Duration::ZERO)
+			.await
+			.unwrap();
+		assert_eq!(deleted, 2);
+		assert!(stored(FLAC).is_none());
+		assert!(stored(svg).is_none());
+	}
+
+	#[tokio::test]
+	async fn uploads_racing_the_same_file_get_the_same_media() {
+		let app = TestApp::new().await;
+		let data = b"fLaC racing";
+
+		// Another upload of the file is inserting it, unseen until it commits.
+		let other = app.db.begin().await.unwrap();
+		let first = Uuid::new_v4();
+		Media::insert(media::ActiveModel {
+			uid: Set(first),
+			owner: Set(USER),
+			hash: Set(hash(data)),
+			mime: Set("audio/flac".to_string()),
+			size: Set(11),
+			width: Set(None),
+			height: Set(None),
+			created: Set(Utc::now()),
+		})
+		.exec(&other)
+		.await
+		.unwrap();
+
+		let (reply, ()) = tokio::join!(app.upload("/api/media", &data[..]), async {
+			tokio::time::sleep(Duration::from_millis(200)).await;
+			other.commit().await.unwrap();
+		});
+		assert_eq!(reply.status, 200, "{:?}", reply.body);
+		assert_eq!(uid(&reply.json()), first);
+	}
+}
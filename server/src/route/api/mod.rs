@@ -4,13 +4,27 @@ use axum::Router;
 mod auth;
 mod deck;
 mod flash_card;
+mod fork;
+mod ipa;
+mod media;
 mod oidc;
+mod page;
+mod review;
+mod search;
+mod study;
 
 pub fn router() -> Router<AppState> {
 	Router::new()
 		// .route("/openapi.json", get(openapi))
-		.nest("/deck", deck::router())
+		.nest(
+			"/deck",
+			deck::router().merge(fork::router()).merge(search::router()),
+		)
 		.nest("/flashcard", flash_card::router())
+		.nest("/ipa", ipa::router())
+		.nest("/media", media::router())
+		.nest("/review", review::router())
+		.nest("/study", study::router())
 		.nest("/auth", auth::router())
 		.nest("/oidc", oidc::router())
 }
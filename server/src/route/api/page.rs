@@ -0,0 +1,198 @@
+//! Cursor pagination for listings.
+//!
+//! A page is requested with `limit` and `after`, the cursor of the previous
+//! page, which is handed out in the `Next-Cursor` header while there may be
+//! more rows. Rows are ordered by the sort key and then by id, and a cursor
+//! holds both for the last row of its page, so pages stay consistent while
+//! rows are added or removed in between, that row included.
+
+use std::fmt::{self, Display, Formatter};
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+	ColumnTrait, Condition, ConnectionTrait, EntityTrait, IdenStatic, ModelTrait, QueryFilter,
+	QueryOrder, QuerySelect, Select, Value,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::internal_error;
+
+/// Rows of a page when no `limit` is given.
+pub const DEFAULT_LIMIT: u64 = 100;
+pub const MAX_LIMIT: u64 = 1000;
+
+/// Header with the cursor of the next page.
+pub const NEXT_CURSOR: HeaderName = HeaderName::from_static("next-cursor");
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+	#[default]
+	Asc,
+	Desc,
+}
+
+impl From<Order> for sea_orm::Order {
+	fn from(order: Order) -> Self {
+		match order {
+			Order::Asc => Self::Asc,
+			Order::Desc => Self::Desc,
+		}
+	}
+}
+
+/// Value of a sort key, of the types listings are sorted by.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum Key {
+	Text(String),
+	Time(DateTime<Utc>),
+}
+
+impl Key {
+	fn of(value: Value) -> Option<Self> {
+		match value {
+			Value::String(Some(text)) => Some(Self::Text(*text)),
+			Value::ChronoDateTimeUtc(Some(time)) => Some(Self::Time(*time)),
+			_ => None,
+		}
+	}
+}
+
+impl From<Key> for Value {
+	fn from(key: Key) -> Self {
+		match key {
+			Key::Text(text) => text.into(),
+			Key::Time(time) => time.into(),
+		}
+	}
+}
+
+/// Where a page ends: the sort column, sort key and id of its last row.
+///
+/// Clients get it encoded and pass it back as is.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cursor {
+	sort: String,
+	key: Key,
+	uid: Uuid,
+}
+
+impl Cursor {
+	/// Cursor after `row`, sorted by `key`.
+	pub fn of<E: EntityTrait>(
+		row: &E::Model,
+		key: E::Column,
+		uid: E::Column,
+	) -> Result<Self, (StatusCode, String)> {
+		let cursor = match (Key::of(row.get(key)), row.get(uid)) {
+			(Some(value), Value::Uuid(Some(id))) => Some(Self {
+				sort: key.as_str().to_string(),
+				key: value,
+				uid: *id,
+			}),
+			_ => None,
+		};
+		cursor.ok_or_else(|| {
+			(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				format!("Can't page by {}", key.as_str()),
+			)
+		})
+	}
+}
+
+impl Display for Cursor {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		let json =
+			serde_json::to_vec(&(&self.sort, &self.key, self.uid)).map_err(|_| fmt::Error)?;
+		f.write_str(&URL_SAFE_NO_PAD.encode(json))
+	}
+}
+
+impl TryFrom<String> for Cursor {
+	type Error = &'static str;
+
+	fn try_from(text: String) -> Result<Self, Self::Error> {
+		let json = URL_SAFE_NO_PAD.decode(text).map_err(|_| "invalid cursor")?;
+		let (sort, key, uid) = serde_json::from_slice(&json).map_err(|_| "invalid cursor")?;
+		Ok(Self { sort, key, uid })
+	}
+}
+
+/// The `Next-Cursor` header, if there is a next page.
+pub fn headers(next: Option<&Cursor>) -> HeaderMap {
+	let mut headers = HeaderMap::new();
+	if let Some(value) = next.and_then(|next| HeaderValue::try_from(next.to_string()).ok()) {
+		headers.insert(NEXT_CURSOR, value);
+	}
+	headers
+}
+
+/// Rows of a page, and the cursor of the next one if there may be more.
+pub struct Page<M> {
+	pub rows: Vec<M>,
+	pub next: Option<Cursor>,
+}
+
+impl<M> Page<M> {
+	pub fn headers(&self) -> HeaderMap {
+		headers(self.next.as_ref())
+	}
+}
+
+/// Fetches up to `limit` rows of `select`, ordered by `key` and `uid`,
+/// starting after `after`.
+///
+/// A cursor of another sort column is rejected.
+pub async fn fetch<E, C>(
+	db: &C,
+	select: Select<E>,
+	key: E::Column,
+	uid: E::Column,
+	order: Order,
+	after: Option<Cursor>,
+	limit: Option<u64>,
+) -> Result<Page<E::Model>, (StatusCode, String)>
+where
+	E: EntityTrait,
+	C: ConnectionTrait,
+{
+	let mut select = select;
+	if let Some(after) = after {
+		if after.sort != key.as_str() {
+			return Err((
+				StatusCode::BAD_REQUEST,
+				"Cursor is for another sort".to_string(),
+			));
+		}
+
+		let value = Value::from(after.key);
+		select = select.filter(match order {
+			Order::Asc => Condition::any()
+				.add(key.gt(value.clone()))
+				.add(key.eq(value).and(uid.gt(after.uid))),
+			Order::Desc => Condition::any()
+				.add(key.lt(value.clone()))
+				.add(key.eq(value).and(uid.lt(after.uid))),
+		});
+	}
+
+	let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+	let rows = select
+		.order_by(key, order.into())
+		.order_by(uid, order.into())
+		.limit(limit)
+		.all(db)
+		.await
+		.map_err(internal_error)?;
+
+	let next = match rows.last() {
+		Some(last) if rows.len() as u64 == limit => Some(Cursor::of::<E>(last, key, uid)?),
+		_ => None,
+	};
+	Ok(Page { rows, next })
+}
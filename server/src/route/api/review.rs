@@ -0,0 +1,421 @@
+use axum::{
+	extract::{Path, Query, State},
+	http::StatusCode,
+	middleware,
+	response::IntoResponse,
+	routing::{get, post, put},
+	Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use rustc_hash::FxHashMap;
+use sea_orm::{
+	sea_query::OnConflict, ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait,
+	DatabaseConnection, DbErr, EntityTrait, IntoActiveModel, ModelTrait, QueryFilter, QueryOrder,
+	TransactionTrait, TryIntoModel,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+	app::AppState,
+	internal_error, quiz,
+	render::Render,
+	scheduler::{self, fsrs, Memory, Scheduler, Study},
+	session,
+};
+use entity::{
+	custom::fsrs::{FsrsWeights, WEIGHT_COUNT},
+	deck, deck_cards, deck_settings, flash_card,
+	prelude::*,
+	review_log, review_state,
+	sea_orm_active_enums::{Algorithm, CardState, Grade, Share},
+	user, user_settings,
+};
+
+#[derive(Serialize)]
+pub struct DueCard {
+	card: flash_card::Model,
+	unit: String,
+	state: Option<review_state::Model>,
+}
+
+#[derive(Deserialize)]
+pub struct Answer {
+	grade: Grade,
+	/// Part of the card that was reviewed, the whole card by default.
+	#[serde(default)]
+	unit: String,
+}
+
+#[derive(Serialize)]
+pub struct Optimized {
+	weights: FsrsWeights,
+	initial_loss: f64,
+	loss: f64,
+	reviews: usize,
+}
+
+fn default_settings(user: u32) -> user_settings::Model {
+	user_settings::Model {
+		user,
+		algorithm: Algorithm::Sm2,
+		desired_retention: fsrs::DEFAULT_RETENTION,
+		fsrs_weights: None,
+	}
+}
+
+/// Records a review of a unit of `card` and reschedules it.
+pub(super) async fn grade_card<C>(
+	conn: &C,
+	user: u32,
+	card: Uuid,
+	unit: &str,
+	grade: Grade,
+	now: DateTime<Utc>,
+) -> Result<review_state::Model, DbErr>
+where
+	C: ConnectionTrait + TransactionTrait,
+{
+	let txn = conn.begin().await?;
+
+	let current = ReviewState::find()
+		.filter(review_state::Column::User.eq(user))
+		.filter(review_state::Column::Card.eq(card))
+		.filter(review_state::Column::Unit.eq(unit))
+		.one(&txn)
+		.await?;
+
+	let settings = UserSettings::find_by_id(user).one(&txn).await?;
+	let memory = Scheduler::new(settings.as_ref()).schedule(
+		current.as_ref().map(Memory::from).as_ref(),
+		grade,
+		now,
+	);
+
+	ReviewLog::insert(review_log::ActiveModel {
+		user: Set(user),
+		card: Set(card),
+		unit: Set(unit.to_string()),
+		grade: Set(grade),
+		state: Set(current.as_ref().map_or(CardState::New, |c| c.state)),
+		ease: Set(memory.ease),
+		interval: Set(memory.interval),
+		reviewed_at: Set(now),
+		..Default::default()
+	})
+	.exec(&txn)
+	.await?;
+
+	let mut state = match current {
+		Some(model) => model.into_active_model(),
+		None => review_state::ActiveModel {
+			user: Set(user),
+			card: Set(card),
+			unit: Set(unit.to_string()),
+			..Default::default()
+		},
+	};
+	state.state = Set(memory.state);
+	state.ease = Set(memory.ease);
+	state.interval = Set(memory.interval);
+	state.reps = Set(memory.reps);
+	state.lapses = Set(memory.lapses);
+	state.due = Set(memory.due);
+	state.last_review = Set(memory.last_review);
+	state.stability = Set(memory.stability);
+	state.difficulty = Set(memory.difficulty);
+
+	let state = state.save(&txn).await?.try_into_model()?;
+	txn.commit().await?;
+
+	Ok(state)
+}
+
+async fn due(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+	Query(render): Query<Render>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let Some(deck) = Deck::find_by_id(uid)
+		.filter(
+			deck::Column::Share.eq(Share::Public).or(deck::Column::Share
+				.eq(Share::Private)
+				.and(deck::Column::Creator.eq(user.id))),
+		)
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+	else {
+		return Err((StatusCode::NOT_FOUND, "Not found".to_string()));
+	};
+
+	let mut cards = deck
+		.find_related(FlashCard)
+		.filter(
+			flash_card::Column::Share
+				.eq(Share::Public)
+				.or(flash_card::Column::Share
+					.eq(Share::Private)
+					.and(flash_card::Column::Creator.eq(user.id))),
+		)
+		.all(&conn)
+		.await
+		.map_err(internal_error)?;
+
+	let deck_cards = cards.clone();
+	quiz::add_distractors(&mut cards, &deck_cards);
+	for card in &mut cards {
+		quiz::redact(card, user.id);
+		render.apply(card);
+	}
+
+	let mut states: FxHashMap<(Uuid, String), review_state::Model> = ReviewState::find()
+		.filter(review_state::Column::User.eq(user.id))
+		.filter(review_state::Column::Card.is_in(cards.iter().map(|c| c.uid)))
+		.all(&conn)
+		.await
+		.map_err(internal_error)?
+		.into_iter()
+		.map(|s| ((s.card, s.unit.clone()), s))
+		.collect();
+
+	let study = DeckSettings::find_by_id((user.id, deck.uid))
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+		.as_ref()
+		.map(Study::from)
+		.unwrap_or_default();
+
+	let now = Utc::now();
+	let mut due: Vec<DueCard> = cards
+		.into_iter()
+		.flat_map(|card| {
+			study
+				.units(&card.content)
+				.into_iter()
+				.map(|unit| DueCard {
+					state: states.remove(&(card.uid, unit.clone())),
+					card: card.clone(),
+					unit,
+				})
+				.collect::<Vec<_>>()
+		})
+		.filter(|c| c.state.as_ref().is_none_or(|s| s.due <= now))
+		.collect();
+	// Overdue cards first, new cards last.
+	due.sort_by_key(|c| c.state.as_ref().map_or(DateTime::<Utc>::MAX_UTC, |s| s.due));
+
+	Ok(Json(due))
+}
+
+/// Units of a card as `user` studies it in any of its decks, or outside them.
+async fn units(
+	db: &impl ConnectionTrait,
+	user: u32,
+	card: &flash_card::Model,
+) -> Result<Vec<String>, DbErr> {
+	let decks: Vec<Uuid> = DeckCards::find()
+		.filter(deck_cards::Column::Card.eq(card.uid))
+		.all(db)
+		.await?
+		.into_iter()
+		.map(|link| link.deck)
+		.collect();
+	let settings = DeckSettings::find()
+		.filter(deck_settings::Column::User.eq(user))
+		.filter(deck_settings::Column::Deck.is_in(decks))
+		.all(db)
+		.await?;
+
+	let mut units = Study::default().units(&card.content);
+	for settings in &settings {
+		for unit in Study::from(settings).units(&card.content) {
+			if !units.contains(&unit) {
+				units.push(unit);
+			}
+		}
+	}
+	Ok(units)
+}
+
+async fn answer(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+	Json(answer): Json<Answer>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let card = FlashCard::find_by_id(uid)
+		.filter(
+			flash_card::Column::Share
+				.eq(Share::Public)
+				.or(flash_card::Column::Creator.eq(user.id)),
+		)
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+		.ok_or_else(|| (StatusCode::NOT_FOUND, "Not found".to_string()))?;
+
+	if answer.unit.len() > scheduler::MAX_UNIT
+		|| !units(&conn, user.id, &card)
+			.await
+			.map_err(internal_error)?
+			.contains(&answer.unit)
+	{
+		return Err((
+			StatusCode::UNPROCESSABLE_ENTITY,
+			format!("Card has no review unit {:?}", answer.unit),
+		));
+	}
+
+	let state = grade_card(
+		&conn,
+		user.id,
+		card.uid,
+		&answer.unit,
+		answer.grade,
+		Utc::now(),
+	)
+	.await
+	.map_err(internal_error)?;
+
+	Ok(Json(state))
+}
+
+async fn get_settings(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let settings = UserSettings::find_by_id(user.id)
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+		.unwrap_or_else(|| default_settings(user.id));
+
+	Ok(Json(settings))
+}
+
+async fn put_settings(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Json(body): Json<user_settings::Model>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	if !(0.7..=0.99).contains(&body.desired_retention) {
+		return Err((
+			StatusCode::UNPROCESSABLE_ENTITY,
+			"Desired retention must be between 0.7 and 0.99".to_string(),
+		));
+	}
+	if body
+		.fsrs_weights
+		.as_ref()
+		.is_some_and(|w| w.0.len() != WEIGHT_COUNT)
+	{
+		return Err((
+			StatusCode::UNPROCESSABLE_ENTITY,
+			format!("FSRS weights must have {WEIGHT_COUNT} entries"),
+		));
+	}
+
+	UserSettings::insert(user_settings::ActiveModel {
+		user: Set(user.id),
+		algorithm: Set(body.algorithm),
+		desired_retention: Set(body.desired_retention),
+		fsrs_weights: Set(body.fsrs_weights),
+	})
+	.on_conflict(
+		OnConflict::column(user_settings::Column::User)
+			.update_columns([
+				user_settings::Column::Algorithm,
+				user_settings::Column::DesiredRetention,
+				user_settings::Column::FsrsWeights,
+			])
+			.to_owned(),
+	)
+	.exec(&conn)
+	.await
+	.map_err(internal_error)?;
+	Ok(StatusCode::NO_CONTENT)
+}
+
+/// Re-fits the caller's FSRS weights to their review log and saves them.
+async fn optimize(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let settings = UserSettings::find_by_id(user.id)
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+		.unwrap_or_else(|| default_settings(user.id));
+
+	let logs = ReviewLog::find()
+		.filter(review_log::Column::User.eq(user.id))
+		.order_by_asc(review_log::Column::Card)
+		.order_by_asc(review_log::Column::Unit)
+		.order_by_asc(review_log::Column::ReviewedAt)
+		.all(&conn)
+		.await
+		.map_err(internal_error)?;
+
+	let mut histories: Vec<fsrs::History> = Vec::new();
+	let mut last: Option<&review_log::Model> = None;
+	for log in &logs {
+		match last {
+			Some(prev) if prev.card == log.card && prev.unit == log.unit => {
+				#[allow(clippy::cast_precision_loss)]
+				let elapsed = (log.reviewed_at - prev.reviewed_at).num_seconds().max(0) as f64 / 86400.0;
+				if let Some(history) = histories.last_mut() {
+					history.push((elapsed, log.grade));
+				}
+			}
+			_ => histories.push(vec![(0.0, log.grade)]),
+		}
+		last = Some(log);
+	}
+
+	let initial = fsrs::Parameters::from(&settings).weights;
+	let fit = tokio::task::spawn_blocking(move || fsrs::optimize(&histories, &initial))
+		.await
+		.map_err(internal_error)?
+		.ok_or_else(|| {
+			(
+				StatusCode::UNPROCESSABLE_ENTITY,
+				format!(
+					"At least {} reviews are needed to optimize",
+					fsrs::MIN_REVIEWS
+				),
+			)
+		})?;
+
+	let weights = FsrsWeights(fit.weights.to_vec());
+	let mut settings = settings.into_active_model();
+	settings.fsrs_weights = Set(Some(weights.clone()));
+	UserSettings::insert(settings)
+		.on_conflict(
+			OnConflict::column(user_settings::Column::User)
+				.update_column(user_settings::Column::FsrsWeights)
+				.to_owned(),
+		)
+		.exec(&conn)
+		.await
+		.map_err(internal_error)?;
+
+	Ok(Json(Optimized {
+		weights,
+		initial_loss: fit.initial_loss,
+		loss: fit.loss,
+		reviews: fit.reviews,
+	}))
+}
+
+pub fn router() -> Router<AppState> {
+	Router::new()
+		.route("/settings", get(get_settings))
+		.route("/settings", put(put_settings))
+		.route("/optimize", post(optimize))
+		.route("/deck/:id", get(due))
+		.route("/card/:id", post(answer))
+		.route_layer(middleware::from_fn(session::auth))
+}
@@ -0,0 +1,482 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+	extract::{Query, State},
+	http::StatusCode,
+	middleware,
+	response::IntoResponse,
+	routing::get,
+	Json, Router,
+};
+use sea_orm::{
+	sea_query::{self, Alias, Expr, Func, LikeExpr, SimpleExpr, WindowStatement},
+	ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+	FromQueryResult, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{app::AppState, card_query, internal_error, session};
+use entity::{
+	custom::{
+		flash_card::{FlashCardContent, FlashCardSection},
+		lang::Language,
+	},
+	deck, deck_cards, flash_card,
+	prelude::*,
+	sea_orm_active_enums::{Kind, Share},
+};
+
+const DEFAULT_PER_PAGE: u64 = 20;
+const MAX_PER_PAGE: u64 = 100;
+/// Decks scored per search. They are the first in a rough ranking done by the
+/// database, on terms in the name and then the number of matching cards.
+const MAX_CANDIDATES: usize = 500;
+/// Cards of a deck that may match that are loaded to score it.
+const CARDS_PER_DECK: u64 = 20;
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+	#[serde(default)]
+	q: String,
+	#[serde(default)]
+	kind: Option<Kind>,
+	/// Only decks with cards to show in this language, see [`Language::resolve`].
+	#[serde(default)]
+	language: Option<Language>,
+	#[serde(default)]
+	page: Option<u64>,
+	#[serde(default)]
+	per_page: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct SearchResult {
+	deck: deck::Model,
+	score: f64,
+	/// Public cards of the deck that contain a search term, among the
+	/// [`CARDS_PER_DECK`] looked at per term.
+	matching_cards: usize,
+}
+
+#[derive(Serialize)]
+pub struct SearchPage {
+	results: Vec<SearchResult>,
+	/// Decks found, which can all be paged through.
+	total: usize,
+	/// Whether more decks may match than were looked at, which were the
+	/// [`MAX_CANDIDATES`] most promising ones.
+	truncated: bool,
+	page: u64,
+	per_page: u64,
+}
+
+#[derive(FromQueryResult)]
+struct Match {
+	deck: Uuid,
+	card: Uuid,
+	content: FlashCardContent,
+}
+
+/// `%term%`, with LIKE wildcards in the term escaped.
+pub(super) fn like(term: &str) -> LikeExpr {
+	let escaped = term
+		.replace('\\', "\\\\")
+		.replace('%', "\\%")
+		.replace('_', "\\_");
+	LikeExpr::new(format!("%{escaped}%")).escape('\\')
+}
+
+/// Case-insensitive LIKE on a card's content, as JSON text.
+pub(super) fn content_like(pattern: LikeExpr) -> SimpleExpr {
+	Expr::expr(Func::lower(Expr::col((
+		FlashCard,
+		flash_card::Column::Content,
+	))))
+	.like(pattern)
+}
+
+/// Matches content that may have a `Lang` section to show for `lang`: one
+/// in a tag of the same primary language, among others.
+pub(super) fn language_like(lang: &Language) -> LikeExpr {
+	like(&format!("\"{}", lang.primary()))
+}
+
+/// Public cards in public decks whose content matches `pattern`.
+fn cards_matching(pattern: LikeExpr) -> sea_query::SelectStatement {
+	sea_query::Query::select()
+		.column((DeckCards, deck_cards::Column::Deck))
+		.from(DeckCards)
+		.inner_join(
+			FlashCard,
+			Expr::col((FlashCard, flash_card::Column::Uid))
+				.equals((DeckCards, deck_cards::Column::Card)),
+		)
+		.and_where(flash_card::Column::Share.eq(Share::Public))
+		.and_where(content_like(pattern))
+		.to_owned()
+}
+
+/// Number of public cards in the deck of the outer query whose content
+/// matches `pattern`.
+fn count_matching(pattern: LikeExpr) -> SimpleExpr {
+	SimpleExpr::SubQuery(
+		None,
+		Box::new(
+			sea_query::Query::select()
+				.expr(Expr::col((FlashCard, flash_card::Column::Uid)).count())
+				.from(DeckCards)
+				.inner_join(
+					FlashCard,
+					Expr::col((FlashCard, flash_card::Column::Uid))
+						.equals((DeckCards, deck_cards::Column::Card)),
+				)
+				.and_where(
+					Expr::col((DeckCards, deck_cards::Column::Deck))
+						.equals((Deck, deck::Column::Uid)),
+				)
+				.and_where(flash_card::Column::Share.eq(Share::Public))
+				.and_where(content_like(pattern))
+				.to_owned()
+				.into_sub_query_statement(),
+		),
+	)
+}
+
+/// Up to [`CARDS_PER_DECK`] public cards of each of `decks` whose content
+/// matches `pattern`.
+async fn sample_matching(
+	conn: &DatabaseConnection,
+	decks: &[deck::Model],
+	pattern: LikeExpr,
+) -> Result<Vec<Match>, DbErr> {
+	let nth = Alias::new("nth");
+	let matching = sea_query::Query::select()
+		.column((DeckCards, deck_cards::Column::Deck))
+		.column((DeckCards, deck_cards::Column::Card))
+		.column((FlashCard, flash_card::Column::Content))
+		.expr_window_as(
+			Func::cust(Alias::new("ROW_NUMBER")),
+			WindowStatement::partition_by((DeckCards, deck_cards::Column::Deck)),
+			nth.clone(),
+		)
+		.from(DeckCards)
+		.inner_join(
+			FlashCard,
+			Expr::col((FlashCard, flash_card::Column::Uid))
+				.equals((DeckCards, deck_cards::Column::Card)),
+		)
+		.and_where(
+			Expr::col((DeckCards, deck_cards::Column::Deck)).is_in(decks.iter().map(|d| d.uid)),
+		)
+		.and_where(flash_card::Column::Share.eq(Share::Public))
+		.and_where(content_like(pattern))
+		.to_owned();
+	let sample = sea_query::Query::select()
+		.column(deck_cards::Column::Deck)
+		.column(deck_cards::Column::Card)
+		.column(flash_card::Column::Content)
+		.from_subquery(matching, Alias::new("matching"))
+		.and_where(Expr::col(nth).lte(CARDS_PER_DECK))
+		.to_owned();
+
+	Match::find_by_statement(conn.get_database_backend().build(&sample))
+		.all(conn)
+		.await
+}
+
+fn name_like(term: &str) -> SimpleExpr {
+	Expr::expr(Func::lower(Expr::col((Deck, deck::Column::Name)))).like(like(term))
+}
+
+fn has_language(content: &FlashCardContent, lang: &Language) -> bool {
+	content.0.iter().any(|section| match section {
+		FlashCardSection::Lang(items) => lang.resolve(items).is_some(),
+		_ => false,
+	})
+}
+
+/// Searches public decks by name and by the text of their public cards.
+///
+/// Every term has to appear in the deck name or in one of its cards. Terms in
+/// the name weigh more than terms in cards, and whole words more than parts of
+/// words. Without terms, public decks are listed by name.
+async fn search(
+	State(conn): State<DatabaseConnection>,
+	Query(query): Query<SearchQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let per_page = query
+		.per_page
+		.unwrap_or(DEFAULT_PER_PAGE)
+		.clamp(1, MAX_PER_PAGE);
+	let page = query.page.unwrap_or(1).max(1);
+
+	let mut terms: Vec<String> = Vec::new();
+	for term in query.q.to_lowercase().split_whitespace() {
+		if !terms.iter().any(|t| t == term) {
+			terms.push(term.to_string());
+		}
+	}
+
+	// Narrow down with LIKE, which also sees JSON keys and escapes; the exact
+	// matching is done on the loaded cards below.
+	let mut select = Deck::find().filter(deck::Column::Share.eq(Share::Public));
+	if let Some(kind) = query.kind {
+		select = select.filter(deck::Column::Kind.eq(kind));
+	}
+	for term in &terms {
+		select = select.filter(
+			Condition::any()
+				.add(name_like(term))
+				.add(deck::Column::Uid.in_subquery(cards_matching(like(term)))),
+		);
+	}
+	let language = query.language.as_ref();
+	if let Some(lang) = language {
+		select = select.filter(deck::Column::Uid.in_subquery(cards_matching(language_like(lang))));
+	}
+	let mut in_name = SimpleExpr::from(0);
+	let mut in_cards = SimpleExpr::from(0);
+	for term in &terms {
+		in_name = in_name.add(Expr::case(name_like(term), 1).finally(0));
+		in_cards = in_cards.add(count_matching(like(term)));
+	}
+	if !terms.is_empty() {
+		select = select.order_by_desc(in_name).order_by_desc(in_cards);
+	}
+	let mut decks = select
+		.order_by_asc(deck::Column::Name)
+		.limit(MAX_CANDIDATES as u64 + 1)
+		.all(&conn)
+		.await
+		.map_err(internal_error)?;
+	let truncated = decks.len() > MAX_CANDIDATES;
+	decks.truncate(MAX_CANDIDATES);
+
+	// Sampled per term, so cards matching a common term can't crowd out the
+	// one matching a rarer term.
+	let mut texts: HashMap<Uuid, HashMap<Uuid, String>> = HashMap::new();
+	let mut languages: HashSet<Uuid> = HashSet::new();
+	if !decks.is_empty() {
+		for term in &terms {
+			let cards = sample_matching(&conn, &decks, like(term))
+				.await
+				.map_err(internal_error)?;
+			for card in cards {
+				texts
+					.entry(card.deck)
+					.or_default()
+					.entry(card.card)
+					.or_insert_with(|| card_query::text(&card.content));
+			}
+		}
+		if let Some(lang) = language {
+			let cards = sample_matching(&conn, &decks, language_like(lang))
+				.await
+				.map_err(internal_error)?;
+			for card in cards {
+				if has_language(&card.content, lang) {
+					languages.insert(card.deck);
+				}
+			}
+		}
+	}
+
+	let mut results: Vec<SearchResult> = decks
+		.into_iter()
+		.filter(|deck| language.is_none() || languages.contains(&deck.uid))
+		.filter_map(|deck| {
+			let name = deck.name.to_lowercase();
+			let no_cards = HashMap::new();
+			let cards = texts.get(&deck.uid).unwrap_or(&no_cards);
+
+			let mut score = 0.0;
+			let mut matching = HashSet::new();
+			for term in &terms {
+				let in_name = name.contains(term.as_str());
+				let hits: Vec<Uuid> = cards
+					.iter()
+					.filter(|(_, text)| text.contains(term.as_str()))
+					.map(|(card, _)| *card)
+					.collect();
+				if !in_name && hits.is_empty() {
+					return None;
+				}
+
+				if in_name {
+					score += 10.0;
+					if name.split_whitespace().any(|word| word == term) {
+						score += 5.0;
+					}
+				}
+				#[allow(clippy::cast_precision_loss)]
+				let hit_count = hits.len() as f64;
+				score += hit_count.ln_1p() * 2.0;
+				if cards.values().any(|text| {
+					text.split(|c: char| !c.is_alphanumeric())
+						.any(|w| w == term)
+				}) {
+					score += 1.0;
+				}
+				matching.extend(hits);
+			}
+
+			Some(SearchResult {
+				deck,
+				score,
+				matching_cards: matching.len(),
+			})
+		})
+		.collect();
+
+	// Stable, so equal scores stay in the database's order.
+	results.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+	let total = results.len();
+	let start = usize::try_from((page - 1).saturating_mul(per_page)).unwrap_or(usize::MAX);
+	let results = results
+		.into_iter()
+		.skip(start)
+		.take(usize::try_from(per_page).unwrap_or(usize::MAX))
+		.collect();
+
+	Ok(Json(SearchPage {
+		results,
+		total,
+		truncated,
+		page,
+		per_page,
+	}))
+}
+
+pub fn router() -> Router<AppState> {
+	Router::new()
+		.route("/search", get(search))
+		.route_layer(middleware::from_fn(session::auth))
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::Utc;
+	use entity::{
+		deck,
+		prelude::*,
+		sea_orm_active_enums::{Kind, Share},
+	};
+	use sea_orm::{EntityTrait, Set};
+	use serde_json::Value;
+	use uuid::Uuid;
+
+	use super::MAX_CANDIDATES;
+	use crate::testing::{titled, uid, TestApp, OTHER};
+
+	fn names(page: &Value) -> Vec<&str> {
+		page["results"]
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|r| r["deck"]["name"].as_str().unwrap())
+			.collect()
+	}
+
+	#[tokio::test]
+	async fn ranks_names_above_cards() {
+		let app = TestApp::new().await;
+		let in_cards = app
+			.deck(OTHER, "Animals", "Public", &[titled("kot"), titled("pies")])
+			.await;
+		let in_name = app.deck(OTHER, "Kot i pies", "Public", &[]).await;
+		app.deck(OTHER, "Kot private", "Private", &[]).await;
+		app.deck(OTHER, "Colours", "Public", &[titled("czerwony")])
+			.await;
+
+		let page = app.call("GET", "/api/deck/search?q=kot", None).await.json();
+		let found: Vec<Uuid> = page["results"]
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|r| uid(&r["deck"]))
+			.collect();
+		assert_eq!(found, [in_name, in_cards]);
+		assert_eq!(page["results"][1]["matching_cards"], 1);
+		assert_eq!(page["total"], 2);
+		assert_eq!(page["truncated"], false);
+
+		// Every term has to match somewhere in the deck.
+		let page = app
+			.call("GET", "/api/deck/search?q=kot+pies", None)
+			.await
+			.json();
+		assert_eq!(page["total"], 2);
+		assert_eq!(page["results"][1]["matching_cards"], 2);
+		let page = app
+			.call("GET", "/api/deck/search?q=kot+czerwony", None)
+			.await
+			.json();
+		assert_eq!(page["total"], 0);
+	}
+
+	#[tokio::test]
+	async fn pages_through_results() {
+		let app = TestApp::new().await;
+		for name in ["Deck a", "Deck b", "Deck c", "Deck d", "Deck e"] {
+			app.deck(OTHER, name, "Public", &[]).await;
+		}
+
+		let mut seen = Vec::new();
+		for page in 1..=3 {
+			let reply = app
+				.call(
+					"GET",
+					&format!("/api/deck/search?q=deck&per_page=2&page={page}"),
+					None,
+				)
+				.await
+				.json();
+			assert_eq!(reply["total"], 5);
+			assert_eq!(reply["per_page"], 2);
+			seen.extend(names(&reply).into_iter().map(str::to_string));
+		}
+		assert_eq!(seen, ["Deck a", "Deck b", "Deck c", "Deck d", "Deck e"]);
+
+		let past = app
+			.call("GET", "/api/deck/search?q=deck&per_page=2&page=4", None)
+			.await
+			.json();
+		assert!(names(&past).is_empty());
+	}
+
+	#[tokio::test]
+	async fn says_when_more_decks_match_than_are_scored() {
+		let app = TestApp::new().await;
+		let decks = (0..=MAX_CANDIDATES).map(|i| deck::ActiveModel {
+			uid: Set(Uuid::new_v4()),
+			name: Set(format!("Verbs {i:04}")),
+			creator: Set(OTHER),
+			kind: Set(Kind::Language),
+			share: Set(Share::Public),
+			created: Set(Utc::now()),
+			updated: Set(Utc::now()),
+		});
+		Deck::insert_many(decks).exec(&app.db).await.unwrap();
+		// Matches by its cards, which ranks it below every name match.
+		app.deck(OTHER, "Grammar", "Public", &[titled("verbs")])
+			.await;
+
+		let page = app
+			.call("GET", "/api/deck/search?q=verbs&per_page=100&page=5", None)
+			.await
+			.json();
+		assert_eq!(page["truncated"], true);
+		assert_eq!(page["total"], MAX_CANDIDATES);
+		assert_eq!(names(&page).last(), Some(&"Verbs 0499"));
+		assert!(!names(&page).contains(&"Grammar"));
+
+		let page = app
+			.call("GET", "/api/deck/search?q=grammar", None)
+			.await
+			.json();
+		assert_eq!(page["truncated"], false);
+		assert_eq!(page["total"], 1);
+	}
+}
@@ -0,0 +1,965 @@
+use axum::{
+	extract::{Path, Query, State},
+	http::{header::LOCATION, StatusCode},
+	middleware,
+	response::IntoResponse,
+	routing::{delete, get, post, put},
+	Extension, Json, Router,
+};
+use chrono::{DateTime, Duration, Utc};
+use rustc_hash::{FxHashMap, FxHashSet};
+use sea_orm::{
+	sea_query::{self, OnConflict},
+	ActiveValue::Set,
+	ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+	IntoActiveModel, ModelTrait, QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::review::grade_card;
+use crate::{
+	app::AppState,
+	internal_error, quiz,
+	render::Render,
+	scheduler::{self, Study},
+	session,
+};
+use entity::{
+	custom::{
+		flash_card::FlashCardSection,
+		study::{Directions, QueueEntry, StudyQueue},
+	},
+	deck, deck_cards, deck_settings, flash_card, followed_decks,
+	prelude::*,
+	review_log, review_state,
+	sea_orm_active_enums::{CardState, Grade, Share},
+	study_session, user,
+};
+
+pub const DEFAULT_NEW_PER_DAY: u32 = 20;
+pub const DEFAULT_REVIEWS_PER_DAY: u32 = 200;
+
+/// Queue entries whose cards are looked up at a time for the next card.
+const NEXT_BATCH: usize = 50;
+
+/// Cards of a deck the wrong options of a question are picked from.
+const DISTRACTOR_SAMPLE: u64 = 100;
+
+/// Learning cards due within this window are pulled into the session early.
+fn learn_ahead() -> Duration {
+	Duration::minutes(20)
+}
+
+#[derive(Deserialize)]
+pub struct NewSession {
+	/// Study a single deck, or every owned and followed deck when absent.
+	#[serde(default)]
+	deck: Option<Uuid>,
+}
+
+#[derive(Serialize)]
+pub struct Summary {
+	uid: Uuid,
+	deck: Option<Uuid>,
+	new: usize,
+	learning: usize,
+	review: usize,
+	done: u32,
+}
+
+impl From<&study_session::Model> for Summary {
+	fn from(session: &study_session::Model) -> Self {
+		let count =
+			|f: fn(CardState) -> bool| session.queue.0.iter().filter(|e| f(e.state)).count();
+
+		Self {
+			uid: session.uid,
+			deck: session.deck,
+			new: count(|s| s == CardState::New),
+			learning: count(|s| matches!(s, CardState::Learning | CardState::Relearning)),
+			review: count(|s| s == CardState::Review),
+			done: session.done,
+		}
+	}
+}
+
+#[derive(Serialize)]
+pub struct Next {
+	entry: QueueEntry,
+	card: flash_card::Model,
+}
+
+#[derive(Deserialize)]
+pub struct SessionAnswer {
+	card: Uuid,
+	#[serde(default)]
+	unit: String,
+	grade: Grade,
+}
+
+#[derive(Serialize)]
+pub struct Answered {
+	state: review_state::Model,
+	session: Summary,
+}
+
+fn default_deck_settings(user: u32, deck: Uuid) -> deck_settings::Model {
+	deck_settings::Model {
+		user,
+		deck,
+		new_per_day: DEFAULT_NEW_PER_DAY,
+		reviews_per_day: DEFAULT_REVIEWS_PER_DAY,
+		directions: None,
+		reverse: false,
+	}
+}
+
+/// Builds today's queue for `decks`: learning cards first, then reviews with new
+/// cards spread among them, within each deck's daily limits.
+async fn build_queue<C: ConnectionTrait>(
+	conn: &C,
+	user: u32,
+	decks: &[deck::Model],
+	now: DateTime<Utc>,
+) -> Result<Vec<QueueEntry>, DbErr> {
+	let start_of_day = now
+		.date_naive()
+		.and_hms_opt(0, 0, 0)
+		.unwrap_or_default()
+		.and_utc();
+
+	let mut seen = FxHashSet::default();
+	let mut learning = Vec::new();
+	let mut reviews = Vec::new();
+	let mut new = Vec::new();
+
+	for deck in decks {
+		let limits = DeckSettings::find_by_id((user, deck.uid))
+			.one(conn)
+			.await?
+			.unwrap_or_else(|| default_deck_settings(user, deck.uid));
+
+		let cards: Vec<flash_card::Model> = deck
+			.find_related(FlashCard)
+			.filter(
+				flash_card::Column::Share
+					.eq(Share::Public)
+					.or(flash_card::Column::Creator.eq(user)),
+			)
+			.all(conn)
+			.await?
+			.into_iter()
+			.filter(|c| !seen.contains(&c.uid))
+			.collect();
+		let uids: Vec<Uuid> = cards.iter().map(|c| c.uid).collect();
+
+		let mut states: FxHashMap<(Uuid, String), review_state::Model> = ReviewState::find()
+			.filter(review_state::Column::User.eq(user))
+			.filter(review_state::Column::Card.is_in(uids.clone()))
+			.all(conn)
+			.await?
+			.into_iter()
+			.map(|s| ((s.card, s.unit.clone()), s))
+			.collect();
+
+		let today = ReviewLog::find()
+			.filter(review_log::Column::User.eq(user))
+			.filter(review_log::Column::Card.is_in(uids))
+			.filter(review_log::Column::ReviewedAt.gte(start_of_day))
+			.all(conn)
+			.await?;
+		let studied = |state: CardState| {
+			u32::try_from(today.iter().filter(|l| l.state == state).count()).unwrap_or(u32::MAX)
+		};
+		let mut new_left = limits.new_per_day.saturating_sub(studied(CardState::New));
+		let reviews_left = limits
+			.reviews_per_day
+			.saturating_sub(studied(CardState::Review));
+
+		let study = Study::from(&limits);
+		let mut deck_reviews = Vec::new();
+		for card in cards {
+			for unit in study.units(&card.content) {
+				let state = states.remove(&(card.uid, unit.clone()));
+				let entry = |state| QueueEntry {
+					card: card.uid,
+					unit,
+					deck: deck.uid,
+					state,
+				};
+
+				match state {
+					None if new_left > 0 => {
+						new_left -= 1;
+						new.push(entry(CardState::New));
+					}
+					None => continue,
+					Some(s) if s.state == CardState::Review => {
+						if s.due <= now {
+							deck_reviews.push((s.due, entry(s.state)));
+						}
+					}
+					Some(s) => {
+						if s.due <= now + learn_ahead() {
+							learning.push((s.due, entry(s.state)));
+						}
+					}
+				}
+			}
+			seen.insert(card.uid);
+		}
+
+		deck_reviews.sort_by_key(|(due, _)| *due);
+		reviews.extend(deck_reviews.into_iter().take(reviews_left as usize));
+	}
+
+	learning.sort_by_key(|(due, _)| *due);
+	reviews.sort_by_key(|(due, _)| *due);
+
+	let mut queue: Vec<QueueEntry> = learning.into_iter().map(|(_, e)| e).collect();
+	let every = (reviews.len() / new.len().max(1)).max(1);
+	let mut new = new.into_iter();
+	for (i, (_, entry)) in reviews.into_iter().enumerate() {
+		queue.push(entry);
+		if (i + 1) % every == 0 {
+			queue.extend(new.next());
+		}
+	}
+	queue.extend(new);
+
+	Ok(queue)
+}
+
+async fn find_session(
+	conn: &DatabaseConnection,
+	user: u32,
+	uid: Uuid,
+) -> Result<study_session::Model, (StatusCode, String)> {
+	StudySession::find_by_id(uid)
+		.filter(study_session::Column::User.eq(user))
+		.one(conn)
+		.await
+		.map_err(internal_error)?
+		.ok_or_else(|| (StatusCode::NOT_FOUND, "Not found".to_string()))
+}
+
+async fn create(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Json(body): Json<NewSession>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let visible = deck::Column::Share
+		.eq(Share::Public)
+		.or(deck::Column::Creator.eq(user.id));
+
+	let decks = match body.deck {
+		Some(uid) => vec![Deck::find_by_id(uid)
+			.filter(visible)
+			.one(&conn)
+			.await
+			.map_err(internal_error)?
+			.ok_or_else(|| (StatusCode::NOT_FOUND, "Not found".to_string()))?],
+		None => Deck::find()
+			.filter(visible)
+			.filter(
+				Condition::any().add(deck::Column::Creator.eq(user.id)).add(
+					deck::Column::Uid.in_subquery(
+						sea_query::Query::select()
+							.column(followed_decks::Column::Deck)
+							.from(FollowedDecks)
+							.and_where(followed_decks::Column::User.eq(user.id))
+							.to_owned(),
+					),
+				),
+			)
+			.order_by_asc(deck::Column::Name)
+			.all(&conn)
+			.await
+			.map_err(internal_error)?,
+	};
+
+	let now = Utc::now();
+	let queue = build_queue(&conn, user.id, &decks, now)
+		.await
+		.map_err(internal_error)?;
+
+	let session = study_session::Model {
+		uid: Uuid::new_v4(),
+		user: user.id,
+		deck: body.deck,
+		queue: StudyQueue(queue),
+		done: 0,
+		created: now,
+		updated: now,
+	};
+
+	StudySession::insert(session.clone().into_active_model())
+		.exec(&conn)
+		.await
+		.map_err(internal_error)?;
+
+	Ok((
+		StatusCode::CREATED,
+		[(LOCATION, session.uid.to_string())],
+		Json(Summary::from(&session)),
+	))
+}
+
+async fn all(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let sessions = StudySession::find()
+		.filter(study_session::Column::User.eq(user.id))
+		.order_by_desc(study_session::Column::Updated)
+		.all(&conn)
+		.await
+		.map_err(internal_error)?;
+
+	Ok(Json(sessions.iter().map(Summary::from).collect::<Vec<_>>()))
+}
+
+async fn get_one(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let session = find_session(&conn, user.id, uid).await?;
+	Ok(Json(Summary::from(&session)))
+}
+
+fn visible_card(user: u32) -> Condition {
+	Condition::any()
+		.add(flash_card::Column::Share.eq(Share::Public))
+		.add(flash_card::Column::Creator.eq(user))
+}
+
+/// Other cards of `deck` for the questions of `card` to take wrong options
+/// from, if they want any: a sample of [`DISTRACTOR_SAMPLE`] of them, those
+/// after a random id and then from the start.
+async fn distractor_pool(
+	conn: &DatabaseConnection,
+	user: u32,
+	deck: Uuid,
+	card: &flash_card::Model,
+) -> Result<Vec<flash_card::Model>, DbErr> {
+	let wanted = card.content.0.iter().any(|section| {
+		matches!(section, FlashCardSection::MultipleChoice(question) if question.distractors > 0)
+	});
+	if !wanted {
+		return Ok(Vec::new());
+	}
+
+	let others = FlashCard::find()
+		.filter(
+			flash_card::Column::Uid.in_subquery(
+				sea_query::Query::select()
+					.column(deck_cards::Column::Card)
+					.from(DeckCards)
+					.and_where(deck_cards::Column::Deck.eq(deck))
+					.to_owned(),
+			),
+		)
+		.filter(visible_card(user))
+		.filter(flash_card::Column::Uid.ne(card.uid))
+		.order_by_asc(flash_card::Column::Uid);
+	let start = Uuid::new_v4();
+	let mut pool = others
+		.clone()
+		.filter(flash_card::Column::Uid.gte(start))
+		.limit(DISTRACTOR_SAMPLE)
+		.all(conn)
+		.await?;
+	let missing = DISTRACTOR_SAMPLE.saturating_sub(pool.len() as u64);
+	if missing > 0 {
+		pool.extend(
+			others
+				.filter(flash_card::Column::Uid.lt(start))
+				.limit(missing)
+				.all(conn)
+				.await?,
+		);
+	}
+	Ok(pool)
+}
+
+async fn next(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+	Query(render): Query<Render>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let session = find_session(&conn, user.id, uid).await?;
+
+	for entries in session.queue.0.chunks(NEXT_BATCH) {
+		// Cards deleted or made private since the session started are skipped.
+		let mut cards: FxHashMap<Uuid, flash_card::Model> = FlashCard::find()
+			.filter(flash_card::Column::Uid.is_in(entries.iter().map(|e| e.card)))
+			.filter(visible_card(user.id))
+			.all(&conn)
+			.await
+			.map_err(internal_error)?
+			.into_iter()
+			.map(|card| (card.uid, card))
+			.collect();
+		let Some((entry, mut card)) = entries
+			.iter()
+			.find_map(|entry| Some((entry, cards.remove(&entry.card)?)))
+		else {
+			continue;
+		};
+
+		let pool = distractor_pool(&conn, user.id, entry.deck, &card)
+			.await
+			.map_err(internal_error)?;
+		quiz::add_distractors(std::slice::from_mut(&mut card), &pool);
+		quiz::redact(&mut card, user.id);
+		render.apply(&mut card);
+
+		return Ok(Json(Next {
+			entry: entry.clone(),
+			card,
+		})
+		.into_response());
+	}
+
+	Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn answer(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+	Json(body): Json<SessionAnswer>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let mut session = find_session(&conn, user.id, uid).await?;
+
+	let Some(pos) = session
+		.queue
+		.0
+		.iter()
+		.position(|e| e.card == body.card && e.unit == body.unit)
+	else {
+		return Err((
+			StatusCode::CONFLICT,
+			"Card is not queued in this session".to_string(),
+		));
+	};
+	let entry = session.queue.0.remove(pos);
+
+	let now = Utc::now();
+	let state = grade_card(&conn, user.id, entry.card, &entry.unit, body.grade, now)
+		.await
+		.map_err(internal_error)?;
+
+	// Failed cards come back before the session ends.
+	if state.state != CardState::Review && state.due <= now + learn_ahead() {
+		session.queue.0.push(QueueEntry {
+			state: state.state,
+			..entry
+		});
+	}
+	session.done += 1;
+	session.updated = now;
+
+	StudySession::update(study_session::ActiveModel {
+		uid: Set(session.uid),
+		queue: Set(session.queue.clone()),
+		done: Set(session.done),
+		updated: Set(session.updated),
+		..Default::default()
+	})
+	.exec(&conn)
+	.await
+	.map_err(internal_error)?;
+
+	Ok(Json(Answered {
+		state,
+		session: Summary::from(&session),
+	}))
+}
+
+async fn delete_session(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+	if StudySession::delete_by_id(uid)
+		.filter(study_session::Column::User.eq(user.id))
+		.exec(&conn)
+		.await
+		.map_err(internal_error)?
+		.rows_affected
+		== 0
+	{
+		Ok(StatusCode::NOT_FOUND)
+	} else {
+		Ok(StatusCode::NO_CONTENT)
+	}
+}
+
+async fn visible_deck(
+	conn: &DatabaseConnection,
+	user: u32,
+	uid: Uuid,
+) -> Result<deck::Model, (StatusCode, String)> {
+	Deck::find_by_id(uid)
+		.filter(
+			deck::Column::Share
+				.eq(Share::Public)
+				.or(deck::Column::Creator.eq(user)),
+		)
+		.one(conn)
+		.await
+		.map_err(internal_error)?
+		.ok_or_else(|| (StatusCode::NOT_FOUND, "Not found".to_string()))
+}
+
+async fn get_deck_settings(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let deck = visible_deck(&conn, user.id, uid).await?;
+
+	let settings = DeckSettings::find_by_id((user.id, deck.uid))
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+		.unwrap_or_else(|| default_deck_settings(user.id, deck.uid));
+
+	Ok(Json(settings))
+}
+
+/// Moves `user`'s review state and history for the cards of `deck` between
+/// the whole card and its first other unit, when `old` reviews one of them and
+/// `new` the other. Turning directions on or off then doesn't start the cards
+/// over. Cards already reviewed in the unit they'd move to are left alone.
+async fn carry_over<C: ConnectionTrait>(
+	db: &C,
+	user: u32,
+	deck: &deck::Model,
+	old: &Study,
+	new: &Study,
+) -> Result<(), DbErr> {
+	if old == new {
+		return Ok(());
+	}
+	let cards = deck.find_related(FlashCard).all(db).await?;
+	let reviewed: FxHashSet<(Uuid, String)> = ReviewState::find()
+		.filter(review_state::Column::User.eq(user))
+		.filter(review_state::Column::Card.is_in(cards.iter().map(|card| card.uid)))
+		.all(db)
+		.await?
+		.into_iter()
+		.map(|state| (state.card, state.unit))
+		.collect();
+
+	let whole = scheduler::WHOLE_CARD.to_string();
+	for card in &cards {
+		let (before, after) = (old.units(&card.content), new.units(&card.content));
+		let moved = match (before.contains(&whole), after.contains(&whole)) {
+			(true, false) => after.first().map(|to| (&whole, to)),
+			(false, true) => before.first().map(|from| (from, &whole)),
+			_ => None,
+		};
+		let Some((from, to)) = moved else {
+			continue;
+		};
+		if before.contains(to)
+			|| !reviewed.contains(&(card.uid, from.clone()))
+			|| reviewed.contains(&(card.uid, to.clone()))
+		{
+			continue;
+		}
+
+		ReviewState::update_many()
+			.col_expr(review_state::Column::Unit, to.as_str().into())
+			.filter(review_state::Column::User.eq(user))
+			.filter(review_state::Column::Card.eq(card.uid))
+			.filter(review_state::Column::Unit.eq(from.as_str()))
+			.exec(db)
+			.await?;
+		ReviewLog::update_many()
+			.col_expr(review_log::Column::Unit, to.as_str().into())
+			.filter(review_log::Column::User.eq(user))
+			.filter(review_log::Column::Card.eq(card.uid))
+			.filter(review_log::Column::Unit.eq(from.as_str()))
+			.exec(db)
+			.await?;
+	}
+	Ok(())
+}
+
+/// Saves the user's settings for a deck. Cards whose whole-card unit comes or
+/// goes with the directions keep their progress, see [`carry_over`].
+async fn put_deck_settings(
+	State(conn): State<DatabaseConnection>,
+	Extension(user): Extension<user::Model>,
+	Path(uid): Path<Uuid>,
+	Json(body): Json<deck_settings::Model>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let deck = visible_deck(&conn, user.id, uid).await?;
+
+	let mut directions = Vec::new();
+	for direction in body.directions.into_iter().flat_map(|d| d.0) {
+		if direction.from == direction.to {
+			return Err((
+				StatusCode::UNPROCESSABLE_ENTITY,
+				format!("Can't study {} to {}", direction.from, direction.to),
+			));
+		}
+		if scheduler::unit(&direction).len() > scheduler::MAX_UNIT {
+			return Err((
+				StatusCode::UNPROCESSABLE_ENTITY,
+				format!(
+					"Language tags too long: {} to {}",
+					direction.from, direction.to
+				),
+			));
+		}
+		if !directions.contains(&direction) {
+			directions.push(direction);
+		}
+	}
+
+	let old = DeckSettings::find_by_id((user.id, deck.uid))
+		.one(&conn)
+		.await
+		.map_err(internal_error)?
+		.as_ref()
+		.map(Study::from)
+		.unwrap_or_default();
+	let new = Study {
+		directions: directions.clone(),
+		reverse: body.reverse,
+	};
+
+	let txn = conn.begin().await.map_err(internal_error)?;
+	DeckSettings::insert(deck_settings::ActiveModel {
+		user: Set(user.id),
+		deck: Set(deck.uid),
+		new_per_day: Set(body.new_per_day),
+		reviews_per_day: Set(body.reviews_per_day),
+		directions: Set((!directions.is_empty()).then_some(Directions(directions))),
+		reverse: Set(body.reverse),
+	})
+	.on_conflict(
+		OnConflict::columns([deck_settings::Column::User, deck_settings::Column::Deck])
+			.update_columns([
+				deck_settings::Column::NewPerDay,
+				deck_settings::Column::ReviewsPerDay,
+				deck_settings::Column::Directions,
+				deck_settings::Column::Reverse,
+			])
+			.to_owned(),
+	)
+	.exec(&txn)
+	.await
+	.map_err(internal_error)?;
+	carry_over(&txn, user.id, &deck, &old, &new)
+		.await
+		.map_err(internal_error)?;
+	txn.commit().await.map_err(internal_error)?;
+	Ok(StatusCode::NO_CONTENT)
+}
+
+pub fn router() -> Router<AppState> {
+	Router::new()
+		.route("/", post(create))
+		.route("/", get(all))
+		.route("/:id", get(get_one))
+		.route("/:id", delete(delete_session))
+		.route("/:id/next", get(next))
+		.route("/:id/answer", post(answer))
+		.route("/deck/:id/settings", get(get_deck_settings))
+		.route("/deck/:id/settings", put(put_deck_settings))
+		.route_layer(middleware::from_fn(session::auth))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeSet;
+
+	use serde_json::{json, Value};
+
+	use crate::testing::{titled, uid, TestApp, OTHER};
+
+	async fn start(app: &TestApp, deck: Option<uuid::Uuid>) -> (String, Value) {
+		let reply = app
+			.call("POST", "/api/study", Some(json!({ "deck": deck })))
+			.await;
+		assert_eq!(reply.status, 201);
+		let summary = reply.json();
+		(uid(&summary).to_string(), summary)
+	}
+
+	/// Answers the next card of a session, if there is one.
+	async fn answer_next(app: &TestApp, session: &str, grade: &str) -> Option<(Value, Value)> {
+		let next = app
+			.call("GET", &format!("/api/study/{session}/next"), None)
+			.await;
+		if next.status == 204 {
+			return None;
+		}
+		let next = next.json();
+		let reply = app
+			.call(
+				"POST",
+				&format!("/api/study/{session}/answer"),
+				Some(json!({
+					"card": next["entry"]["card"],
+					"unit": next["entry"]["unit"],
+					"grade": grade,
+				})),
+			)
+			.await;
+		assert_eq!(reply.status, 200);
+		Some((next, reply.json()["session"].clone()))
+	}
+
+	#[tokio::test]
+	async fn queue_keeps_daily_limits() {
+		let app = TestApp::new().await;
+		let cards: Vec<Value> = (1..=5).map(|i| titled(&format!("card {i}"))).collect();
+		let deck = app.deck(0, "Deck", "Private", &cards).await;
+		let reply = app
+			.call(
+				"PUT",
+				&format!("/api/study/deck/{deck}/settings"),
+				Some(json!({"new_per_day": 2, "reviews_per_day": 10})),
+			)
+			.await;
+		assert_eq!(reply.status, 204);
+
+		let (session, summary) = start(&app, Some(deck)).await;
+		assert_eq!(
+			(summary["new"].as_u64(), summary["review"].as_u64()),
+			(Some(2), Some(0))
+		);
+
+		let (_, summary) = answer_next(&app, &session, "Good").await.unwrap();
+		assert_eq!(
+			(summary["new"].as_u64(), summary["done"].as_u64()),
+			(Some(1), Some(1))
+		);
+		// A failed card comes back in the same session.
+		let (failed, summary) = answer_next(&app, &session, "Again").await.unwrap();
+		assert_eq!(
+			(summary["new"].as_u64(), summary["learning"].as_u64()),
+			(Some(0), Some(1))
+		);
+		let (again, _) = answer_next(&app, &session, "Good").await.unwrap();
+		assert_eq!(again["entry"]["card"], failed["entry"]["card"]);
+		assert_eq!(again["entry"]["state"], "Learning");
+		assert!(answer_next(&app, &session, "Good").await.is_none());
+
+		// Today's new cards are used up.
+		let (_, summary) = start(&app, Some(deck)).await;
+		assert_eq!(summary["new"].as_u64(), Some(0));
+	}
+
+	#[tokio::test]
+	async fn queue_covers_owned_and_followed_decks() {
+		let app = TestApp::new().await;
+		app.deck(0, "Mine", "Private", &[titled("mine")]).await;
+		let followed = app
+			.deck(OTHER, "Followed", "Public", &[titled("a"), titled("b")])
+			.await;
+		app.deck(OTHER, "Unfollowed", "Public", &[titled("c")])
+			.await;
+		let reply = app
+			.call("POST", &format!("/api/deck/{followed}/follow"), None)
+			.await;
+		assert!(reply.status.is_success());
+
+		let (session, summary) = start(&app, None).await;
+		assert_eq!(summary["new"].as_u64(), Some(3));
+
+		// Cards made private since the session started are skipped.
+		let cards = app
+			.call_as(OTHER, "GET", &format!("/api/deck/{followed}/cards"), None)
+			.await
+			.json();
+		let mut hidden = cards[0].clone();
+		hidden["share"] = json!("Private");
+		let reply = app
+			.call_as(
+				OTHER,
+				"PUT",
+				&format!("/api/flashcard/{}", uid(&hidden)),
+				Some(hidden.clone()),
+			)
+			.await;
+		assert_eq!(reply.status, 204);
+
+		let mut studied = Vec::new();
+		while let Some((next, _)) = answer_next(&app, &session, "Good").await {
+			studied.push(next["card"]["uid"].clone());
+		}
+		assert_eq!(studied.len(), 2);
+		assert!(!studied.contains(&hidden["uid"]));
+	}
+
+	#[tokio::test]
+	async fn sessions_are_private() {
+		let app = TestApp::new().await;
+		let deck = app.deck(0, "Deck", "Public", &[titled("a")]).await;
+		let (session, _) = start(&app, Some(deck)).await;
+		let reply = app
+			.call_as(OTHER, "GET", &format!("/api/study/{session}/next"), None)
+			.await;
+		assert_eq!(reply.status, 404);
+		let reply = app
+			.call_as(OTHER, "POST", "/api/study", Some(json!({ "deck": deck })))
+			.await;
+		assert_eq!(reply.status, 201);
+		let private = app.deck(0, "Private", "Private", &[titled("b")]).await;
+		let reply = app
+			.call_as(
+				OTHER,
+				"POST",
+				"/api/study",
+				Some(json!({ "deck": private })),
+			)
+			.await;
+		assert_eq!(reply.status, 404);
+	}
+
+	#[tokio::test]
+	async fn next_adds_distractors_from_the_deck() {
+		let app = TestApp::new().await;
+		let question = json!([{"type": "MultipleChoice", "content": {
+			"prompt": "Kot?",
+			"options": ["cat"],
+			"correct": [0],
+			"distractors": 2,
+		}}]);
+		let back = |front: &str, back: &str| {
+			json!([{"type": "FrontBack", "content": {
+				"front": {"title": front},
+				"back": {"title": back},
+			}}])
+		};
+		let deck = app
+			.deck(
+				OTHER,
+				"Quiz",
+				"Public",
+				&[question, back("pies", "dog"), back("mysz", "mouse")],
+			)
+			.await;
+
+		let (session, _) = start(&app, Some(deck)).await;
+		let mut seen = 0;
+		while let Some((next, _)) = answer_next(&app, &session, "Good").await {
+			let section = &next["card"]["content"][0];
+			if section["type"] != "MultipleChoice" {
+				continue;
+			}
+			seen += 1;
+			let mut options: Vec<&str> = section["content"]["options"]
+				.as_array()
+				.unwrap()
+				.iter()
+				.map(|o| o.as_str().unwrap())
+				.collect();
+			options.sort_unstable();
+			assert_eq!(options, ["cat", "dog", "mouse"]);
+			assert!(section["content"].get("correct").is_none());
+		}
+		assert_eq!(seen, 1);
+	}
+
+	#[tokio::test]
+	async fn directions_are_studied_apart() {
+		let app = TestApp::new().await;
+		let lang =
+			json!([{"type": "Lang", "content": {"pl": {"title": "kot"}, "en": {"title": "cat"}}}]);
+		let front_back = json!([{"type": "FrontBack", "content": {
+			"front": {"title": "Hund"},
+			"back": {"title": "dog"},
+		}}]);
+		let deck = app
+			.deck(0, "Deck", "Private", &[lang, front_back.clone()])
+			.await;
+		let cards = app
+			.call("GET", &format!("/api/deck/{deck}/cards"), None)
+			.await
+			.json();
+		let kot = cards
+			.as_array()
+			.unwrap()
+			.iter()
+			.find(|card| card["content"] != front_back)
+			.map(uid)
+			.unwrap();
+
+		let settings = format!("/api/study/deck/{deck}/settings");
+		let both_ways = json!({
+			"new_per_day": 20,
+			"reviews_per_day": 100,
+			"directions": [{"from": "pl", "to": "en"}, {"from": "en", "to": "pl"}],
+			"reverse": true,
+		});
+		let reply = app.call("PUT", &settings, Some(both_ways.clone())).await;
+		assert_eq!(reply.status, 204);
+		let saved = app.call("GET", &settings, None).await.json();
+		assert_eq!(saved["directions"], both_ways["directions"]);
+
+		// Each direction is a card of its own.
+		let (session, summary) = start(&app, Some(deck)).await;
+		assert_eq!(summary["new"].as_u64(), Some(4));
+		// Failed units go to the back of the queue, so each comes up once first.
+		let mut units = BTreeSet::new();
+		for _ in 0..4 {
+			let (next, _) = answer_next(&app, &session, "Again").await.unwrap();
+			let entry = &next["entry"];
+			let unit = entry["unit"].as_str().unwrap().to_string();
+			units.insert((entry["card"] == json!(kot), unit));
+		}
+		let expected = [
+			(false, ""),
+			(false, "reverse"),
+			(true, "en>pl"),
+			(true, "pl>en"),
+		];
+		assert_eq!(
+			units,
+			expected.map(|(kot, unit)| (kot, unit.to_string())).into()
+		);
+
+		// Knowing a word one way says nothing of the other.
+		let (session, _) = start(&app, Some(deck)).await;
+		let answer = json!({"card": kot, "unit": "pl>en", "grade": "Easy"});
+		let reply = app
+			.call(
+				"POST",
+				&format!("/api/study/{session}/answer"),
+				Some(answer),
+			)
+			.await;
+		assert_eq!(reply.status, 200);
+		assert_eq!(reply.json()["state"]["state"], "Review");
+		let (_, summary) = start(&app, Some(deck)).await;
+		assert_eq!(summary["learning"].as_u64(), Some(3));
+		assert_eq!(summary["review"].as_u64(), Some(0));
+
+		// Going back to whole cards keeps what the first direction got to.
+		let one_way = json!({"new_per_day": 20, "reviews_per_day": 100});
+		let reply = app.call("PUT", &settings, Some(one_way)).await;
+		assert_eq!(reply.status, 204);
+		let (_, summary) = start(&app, Some(deck)).await;
+		assert_eq!(summary["learning"].as_u64(), Some(1));
+		assert_eq!(summary["new"].as_u64(), Some(0));
+
+		let same = json!({
+			"new_per_day": 20,
+			"reviews_per_day": 100,
+			"directions": [{"from": "pl", "to": "pl"}],
+		});
+		let reply = app.call("PUT", &settings, Some(same)).await;
+		assert_eq!(reply.status, 422);
+	}
+}
@@ -0,0 +1,503 @@
+//! Free Spaced Repetition Scheduler, v4.5.
+//!
+//! See <https://github.com/open-spaced-repetition/fsrs4anki/wiki/The-Algorithm>.
+
+use chrono::{DateTime, Duration, Utc};
+use entity::{
+	custom::fsrs::WEIGHT_COUNT,
+	sea_orm_active_enums::{CardState, Grade},
+	user_settings,
+};
+
+use super::{sm2::MAX_INTERVAL, Memory};
+
+pub const DEFAULT_WEIGHTS: [f64; WEIGHT_COUNT] = [
+	0.4872, 1.4003, 3.7145, 13.8206, 5.1618, 1.2298, 0.8975, 0.031, 1.6474, 0.1367, 1.0461, 2.1072,
+	0.0793, 0.3246, 1.587, 0.2272, 2.8755,
+];
+pub const DEFAULT_RETENTION: f64 = 0.9;
+
+/// Reviews (not counting first reviews and same-day repeats) needed before the
+/// weights can be fitted to a user.
+pub const MIN_REVIEWS: usize = 100;
+
+const DECAY: f64 = -0.5;
+const FACTOR: f64 = 19.0 / 81.0;
+
+/// Bounds every weight is kept within, both when loaded and while fitting.
+const BOUNDS: [(f64, f64); WEIGHT_COUNT] = [
+	(0.1, 100.0),
+	(0.1, 100.0),
+	(0.1, 100.0),
+	(0.1, 100.0),
+	(1.0, 10.0),
+	(0.1, 5.0),
+	(0.1, 5.0),
+	(0.0, 0.75),
+	(0.0, 4.0),
+	(0.0, 0.8),
+	(0.01, 3.0),
+	(0.5, 5.0),
+	(0.01, 0.2),
+	(0.01, 0.9),
+	(0.01, 2.0),
+	(0.0, 1.0),
+	(1.0, 6.0),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameters {
+	pub weights: [f64; WEIGHT_COUNT],
+	pub desired_retention: f64,
+}
+
+impl Default for Parameters {
+	fn default() -> Self {
+		Self {
+			weights: DEFAULT_WEIGHTS,
+			desired_retention: DEFAULT_RETENTION,
+		}
+	}
+}
+
+impl From<&user_settings::Model> for Parameters {
+	fn from(settings: &user_settings::Model) -> Self {
+		let mut weights = DEFAULT_WEIGHTS;
+		if let Some(custom) = settings
+			.fsrs_weights
+			.as_ref()
+			.filter(|w| w.0.len() == WEIGHT_COUNT)
+		{
+			for (i, w) in custom.0.iter().enumerate() {
+				weights[i] = w.clamp(BOUNDS[i].0, BOUNDS[i].1);
+			}
+		}
+
+		Self {
+			weights,
+			desired_retention: settings.desired_retention,
+		}
+	}
+}
+
+fn grade_value(grade: Grade) -> f64 {
+	match grade {
+		Grade::Again => 1.0,
+		Grade::Hard => 2.0,
+		Grade::Good => 3.0,
+		Grade::Easy => 4.0,
+	}
+}
+
+/// Probability of recalling a card `elapsed_days` after its last review.
+pub fn retrievability(elapsed_days: f64, stability: f64) -> f64 {
+	(1.0 + FACTOR * elapsed_days / stability).powf(DECAY)
+}
+
+impl Parameters {
+	/// Days until retrievability drops to the desired retention.
+	pub fn interval(&self, stability: f64) -> f64 {
+		stability / FACTOR * (self.desired_retention.powf(1.0 / DECAY) - 1.0)
+	}
+
+	fn init_stability(&self, grade: Grade) -> f64 {
+		self.weights[grade as usize].max(0.1)
+	}
+
+	fn init_difficulty(&self, grade: Grade) -> f64 {
+		let w = &self.weights;
+		(w[4] - (grade_value(grade) - 3.0) * w[5]).clamp(1.0, 10.0)
+	}
+
+	fn next_difficulty(&self, difficulty: f64, grade: Grade) -> f64 {
+		let w = &self.weights;
+		let difficulty = difficulty - w[6] * (grade_value(grade) - 3.0);
+		// Mean reversion towards the difficulty of a first "Good".
+		(w[7] * self.init_difficulty(Grade::Good) + (1.0 - w[7]) * difficulty).clamp(1.0, 10.0)
+	}
+
+	fn recall_stability(&self, difficulty: f64, stability: f64, r: f64, grade: Grade) -> f64 {
+		let w = &self.weights;
+		let hard_penalty = if grade == Grade::Hard { w[15] } else { 1.0 };
+		let easy_bonus = if grade == Grade::Easy { w[16] } else { 1.0 };
+
+		stability
+			* (w[8].exp()
+				* (11.0 - difficulty)
+				* stability.powf(-w[9])
+				* ((w[10] * (1.0 - r)).exp() - 1.0)
+				* hard_penalty
+				* easy_bonus + 1.0)
+	}
+
+	fn forget_stability(&self, difficulty: f64, stability: f64, r: f64) -> f64 {
+		let w = &self.weights;
+		(w[11]
+			* difficulty.powf(-w[12])
+			* ((stability + 1.0).powf(w[13]) - 1.0)
+			* (w[14] * (1.0 - r)).exp())
+		.min(stability)
+	}
+
+	/// Stability and difficulty after a review given `elapsed_days` after the
+	/// previous one, or after the first review if there is no previous state.
+	pub fn next_state(
+		&self,
+		prev: Option<(f64, f64)>,
+		elapsed_days: f64,
+		grade: Grade,
+	) -> (f64, f64) {
+		let Some((stability, difficulty)) = prev else {
+			return (self.init_stability(grade), self.init_difficulty(grade));
+		};
+
+		let r = retrievability(elapsed_days, stability);
+		let stability = if grade == Grade::Again {
+			self.forget_stability(difficulty, stability, r)
+		} else {
+			self.recall_stability(difficulty, stability, r, grade)
+		};
+
+		(stability.max(0.1), self.next_difficulty(difficulty, grade))
+	}
+}
+
+pub fn schedule(
+	params: &Parameters,
+	memory: Option<&Memory>,
+	grade: Grade,
+	now: DateTime<Utc>,
+) -> Memory {
+	let Some(memory) = memory else {
+		let (stability, difficulty) = params.next_state(None, 0.0, grade);
+		return next(params, None, stability, difficulty, grade, now);
+	};
+
+	// Cards last scheduled by SM-2 have no memory state yet, so derive one from
+	// their interval and ease.
+	let stability = memory
+		.stability
+		.unwrap_or_else(|| f64::from(memory.interval).max(params.init_stability(Grade::Good)));
+	let difficulty = memory
+		.difficulty
+		.unwrap_or_else(|| (11.0 - memory.ease * 2.0).clamp(1.0, 10.0));
+
+	#[allow(clippy::cast_precision_loss)]
+	let elapsed_days = (now - memory.last_review).num_seconds().max(0) as f64 / 86400.0;
+	let (stability, difficulty) =
+		params.next_state(Some((stability, difficulty)), elapsed_days, grade);
+
+	next(params, Some(memory), stability, difficulty, grade, now)
+}
+
+fn next(
+	params: &Parameters,
+	memory: Option<&Memory>,
+	stability: f64,
+	difficulty: f64,
+	grade: Grade,
+	now: DateTime<Utc>,
+) -> Memory {
+	let (state, ease, reps, lapses) = memory
+		.map_or((CardState::New, super::sm2::INITIAL_EASE, 0, 0), |m| {
+			(m.state, m.ease, m.reps, m.lapses)
+		});
+
+	if grade == Grade::Again {
+		let (state, lapses) = match state {
+			CardState::New | CardState::Learning => (CardState::Learning, lapses),
+			CardState::Review => (CardState::Relearning, lapses + 1),
+			CardState::Relearning => (CardState::Relearning, lapses),
+		};
+
+		return Memory {
+			state,
+			ease,
+			interval: 0,
+			reps: 0,
+			lapses,
+			due: now + Duration::minutes(10),
+			last_review: now,
+			stability: Some(stability),
+			difficulty: Some(difficulty),
+		};
+	}
+
+	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+	let interval = (params.interval(stability).round().max(1.0) as u32).min(MAX_INTERVAL);
+
+	Memory {
+		state: CardState::Review,
+		ease,
+		interval,
+		reps: reps + 1,
+		lapses,
+		due: now + Duration::days(i64::from(interval)),
+		last_review: now,
+		stability: Some(stability),
+		difficulty: Some(difficulty),
+	}
+}
+
+/// A card's review history: days since the previous review, and the grade.
+pub type History = Vec<(f64, Grade)>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fit {
+	pub weights: [f64; WEIGHT_COUNT],
+	pub initial_loss: f64,
+	pub loss: f64,
+	pub reviews: usize,
+}
+
+/// Mean log loss of the recall predictions `weights` make for `histories`.
+fn loss(weights: &[f64; WEIGHT_COUNT], histories: &[History]) -> (f64, usize) {
+	let params = Parameters {
+		weights: *weights,
+		..Default::default()
+	};
+
+	let mut total = 0.0;
+	let mut count = 0;
+	for history in histories {
+		let mut state = None;
+		for &(elapsed_days, grade) in history {
+			if let Some((stability, _)) = state {
+				// Same-day repeats say little about long-term memory.
+				if elapsed_days >= 1.0 {
+					let r = retrievability(elapsed_days, stability).clamp(1e-4, 1.0 - 1e-4);
+					total -= if grade == Grade::Again {
+						(1.0 - r).ln()
+					} else {
+						r.ln()
+					};
+					count += 1;
+				}
+			}
+			state = Some(params.next_state(state, elapsed_days, grade));
+		}
+	}
+
+	#[allow(clippy::cast_precision_loss)]
+	let mean = if count == 0 {
+		0.0
+	} else {
+		total / count as f64
+	};
+	(mean, count)
+}
+
+/// Fits the weights to a user's review histories, starting from `initial`.
+///
+/// Runs a fixed number of Adam steps over finite-difference gradients, with every
+/// weight scaled to its bounds. Returns `None` when there is too little history.
+pub fn optimize(histories: &[History], initial: &[f64; WEIGHT_COUNT]) -> Option<Fit> {
+	const STEPS: usize = 200;
+	const LEARNING_RATE: f64 = 0.01;
+	const H: f64 = 1e-4;
+	const BETA1: f64 = 0.9;
+	const BETA2: f64 = 0.999;
+
+	let (initial_loss, reviews) = loss(initial, histories);
+	if reviews < MIN_REVIEWS {
+		return None;
+	}
+
+	let to_weights = |x: &[f64; WEIGHT_COUNT]| {
+		let mut w = [0.0; WEIGHT_COUNT];
+		for i in 0..WEIGHT_COUNT {
+			let (lo, hi) = BOUNDS[i];
+			w[i] = lo + x[i].clamp(0.0, 1.0) * (hi - lo);
+		}
+		w
+	};
+	let mut x = [0.0; WEIGHT_COUNT];
+	for i in 0..WEIGHT_COUNT {
+		let (lo, hi) = BOUNDS[i];
+		x[i] = ((initial[i] - lo) / (hi - lo)).clamp(0.0, 1.0);
+	}
+
+	let mut best = (initial_loss, *initial);
+	let mut m = [0.0; WEIGHT_COUNT];
+	let mut v = [0.0; WEIGHT_COUNT];
+	for step in 1..=STEPS {
+		let mut grad = [0.0; WEIGHT_COUNT];
+		for i in 0..WEIGHT_COUNT {
+			let mut up = x;
+			let mut down = x;
+			up[i] = (up[i] + H).min(1.0);
+			down[i] = (down[i] - H).max(0.0);
+			grad[i] = (loss(&to_weights(&up), histories).0 - loss(&to_weights(&down), histories).0)
+				/ (up[i] - down[i]);
+		}
+
+		#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+		let step = step as i32;
+		for i in 0..WEIGHT_COUNT {
+			m[i] = BETA1 * m[i] + (1.0 - BETA1) * grad[i];
+			v[i] = BETA2 * v[i] + (1.0 - BETA2) * grad[i] * grad[i];
+			let m_hat = m[i] / (1.0 - BETA1.powi(step));
+			let v_hat = v[i] / (1.0 - BETA2.powi(step));
+			x[i] = (x[i] - LEARNING_RATE * m_hat / (v_hat.sqrt() + 1e-8)).clamp(0.0, 1.0);
+		}
+
+		let weights = to_weights(&x);
+		let (current, _) = loss(&weights, histories);
+		if current < best.0 {
+			best = (current, weights);
+		}
+	}
+
+	Some(Fit {
+		weights: best.1,
+		initial_loss,
+		loss: best.0,
+		reviews,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::TimeZone;
+	use entity::{custom::fsrs::FsrsWeights, sea_orm_active_enums::Algorithm};
+
+	use super::*;
+	use crate::scheduler::sm2;
+
+	fn now() -> DateTime<Utc> {
+		Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+	}
+
+	fn close(a: f64, b: f64) -> bool {
+		(a - b).abs() < 1e-9
+	}
+
+	#[test]
+	fn interval_keeps_the_desired_retention() {
+		let params = Parameters::default();
+		assert!(close(retrievability(0.0, 5.0), 1.0));
+		assert!(close(params.interval(5.0), 5.0));
+		assert!(close(
+			retrievability(params.interval(5.0), 5.0),
+			DEFAULT_RETENTION
+		));
+		let strict = Parameters {
+			desired_retention: 0.95,
+			..Default::default()
+		};
+		assert!(strict.interval(5.0) < 5.0);
+		assert!(close(retrievability(strict.interval(5.0), 5.0), 0.95));
+	}
+
+	#[test]
+	fn schedules_first_reviews() {
+		let params = Parameters::default();
+		let good = schedule(&params, None, Grade::Good, now());
+		assert_eq!(good.state, CardState::Review);
+		assert_eq!((good.reps, good.interval), (1, 4));
+		assert_eq!(good.stability, Some(DEFAULT_WEIGHTS[2]));
+		assert_eq!(good.difficulty, Some(DEFAULT_WEIGHTS[4]));
+		assert_eq!(good.due, now() + Duration::days(4));
+
+		let again = schedule(&params, None, Grade::Again, now());
+		assert_eq!(again.state, CardState::Learning);
+		assert_eq!(again.due, now() + Duration::minutes(10));
+		assert_eq!(again.stability, Some(DEFAULT_WEIGHTS[0]));
+		let easy = schedule(&params, None, Grade::Easy, now());
+		assert!(easy.difficulty < good.difficulty);
+		assert_eq!(easy.interval, 14);
+	}
+
+	#[test]
+	fn updates_memory_on_review() {
+		let params = Parameters::default();
+		let first = schedule(&params, None, Grade::Good, now());
+		let later = first.due;
+		let grade = |grade| schedule(&params, Some(&first), grade, later);
+		let (again, hard, good, easy) = (
+			grade(Grade::Again),
+			grade(Grade::Hard),
+			grade(Grade::Good),
+			grade(Grade::Easy),
+		);
+
+		assert!(again.stability < first.stability);
+		assert!(first.stability < hard.stability);
+		assert!(hard.stability < good.stability);
+		assert!(good.stability < easy.stability);
+		assert!(hard.interval < good.interval && good.interval < easy.interval);
+		assert!(again.difficulty > first.difficulty);
+		assert!(easy.difficulty < first.difficulty);
+
+		assert_eq!(again.state, CardState::Relearning);
+		assert_eq!((again.reps, again.lapses), (0, 1));
+		assert_eq!(good.reps, 2);
+	}
+
+	#[test]
+	fn takes_over_sm2_cards() {
+		let params = Parameters::default();
+		let sm2 = sm2::schedule(None, Grade::Good, now());
+		let sm2 = sm2::schedule(Some(&sm2), Grade::Good, now());
+		assert_eq!(sm2.interval, 6);
+		let next = schedule(&params, Some(&sm2), Grade::Good, sm2.due);
+		let (stability, difficulty) = params.next_state(Some((6.0, 6.0)), 6.0, Grade::Good);
+		assert_eq!(next.stability, Some(stability));
+		assert_eq!(next.difficulty, Some(difficulty));
+		assert!(next.interval > 6);
+	}
+
+	#[test]
+	fn clamps_custom_weights() {
+		let mut settings = user_settings::Model {
+			user: 0,
+			algorithm: Algorithm::Fsrs,
+			desired_retention: 0.85,
+			fsrs_weights: Some(FsrsWeights(vec![1000.0; WEIGHT_COUNT])),
+		};
+		let params = Parameters::from(&settings);
+		assert!(close(params.desired_retention, 0.85));
+		for (weight, (_, hi)) in params.weights.iter().zip(BOUNDS) {
+			assert!(close(*weight, hi));
+		}
+		settings.fsrs_weights = Some(FsrsWeights(vec![1.0; 3]));
+		assert_eq!(Parameters::from(&settings).weights, DEFAULT_WEIGHTS);
+	}
+
+	/// Histories of cards reviewed at growing gaps, failed a third of the time:
+	/// far more often than the default weights expect.
+	fn forgetful() -> Vec<History> {
+		(0..40)
+			.map(|card| {
+				let mut history = vec![(0.0, Grade::Good)];
+				for review in 1..6 {
+					let grade = if (card + review) % 3 == 0 {
+						Grade::Again
+					} else {
+						Grade::Good
+					};
+					history.push((f64::from(review * 3), grade));
+				}
+				history
+			})
+			.collect()
+	}
+
+	#[test]
+	fn fits_weights() {
+		let histories = forgetful();
+		assert_eq!(optimize(&histories[..10], &DEFAULT_WEIGHTS), None);
+
+		let fit = optimize(&histories, &DEFAULT_WEIGHTS).unwrap();
+		assert_eq!(fit.reviews, 200);
+		assert!(close(
+			fit.initial_loss,
+			loss(&DEFAULT_WEIGHTS, &histories).0
+		));
+		assert!(fit.loss < fit.initial_loss);
+		assert!(close(fit.loss, loss(&fit.weights, &histories).0));
+		for (weight, (lo, hi)) in fit.weights.iter().zip(BOUNDS) {
+			assert!((lo..=hi).contains(weight));
+		}
+	}
+}
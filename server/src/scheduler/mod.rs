@@ -0,0 +1,161 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+use entity::{
+	custom::{
+		flash_card::{FlashCardContent, FlashCardSection},
+		study::Direction,
+	},
+	deck_settings, review_state,
+	sea_orm_active_enums::{Algorithm, CardState, Grade},
+	user_settings,
+};
+
+use crate::cloze;
+
+pub mod fsrs;
+pub mod sm2;
+
+/// Unit of a card that is reviewed as a whole.
+pub const WHOLE_CARD: &str = "";
+
+/// Unit of the `FrontBack` sections of a card studied back to front. Front to
+/// back, they are the whole card.
+pub const REVERSE: &str = "reverse";
+
+/// Longest unit, as long as the column holding it.
+pub const MAX_UNIT: usize = 80;
+
+/// Unit of a card studied in `direction`, like `pl>en`.
+pub fn unit(direction: &Direction) -> String {
+	format!("{}>{}", direction.from, direction.to)
+}
+
+/// Whether a card has a `Lang` section to study in `direction`: one with a
+/// side in each language, or in ones they fall back to.
+fn studied_in(content: &FlashCardContent, direction: &Direction) -> bool {
+	content.0.iter().any(|section| match section {
+		FlashCardSection::Lang(items) => {
+			match (direction.from.resolve(items), direction.to.resolve(items)) {
+				(Some((from, _)), Some((to, _))) => from != to,
+				_ => false,
+			}
+		}
+		_ => false,
+	})
+}
+
+fn has_front_back(content: &FlashCardContent) -> bool {
+	content
+		.0
+		.iter()
+		.any(|section| matches!(section, FlashCardSection::FrontBack { .. }))
+}
+
+/// How the cards of a deck are studied, from a user's settings for it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Study {
+	pub directions: Vec<Direction>,
+	pub reverse: bool,
+}
+
+impl From<&deck_settings::Model> for Study {
+	fn from(settings: &deck_settings::Model) -> Self {
+		Self {
+			directions: settings
+				.directions
+				.as_ref()
+				.map(|d| d.0.clone())
+				.unwrap_or_default(),
+			reverse: settings.reverse,
+		}
+	}
+}
+
+impl Study {
+	/// The parts of a card that are reviewed and scheduled separately: `c<n>`
+	/// for each cloze number, each direction its `Lang` sections can be
+	/// studied in and, if reversed, its `FrontBack` sections both ways. Cards
+	/// without any are reviewed as a whole.
+	pub fn units(&self, content: &FlashCardContent) -> Vec<String> {
+		let numbers: BTreeSet<u32> = content
+			.0
+			.iter()
+			.filter_map(|section| match section {
+				FlashCardSection::Cloze(text) => cloze::parse(text).ok(),
+				_ => None,
+			})
+			.flat_map(|parts| cloze::numbers(&parts))
+			.collect();
+
+		let mut units: Vec<String> = numbers.into_iter().map(|n| format!("c{n}")).collect();
+		for direction in &self.directions {
+			if studied_in(content, direction) {
+				units.push(unit(direction));
+			}
+		}
+		if self.reverse && has_front_back(content) {
+			units.extend([WHOLE_CARD.to_string(), REVERSE.to_string()]);
+		}
+
+		if units.is_empty() {
+			units.push(WHOLE_CARD.to_string());
+		}
+		units
+	}
+}
+
+/// What the scheduler knows about a user's memory of a single card.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Memory {
+	pub state: CardState,
+	pub ease: f64,
+	pub interval: u32,
+	pub reps: u32,
+	pub lapses: u32,
+	pub due: DateTime<Utc>,
+	pub last_review: DateTime<Utc>,
+	pub stability: Option<f64>,
+	pub difficulty: Option<f64>,
+}
+
+impl From<&review_state::Model> for Memory {
+	fn from(model: &review_state::Model) -> Self {
+		Self {
+			state: model.state,
+			ease: model.ease,
+			interval: model.interval,
+			reps: model.reps,
+			lapses: model.lapses,
+			due: model.due,
+			last_review: model.last_review,
+			stability: model.stability,
+			difficulty: model.difficulty,
+		}
+	}
+}
+
+/// The scheduling algorithm picked in a user's settings.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Scheduler {
+	Sm2,
+	Fsrs(fsrs::Parameters),
+}
+
+impl Scheduler {
+	pub fn new(settings: Option<&user_settings::Model>) -> Self {
+		match settings {
+			Some(settings) if settings.algorithm == Algorithm::Fsrs => {
+				Self::Fsrs(fsrs::Parameters::from(settings))
+			}
+			_ => Self::Sm2,
+		}
+	}
+
+	pub fn schedule(&self, memory: Option<&Memory>, grade: Grade, now: DateTime<Utc>) -> Memory {
+		match self {
+			Self::Sm2 => sm2::schedule(memory, grade, now),
+			Self::Fsrs(params) => fsrs::schedule(params, memory, grade, now),
+		}
+	}
+}
@@ -0,0 +1,137 @@
+//! `SuperMemo` 2, with a short relearning step for failed cards so they come back
+//! in the same sitting instead of the next day.
+
+use chrono::{DateTime, Duration, Utc};
+use entity::sea_orm_active_enums::{CardState, Grade};
+
+use super::Memory;
+
+pub const INITIAL_EASE: f64 = 2.5;
+pub const MIN_EASE: f64 = 1.3;
+/// About a hundred years, keeps due dates representable.
+pub const MAX_INTERVAL: u32 = 36500;
+
+fn quality(grade: Grade) -> f64 {
+	match grade {
+		Grade::Again => 1.0,
+		Grade::Hard => 3.0,
+		Grade::Good => 4.0,
+		Grade::Easy => 5.0,
+	}
+}
+
+pub fn schedule(memory: Option<&Memory>, grade: Grade, now: DateTime<Utc>) -> Memory {
+	let (state, ease, interval, reps, lapses) = memory
+		.map_or((CardState::New, INITIAL_EASE, 0, 0, 0), |m| {
+			(m.state, m.ease, m.interval, m.reps, m.lapses)
+		});
+
+	if grade == Grade::Again {
+		// Failed items start over without touching the E-Factor.
+		let (state, lapses) = match state {
+			CardState::New | CardState::Learning => (CardState::Learning, lapses),
+			CardState::Review => (CardState::Relearning, lapses + 1),
+			CardState::Relearning => (CardState::Relearning, lapses),
+		};
+
+		return Memory {
+			state,
+			ease,
+			interval: 0,
+			reps: 0,
+			lapses,
+			due: now + Duration::minutes(10),
+			last_review: now,
+			stability: None,
+			difficulty: None,
+		};
+	}
+
+	let q = quality(grade);
+	let ease = (ease + (0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02))).max(MIN_EASE);
+	let reps = reps + 1;
+	let interval = match reps {
+		1 => 1,
+		2 => 6,
+		#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+		_ => (f64::from(interval.max(1)) * ease).round() as u32,
+	}
+	.min(MAX_INTERVAL);
+
+	Memory {
+		state: CardState::Review,
+		ease,
+		interval,
+		reps,
+		lapses,
+		due: now + Duration::days(i64::from(interval)),
+		last_review: now,
+		stability: None,
+		difficulty: None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::TimeZone;
+
+	use super::*;
+
+	fn now() -> DateTime<Utc> {
+		Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+	}
+
+	fn review(grades: &[Grade]) -> Memory {
+		let mut memory = None;
+		for &grade in grades {
+			memory = Some(schedule(memory.as_ref(), grade, now()));
+		}
+		memory.unwrap()
+	}
+
+	#[test]
+	fn grows_intervals() {
+		let first = review(&[Grade::Good]);
+		assert_eq!(first.state, CardState::Review);
+		assert_eq!((first.reps, first.interval), (1, 1));
+		assert!((first.ease - INITIAL_EASE).abs() < 1e-9);
+		assert_eq!(first.due, now() + Duration::days(1));
+
+		assert_eq!(review(&[Grade::Good, Grade::Good]).interval, 6);
+		assert_eq!(
+			review(&[Grade::Good, Grade::Good, Grade::Good]).interval,
+			15
+		);
+		let easy = review(&[Grade::Easy, Grade::Easy, Grade::Easy]);
+		assert!((easy.ease - 2.8).abs() < 1e-9);
+		assert_eq!(easy.interval, 17);
+	}
+
+	#[test]
+	fn adjusts_ease() {
+		assert!((review(&[Grade::Hard]).ease - 2.36).abs() < 1e-9);
+		assert!((review(&[Grade::Easy]).ease - 2.6).abs() < 1e-9);
+		assert!((review(&[Grade::Hard; 20]).ease - MIN_EASE).abs() < 1e-9);
+		assert_eq!(review(&[Grade::Easy; 20]).interval, MAX_INTERVAL);
+	}
+
+	#[test]
+	fn relearns_failed_cards() {
+		let learning = review(&[Grade::Again]);
+		assert_eq!(learning.state, CardState::Learning);
+		assert_eq!(learning.due, now() + Duration::minutes(10));
+
+		let lapsed = review(&[Grade::Hard, Grade::Good, Grade::Again]);
+		assert_eq!(lapsed.state, CardState::Relearning);
+		assert_eq!((lapsed.reps, lapsed.interval, lapsed.lapses), (0, 0, 1));
+		assert!((lapsed.ease - 2.36).abs() < 1e-9);
+		assert_eq!(review(&[Grade::Good, Grade::Again, Grade::Again]).lapses, 1);
+
+		let relearned = review(&[Grade::Good, Grade::Again, Grade::Good]);
+		assert_eq!(relearned.state, CardState::Review);
+		assert_eq!(
+			(relearned.reps, relearned.interval, relearned.lapses),
+			(1, 1, 1)
+		);
+	}
+}
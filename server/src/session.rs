@@ -5,9 +5,14 @@ use entity::user;
 
 pub const CURRENT_USER: &str = "current_user";
 
+/// Header naming the user requests are made as when users are mocked; user 0
+/// without it.
+#[cfg(any(test, feature = "mock-user"))]
+pub const MOCK_USER: &str = "x-mock-user";
+
 pub type Session = axum_session::Session<SessionNullPool>;
 
-#[cfg(not(feature = "mock-user"))]
+#[cfg(not(any(test, feature = "mock-user")))]
 pub(crate) async fn auth(
 	session: Session,
 	mut req: Request,
@@ -21,14 +26,19 @@ pub(crate) async fn auth(
 	}
 }
 
-#[cfg(feature = "mock-user")]
+#[cfg(any(test, feature = "mock-user"))]
 pub(crate) async fn auth(
 	_session: Session,
 	mut req: Request,
 	next: Next,
 ) -> Result<Response, StatusCode> {
+	let id = req
+		.headers()
+		.get(MOCK_USER)
+		.and_then(|id| id.to_str().ok()?.parse().ok())
+		.unwrap_or(0);
 	req.extensions_mut().insert(user::Model {
-		id: 0,
+		id,
 		sub: "".to_string(),
 		provider: "".to_string(),
 		display: None,
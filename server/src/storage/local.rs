@@ -0,0 +1,62 @@
+use std::{
+	io::{self, ErrorKind},
+	path::PathBuf,
+};
+
+use async_trait::async_trait;
+use tokio::fs;
+use uuid::Uuid;
+
+use super::{invalid, valid, Storage};
+
+/// Stores contents as files in a directory, spread over subdirectories named
+/// after the first two characters of their hash.
+pub struct Local {
+	root: PathBuf,
+}
+
+impl Local {
+	pub fn new(root: impl Into<PathBuf>) -> Self {
+		Self { root: root.into() }
+	}
+
+	fn path(&self, hash: &str) -> io::Result<PathBuf> {
+		if !valid(hash) {
+			return Err(invalid(hash));
+		}
+		Ok(self.root.join(&hash[..2]).join(hash))
+	}
+}
+
+#[async_trait]
+impl Storage for Local {
+	async fn put(&self, hash: &str, data: &[u8]) -> io::Result<()> {
+		let path = self.path(hash)?;
+		let dir = path.parent().unwrap_or(&self.root);
+		fs::create_dir_all(dir).await?;
+
+		// Written next to where it goes and renamed, so a file is never seen
+		// half written.
+		let temp = dir.join(format!(".{hash}.{}", Uuid::new_v4()));
+		if let Err(err) = fs::write(&temp, data).await {
+			let _ = fs::remove_file(&temp).await;
+			return Err(err);
+		}
+		fs::rename(&temp, &path).await
+	}
+
+	async fn get(&self, hash: &str) -> io::Result<Option<Vec<u8>>> {
+		match fs::read(self.path(hash)?).await {
+			Ok(data) => Ok(Some(data)),
+			Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+			Err(err) => Err(err),
+		}
+	}
+
+	async fn delete(&self, hash: &str) -> io::Result<()> {
+		match fs::remove_file(self.path(hash)?).await {
+			Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+			_ => Ok(()),
+		}
+	}
+}
@@ -0,0 +1,47 @@
+//! Where the contents of uploaded media are kept.
+//!
+//! Contents are stored under the hex SHA-256 of their bytes, so a file that is
+//! uploaded several times, by one user or many, is only stored once. Which
+//! media use them is up to the database.
+
+use std::io;
+
+use async_trait::async_trait;
+
+mod local;
+mod s3;
+
+pub use local::Local;
+pub use s3::{S3, URL_EXPIRY};
+
+#[async_trait]
+pub trait Storage: Send + Sync {
+	/// Stores `data` under `hash`, replacing anything already there.
+	async fn put(&self, hash: &str, data: &[u8]) -> io::Result<()>;
+
+	/// The contents stored under `hash`, if there are any.
+	async fn get(&self, hash: &str) -> io::Result<Option<Vec<u8>>>;
+
+	/// Removes the contents stored under `hash`, if there are any.
+	async fn delete(&self, hash: &str) -> io::Result<()>;
+
+	/// A URL the contents stored under `hash` can be downloaded from for a
+	/// while, as `mime`. Backends without one have downloads go through the
+	/// server.
+	async fn url(&self, _hash: &str, _mime: &str) -> io::Result<Option<String>> {
+		Ok(None)
+	}
+}
+
+/// Whether `hash` is a hex SHA-256, and so safe to use as a file or object
+/// name.
+fn valid(hash: &str) -> bool {
+	hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn invalid(hash: &str) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidInput,
+		format!("Invalid content hash `{hash}`"),
+	)
+}
@@ -0,0 +1,108 @@
+use std::{collections::HashMap, io};
+
+use async_trait::async_trait;
+use s3::{creds::Credentials, error::S3Error, Bucket, Region};
+
+use super::{invalid, valid, Storage};
+use crate::config::S3Config;
+
+/// How long download URLs are valid for, in seconds.
+pub const URL_EXPIRY: u32 = 60 * 60;
+
+/// Stores contents as objects in an S3-compatible bucket, named after their
+/// hash. Downloads go to the bucket through pre-signed URLs.
+pub struct S3 {
+	bucket: Box<Bucket>,
+}
+
+impl S3 {
+	pub fn new(config: &S3Config) -> Result<Self, S3Error> {
+		let region = match &config.endpoint {
+			Some(endpoint) => Region::Custom {
+				region: config.region.clone(),
+				endpoint: endpoint.clone(),
+			},
+			None => config.region.parse()?,
+		};
+		// Without keys, credentials come from the environment or instance
+		// metadata.
+		let credentials = Credentials::new(
+			config.access_key.as_deref(),
+			config.secret_key.as_deref(),
+			None,
+			None,
+			None,
+		)?;
+
+		let mut bucket = Bucket::new(&config.bucket, region, credentials)?;
+		if config.path_style {
+			bucket = bucket.with_path_style();
+		}
+		Ok(Self { bucket })
+	}
+
+	fn key(hash: &str) -> io::Result<&str> {
+		if valid(hash) {
+			Ok(hash)
+		} else {
+			Err(invalid(hash))
+		}
+	}
+}
+
+fn failed(status: u16, body: &[u8]) -> io::Error {
+	io::Error::other(format!(
+		"S3 responded with {status}: {}",
+		String::from_utf8_lossy(body)
+	))
+}
+
+#[async_trait]
+impl Storage for S3 {
+	async fn put(&self, hash: &str, data: &[u8]) -> io::Result<()> {
+		let response = self
+			.bucket
+			.put_object(Self::key(hash)?, data)
+			.await
+			.map_err(io::Error::other)?;
+		match response.status_code() {
+			200..=299 => Ok(()),
+			status => Err(failed(status, response.as_slice())),
+		}
+	}
+
+	async fn get(&self, hash: &str) -> io::Result<Option<Vec<u8>>> {
+		let response = self
+			.bucket
+			.get_object(Self::key(hash)?)
+			.await
+			.map_err(io::Error::other)?;
+		match response.status_code() {
+			200..=299 => Ok(Some(response.to_vec())),
+			404 => Ok(None),
+			status => Err(failed(status, response.as_slice())),
+		}
+	}
+
+	async fn delete(&self, hash: &str) -> io::Result<()> {
+		let response = self
+			.bucket
+			.delete_object(Self::key(hash)?)
+			.await
+			.map_err(io::Error::other)?;
+		match response.status_code() {
+			200..=299 | 404 => Ok(()),
+			status => Err(failed(status, response.as_slice())),
+		}
+	}
+
+	async fn url(&self, hash: &str, mime: &str) -> io::Result<Option<String>> {
+		// Objects are stored without a type, so the download is given one.
+		let queries = HashMap::from([("response-content-type".to_string(), mime.to_string())]);
+		self.bucket
+			.presign_get(Self::key(hash)?, URL_EXPIRY, Some(queries))
+			.await
+			.map(Some)
+			.map_err(io::Error::other)
+	}
+}
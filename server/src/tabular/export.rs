@@ -0,0 +1,174 @@
+use csv::WriterBuilder;
+use entity::custom::flash_card::{FlashCardContent, FlashCardItem, FlashCardSection};
+
+use super::{Column, Format};
+use crate::{cloze, quiz};
+
+/// Name of the column appended to every export, saying how each card was
+/// fitted into the chosen columns.
+pub const NOTES: &str = "notes";
+
+/// Separator between the values of a column that several parts of a card went
+/// to, and between notes.
+const JOINER: &str = "; ";
+
+/// Cells of a row, in column order, followed by the notes cell.
+pub type Row = Vec<String>;
+
+fn text(item: &FlashCardItem, notes: &mut Vec<String>) -> String {
+	match item {
+		FlashCardItem::Title(text) | FlashCardItem::Example(text) | FlashCardItem::Image(text) => {
+			text.clone()
+		}
+		FlashCardItem::Pronunciation { ipa, audio_url } => {
+			if audio_url.is_some() {
+				notes.push("audio dropped".to_string());
+			}
+			ipa.clone()
+		}
+		FlashCardItem::Answer { text, alternatives } => {
+			if !alternatives.is_empty() {
+				notes.push("alternative answers dropped".to_string());
+			}
+			text.clone()
+		}
+		FlashCardItem::Markdown { source, .. } | FlashCardItem::Math { source, .. } => {
+			source.clone()
+		}
+	}
+}
+
+fn kind(item: &FlashCardItem) -> Column {
+	match item {
+		FlashCardItem::Title(_) | FlashCardItem::Answer { .. } => Column::Title,
+		FlashCardItem::Example(_) | FlashCardItem::Markdown { .. } | FlashCardItem::Math { .. } => {
+			Column::Example
+		}
+		FlashCardItem::Image(_) => Column::Image,
+		FlashCardItem::Pronunciation { .. } => Column::Ipa,
+	}
+}
+
+/// Flattens a card into a row.
+///
+/// Items go to the column of their kind, the n-th item of a kind to the n-th
+/// such column. Whatever is left over is joined into the last matching column,
+/// or dropped when there is none; either way the notes cell says so.
+pub fn row(content: &FlashCardContent, columns: &[Column]) -> Row {
+	let mut cells: Vec<Vec<String>> = vec![Vec::new(); columns.len()];
+	let mut notes = Vec::new();
+	let mut seen: Vec<usize> = vec![0; columns.len()];
+
+	let mut place = |column: &Column, value: String, notes: &mut Vec<String>| {
+		let matching: Vec<usize> = columns
+			.iter()
+			.enumerate()
+			.filter(|(_, c)| *c == column)
+			.map(|(i, _)| i)
+			.collect();
+		let Some(&last) = matching.last() else {
+			notes.push(format!("{column} dropped"));
+			return;
+		};
+		let i = matching
+			.iter()
+			.copied()
+			.find(|&i| seen[i] == 0)
+			.unwrap_or(last);
+		seen[i] += 1;
+		if !value.is_empty() {
+			cells[i].push(value);
+		}
+	};
+
+	let mut separators = 0;
+	let mut front_backs = 0;
+	let mut langs = 0;
+	for section in &content.0 {
+		match section {
+			FlashCardSection::Separator => separators += 1,
+			FlashCardSection::Item(item) => {
+				let value = text(item, &mut notes);
+				place(&kind(item), value, &mut notes);
+			}
+			FlashCardSection::FrontBack { front, back } => {
+				front_backs += 1;
+				let value = text(front, &mut notes);
+				place(&Column::Front, value, &mut notes);
+				let value = text(back, &mut notes);
+				place(&Column::Back, value, &mut notes);
+			}
+			FlashCardSection::Lang(items) => {
+				langs += 1;
+				let mut items: Vec<_> = items.iter().collect();
+				items.sort_by_key(|(lang, _)| Column::Lang((*lang).clone()).to_string());
+				for (lang, item) in items {
+					let value = text(item, &mut notes);
+					place(&Column::Lang(lang.clone()), value, &mut notes);
+				}
+			}
+			FlashCardSection::Cloze(text) => {
+				notes.push("cloze gaps filled in".to_string());
+				place(&Column::Front, cloze::plain(text), &mut notes);
+			}
+			FlashCardSection::MultipleChoice(question) => {
+				notes.push("wrong options dropped".to_string());
+				place(&Column::Front, question.prompt.clone(), &mut notes);
+				let answer = quiz::right_options(question)
+					.cloned()
+					.collect::<Vec<_>>()
+					.join(JOINER);
+				place(&Column::Back, answer, &mut notes);
+			}
+		}
+	}
+
+	if separators > 0 {
+		notes.push("separators dropped".to_string());
+	}
+	if front_backs > 1 {
+		notes.push(format!("{front_backs} front/back sections joined"));
+	}
+	if langs > 1 {
+		notes.push(format!("{langs} language sections joined"));
+	}
+	for (i, column) in columns.iter().enumerate() {
+		if cells[i].len() > 1 && !matches!(column, Column::Front | Column::Back | Column::Lang(_)) {
+			notes.push(format!("{} {column} values joined", cells[i].len()));
+		}
+	}
+	let mut unique = Vec::new();
+	for note in notes {
+		if !unique.contains(&note) {
+			unique.push(note);
+		}
+	}
+
+	cells
+		.into_iter()
+		.map(|values| values.join(JOINER))
+		.chain([unique.join(JOINER)])
+		.collect()
+}
+
+/// Column names, for the header row.
+pub fn header(columns: &[Column]) -> Row {
+	columns
+		.iter()
+		.map(ToString::to_string)
+		.chain([NOTES.to_string()])
+		.collect()
+}
+
+/// Encodes rows as CSV or TSV.
+pub fn write(format: Format, rows: &[Row]) -> Result<Vec<u8>, csv::Error> {
+	let mut writer = WriterBuilder::new()
+		.delimiter(format.delimiter())
+		.from_writer(Vec::new());
+	for row in rows {
+		writer.write_record(row)?;
+	}
+	writer
+		.into_inner()
+		.map_err(|err| csv::Error::from(err.into_error()))
+}
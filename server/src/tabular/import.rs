@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use csv::{ReaderBuilder, StringRecord};
+use entity::custom::flash_card::{FlashCardContent, FlashCardItem, FlashCardSection};
+use serde::Serialize;
+
+use super::{Column, Error, Format};
+use crate::ipa;
+
+/// A row that could not be turned into a card.
+#[derive(Debug, Serialize)]
+pub struct Failure {
+	pub row: u64,
+	pub error: String,
+}
+
+#[derive(Debug)]
+pub struct Imported {
+	pub cards: Vec<FlashCardContent>,
+	pub failures: Vec<Failure>,
+}
+
+fn is_image_url(url: &str) -> bool {
+	url.starts_with("https://") || url.starts_with("http://") || url.starts_with("data:image/")
+}
+
+/// Maps the cells of a row onto card sections, in column order.
+///
+/// Front and back columns share one `FrontBack` section and language columns
+/// share one `Lang` section, placed where the first of their columns is; a
+/// slot is kept for them until the whole row is read.
+fn map_row(record: &StringRecord, columns: &[Column]) -> Result<FlashCardContent, String> {
+	let mut sections = Vec::new();
+	let mut front_back = None;
+	let mut front = None;
+	let mut back = None;
+	let mut lang_at = None;
+	let mut langs = HashMap::new();
+
+	for (i, column) in columns.iter().enumerate() {
+		let cell = record.get(i).map(str::trim).unwrap_or_default();
+		if cell.is_empty() {
+			continue;
+		}
+		let cell = cell.to_string();
+
+		match column {
+			Column::Front | Column::Back => {
+				if front_back.is_none() {
+					front_back = Some(sections.len());
+					sections.push(FlashCardSection::Separator);
+				}
+				if *column == Column::Front {
+					front = Some(cell);
+				} else {
+					back = Some(cell);
+				}
+				continue;
+			}
+			Column::Lang(lang) => {
+				if lang_at.is_none() {
+					lang_at = Some(sections.len());
+					sections.push(FlashCardSection::Separator);
+				}
+				langs.insert(lang.clone(), FlashCardItem::Title(cell));
+				continue;
+			}
+			Column::Skip => continue,
+			_ => {}
+		}
+
+		sections.push(FlashCardSection::Item(match column {
+			Column::Title => FlashCardItem::Title(cell),
+			Column::Example => FlashCardItem::Example(cell),
+			Column::Image if is_image_url(&cell) => FlashCardItem::Image(cell),
+			Column::Image => return Err(format!("Invalid image URL in column {}", i + 1)),
+			Column::Ipa => FlashCardItem::Pronunciation {
+				ipa: ipa::transcribe(&cell).map_err(|e| format!("{e} in column {}", i + 1))?,
+				audio_url: None,
+			},
+			Column::Front | Column::Back | Column::Lang(_) | Column::Skip => unreachable!(),
+		}));
+	}
+
+	if let Some(at) = front_back {
+		sections[at] = match (front, back) {
+			(Some(front), Some(back)) => FlashCardSection::FrontBack {
+				front: FlashCardItem::Title(front),
+				back: FlashCardItem::Title(back),
+			},
+			(Some(side), None) | (None, Some(side)) => {
+				FlashCardSection::Item(FlashCardItem::Title(side))
+			}
+			(None, None) => unreachable!(),
+		};
+	}
+	if let Some(at) = lang_at {
+		sections[at] = FlashCardSection::Lang(langs);
+	}
+
+	if sections.is_empty() {
+		return Err("Row has no content".to_string());
+	}
+	Ok(FlashCardContent(sections))
+}
+
+/// Reads each row of a CSV or TSV file as a card.
+pub fn read(
+	data: &[u8],
+	format: Format,
+	columns: &[Column],
+	header: bool,
+) -> Result<Imported, Error> {
+	let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+	let mut reader = ReaderBuilder::new()
+		.delimiter(format.delimiter())
+		.has_headers(header)
+		.flexible(true)
+		.from_reader(data);
+
+	let mut cards = Vec::new();
+	let mut failures = Vec::new();
+	for record in reader.records() {
+		let record = match record {
+			Ok(record) => record,
+			Err(err) => match err.position() {
+				Some(pos) => {
+					failures.push(Failure {
+						row: pos.line(),
+						error: err.to_string(),
+					});
+					continue;
+				}
+				None => return Err(err.into()),
+			},
+		};
+
+		// Spreadsheets tend to export trailing blank rows.
+		if record.iter().all(|cell| cell.trim().is_empty()) {
+			continue;
+		}
+
+		match map_row(&record, columns) {
+			Ok(card) => cards.push(card),
+			Err(error) => failures.push(Failure {
+				row: record.position().map_or(0, csv::Position::line),
+				error,
+			}),
+		}
+	}
+
+	Ok(Imported { cards, failures })
+}
@@ -0,0 +1,159 @@
+//! CSV and TSV support, with user-chosen columns.
+
+use std::{
+	fmt::{Display, Formatter},
+	str::FromStr,
+};
+
+use axum::http::StatusCode;
+use entity::custom::lang::Language;
+use serde::Deserialize;
+
+pub mod export;
+pub mod import;
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+	Csv,
+	Tsv,
+}
+
+impl Format {
+	pub fn delimiter(self) -> u8 {
+		match self {
+			Self::Csv => b',',
+			Self::Tsv => b'\t',
+		}
+	}
+
+	pub fn mime_type(self) -> &'static str {
+		match self {
+			Self::Csv => "text/csv; charset=utf-8",
+			Self::Tsv => "text/tab-separated-values; charset=utf-8",
+		}
+	}
+
+	pub fn extension(self) -> &'static str {
+		match self {
+			Self::Csv => "csv",
+			Self::Tsv => "tsv",
+		}
+	}
+}
+
+/// What a column holds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Column {
+	/// Front of a `FrontBack` section.
+	Front,
+	/// Back of a `FrontBack` section.
+	Back,
+	Title,
+	Example,
+	/// Image URL.
+	Image,
+	/// IPA transcription of a `Pronunciation` item.
+	Ipa,
+	/// Term in a language of the card's `Lang` section.
+	Lang(Language),
+	Skip,
+}
+
+impl FromStr for Column {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let name = s.trim().to_lowercase();
+		Ok(match name.as_str() {
+			"front" => Self::Front,
+			"back" => Self::Back,
+			"title" => Self::Title,
+			"example" => Self::Example,
+			"image" => Self::Image,
+			"ipa" => Self::Ipa,
+			"" | "skip" => Self::Skip,
+			_ => name
+				.parse::<Language>()
+				.map(Self::Lang)
+				.map_err(|_| Error::Column(s.to_string()))?,
+		})
+	}
+}
+
+impl Display for Column {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Self::Front => "front",
+			Self::Back => "back",
+			Self::Title => "title",
+			Self::Example => "example",
+			Self::Image => "image",
+			Self::Ipa => "ipa",
+			Self::Lang(lang) => lang.as_str(),
+			Self::Skip => "skip",
+		})
+	}
+}
+
+/// Parses a comma-separated column list, e.g. `pl,en,skip,example`.
+pub fn columns(list: &str) -> Result<Vec<Column>, Error> {
+	let columns = list
+		.split(',')
+		.map(Column::from_str)
+		.collect::<Result<Vec<_>, _>>()?;
+
+	if columns.iter().all(|c| *c == Column::Skip) {
+		return Err(Error::NoColumns);
+	}
+	// Several titles, examples or images make several items, but a card only has
+	// one front, back, transcription and term per language.
+	for (i, column) in columns.iter().enumerate() {
+		let single = matches!(
+			column,
+			Column::Front | Column::Back | Column::Ipa | Column::Lang(_)
+		);
+		if single && columns[..i].contains(column) {
+			return Err(Error::Duplicate(
+				list.split(',').nth(i).unwrap_or_default().to_string(),
+			));
+		}
+	}
+	Ok(columns)
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Csv(csv::Error),
+	Column(String),
+	Duplicate(String),
+	NoColumns,
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Csv(err) => write!(f, "Invalid file: {err}"),
+			Self::Column(name) => write!(f, "Unknown column kind \"{name}\""),
+			Self::Duplicate(name) => write!(f, "Column kind \"{name}\" is used twice"),
+			Self::NoColumns => f.write_str("No columns selected"),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<csv::Error> for Error {
+	fn from(err: csv::Error) -> Self {
+		Self::Csv(err)
+	}
+}
+
+impl From<Error> for (StatusCode, String) {
+	fn from(err: Error) -> Self {
+		match err {
+			Error::Csv(_) => (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()),
+			_ => (StatusCode::BAD_REQUEST, err.to_string()),
+		}
+	}
+}
@@ -0,0 +1,205 @@
+//! The API running against a throwaway SQLite database, for route tests.
+//!
+//! Requests are made as [`USER`] unless another user is asked for; the mocked
+//! `session::auth` takes them from the [`MOCK_USER`] header.
+
+use axum::{
+	body::{Body, Bytes},
+	http::{header::CONTENT_TYPE, HeaderMap, Request, StatusCode},
+	Router,
+};
+use http_body_util::BodyExt;
+use sea_orm::{ConnectionTrait, Database, DatabaseConnection};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::{app::app, config::AppConfig, session::MOCK_USER, storage::Local};
+
+/// The user requests are made as.
+pub const USER: u32 = 0;
+/// Someone else, whose decks and cards [`USER`] may see.
+pub const OTHER: u32 = 1;
+
+/// Tables of the first migration, which only runs on MySQL.
+const INIT: &str = "
+	CREATE TABLE seaql_migrations (version varchar NOT NULL PRIMARY KEY, applied_at bigint NOT NULL);
+	INSERT INTO seaql_migrations VALUES ('m20220101_000001_init', 0);
+	CREATE TABLE user (id integer NOT NULL PRIMARY KEY AUTOINCREMENT, sub varchar(255) NOT NULL, provider varchar NOT NULL, display varchar NULL, email varchar NULL);
+	CREATE TABLE flash_card (uid blob(16) NOT NULL PRIMARY KEY, creator integer NOT NULL, share text NOT NULL, content json_text NOT NULL, FOREIGN KEY (creator) REFERENCES user (id));
+	CREATE TABLE deck (uid blob(16) NOT NULL PRIMARY KEY, name text NOT NULL, creator integer NOT NULL, kind text NOT NULL, share text NOT NULL, FOREIGN KEY (creator) REFERENCES user (id));
+	CREATE TABLE deck_cards (deck blob(16) NOT NULL, card blob(16) NOT NULL, PRIMARY KEY (deck, card), FOREIGN KEY (deck) REFERENCES deck (uid), FOREIGN KEY (card) REFERENCES flash_card (uid));
+	CREATE TABLE followed_decks (user integer NOT NULL, deck blob(16) NOT NULL, PRIMARY KEY (user, deck), FOREIGN KEY (user) REFERENCES user (id), FOREIGN KEY (deck) REFERENCES deck (uid));
+";
+
+pub struct TestApp {
+	router: Router,
+	/// For setting up more than is worth doing through the API.
+	pub db: DatabaseConnection,
+	/// Holds the database and stored media.
+	dir: TempDir,
+}
+
+/// A response, read in full.
+pub struct Reply {
+	pub status: StatusCode,
+	pub headers: HeaderMap,
+	pub body: Bytes,
+}
+
+impl Reply {
+	pub fn header(&self, name: &str) -> Option<&str> {
+		self.headers.get(name).map(|value| value.to_str().unwrap())
+	}
+
+	pub fn json(&self) -> Value {
+		serde_json::from_slice(&self.body).unwrap_or_else(|_| {
+			panic!(
+				"{} is not JSON: {}",
+				self.status,
+				String::from_utf8_lossy(&self.body)
+			)
+		})
+	}
+}
+
+impl TestApp {
+	pub async fn new() -> Self {
+		Self::with_config(|_| {}).await
+	}
+
+	pub async fn with_config(configure: impl FnOnce(&mut AppConfig)) -> Self {
+		let dir = TempDir::new().unwrap();
+		let db_url = format!(
+			"sqlite://{}?mode=rwc",
+			dir.path().join("db.sqlite").display()
+		);
+		let db = Database::connect(&db_url).await.unwrap();
+		db.execute_unprepared(INIT).await.unwrap();
+
+		let mut config = AppConfig {
+			db_url,
+			media_dir: dir.path().join("media").display().to_string(),
+			..Default::default()
+		};
+		configure(&mut config);
+		let router = app(config).await;
+		db.execute_unprepared(&format!(
+			"INSERT INTO user (id, sub, provider) VALUES ({USER}, 'user', 'test'), ({OTHER}, 'other', 'test')"
+		))
+		.await
+		.unwrap();
+
+		Self { router, db, dir }
+	}
+
+	/// Where the app keeps uploaded media.
+	pub fn storage(&self) -> Local {
+		Local::new(self.dir.path().join("media"))
+	}
+
+	pub async fn send(&self, user: u32, request: Request<Body>) -> Reply {
+		let (mut parts, body) = request.into_parts();
+		parts.headers.insert(MOCK_USER, user.into());
+		let response = self
+			.router
+			.clone()
+			.oneshot(Request::from_parts(parts, body))
+			.await
+			.unwrap();
+		let (parts, body) = response.into_parts();
+		Reply {
+			status: parts.status,
+			headers: parts.headers,
+			body: body.collect().await.unwrap().to_bytes(),
+		}
+	}
+
+	pub async fn call_as(&self, user: u32, method: &str, uri: &str, body: Option<Value>) -> Reply {
+		let request = Request::builder().method(method).uri(uri);
+		let request = match body {
+			Some(body) => request
+				.header(CONTENT_TYPE, "application/json")
+				.body(Body::from(body.to_string())),
+			None => request.body(Body::empty()),
+		};
+		self.send(user, request.unwrap()).await
+	}
+
+	pub async fn call(&self, method: &str, uri: &str, body: Option<Value>) -> Reply {
+		self.call_as(USER, method, uri, body).await
+	}
+
+	/// Posts a file as [`USER`].
+	pub async fn upload(&self, uri: &str, data: impl Into<Body>) -> Reply {
+		let request = Request::post(uri).body(data.into()).unwrap();
+		self.send(USER, request).await
+	}
+
+	/// Creates a `Language` deck of `user`'s holding new cards with `contents`.
+	pub async fn deck(&self, user: u32, name: &str, share: &str, contents: &[Value]) -> Uuid {
+		let reply = self
+			.call_as(
+				user,
+				"POST",
+				"/api/deck",
+				Some(json!({
+					"uid": Uuid::nil(),
+					"name": name,
+					"creator": user,
+					"kind": "Language",
+					"share": share,
+				})),
+			)
+			.await;
+		assert_eq!(reply.status, StatusCode::CREATED, "{:?}", reply.body);
+		let deck = uid(&reply.json());
+
+		let mut cards = Vec::new();
+		for content in contents {
+			cards.push(self.card(user, share, content.clone()).await);
+		}
+		if !cards.is_empty() {
+			let reply = self
+				.call_as(
+					user,
+					"PUT",
+					&format!("/api/deck/{deck}/cards"),
+					Some(json!(cards)),
+				)
+				.await;
+			assert!(reply.status.is_success(), "{:?}", reply.body);
+		}
+		deck
+	}
+
+	/// Creates a card of `user`'s.
+	pub async fn card(&self, user: u32, share: &str, content: Value) -> Uuid {
+		let reply = self
+			.call_as(
+				user,
+				"POST",
+				"/api/flashcard",
+				Some(json!({
+					"uid": Uuid::nil(),
+					"creator": user,
+					"share": share,
+					"content": content,
+				})),
+			)
+			.await;
+		assert_eq!(reply.status, StatusCode::CREATED, "{:?}", reply.body);
+		uid(&reply.json())
+	}
+}
+
+/// The `uid` of a JSON object.
+pub fn uid(value: &Value) -> Uuid {
+	value["uid"].as_str().unwrap().parse().unwrap()
+}
+
+/// Content of a card titled `title`.
+pub fn titled(title: &str) -> Value {
+	json!([{"type": "Item", "content": {"title": title}}])
+}
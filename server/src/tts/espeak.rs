@@ -0,0 +1,81 @@
+use std::{collections::HashMap, io, process::Stdio, time::Duration};
+
+use async_trait::async_trait;
+use entity::custom::lang::Language;
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use super::Tts;
+
+/// Longest a text may take to speak.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// [eSpeak NG](https://github.com/espeak-ng/espeak-ng), run as a program.
+pub struct Espeak {
+	program: String,
+	/// Installed voices, by the language they speak.
+	voices: HashMap<Language, String>,
+}
+
+impl Espeak {
+	/// Runs `program` to find out which voices it has.
+	pub fn new(program: &str) -> io::Result<Self> {
+		let output = std::process::Command::new(program)
+			.arg("--voices")
+			.stdin(Stdio::null())
+			.output()?;
+		if !output.status.success() {
+			return Err(io::Error::other(format!(
+				"{program} --voices failed: {}",
+				String::from_utf8_lossy(&output.stderr).trim()
+			)));
+		}
+
+		// Columns are priority, language, age and gender, name, file and other
+		// languages.
+		let voices = String::from_utf8_lossy(&output.stdout)
+			.lines()
+			.skip(1)
+			.filter_map(|line| {
+				let voice = line.split_whitespace().nth(1)?;
+				Some((voice.parse().ok()?, voice.to_string()))
+			})
+			.collect();
+		Ok(Self {
+			program: program.to_string(),
+			voices,
+		})
+	}
+}
+
+#[async_trait]
+impl Tts for Espeak {
+	fn voice(&self, lang: &Language) -> Option<String> {
+		lang.resolve(&self.voices).map(|(_, voice)| voice.clone())
+	}
+
+	async fn speak(&self, text: &str, voice: &str) -> io::Result<Vec<u8>> {
+		// The text goes in on its own, so that nothing in it reads as an option.
+		let mut child = Command::new(&self.program)
+			.args(["--stdout", "-v", voice])
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped())
+			.kill_on_drop(true)
+			.spawn()?;
+		if let Some(mut stdin) = child.stdin.take() {
+			stdin.write_all(text.as_bytes()).await?;
+		}
+
+		let output = tokio::time::timeout(TIMEOUT, child.wait_with_output())
+			.await
+			.map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Speaking took too long"))??;
+		if !output.status.success() || output.stdout.is_empty() {
+			return Err(io::Error::other(format!(
+				"{} failed: {}",
+				self.program,
+				String::from_utf8_lossy(&output.stderr).trim()
+			)));
+		}
+		Ok(output.stdout)
+	}
+}
@@ -0,0 +1,300 @@
+//! Text to speech, for pronunciations nobody recorded.
+//!
+//! The terms of a card's `Lang` sections are spoken in their language, and the
+//! audio is stored as media of the card's creator. What a voice said is kept
+//! by language, voice and text, so that each is only spoken once.
+
+use std::{
+	collections::{HashMap, HashSet},
+	fmt::{Display, Formatter},
+	io,
+	sync::Arc,
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use entity::{
+	custom::{
+		flash_card::{FlashCardContent, FlashCardItem, FlashCardSection},
+		lang::Language,
+	},
+	flash_card,
+	prelude::*,
+	speech,
+};
+use sea_orm::{
+	sea_query::OnConflict, ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+	QueryFilter, TransactionTrait,
+};
+use uuid::Uuid;
+
+use crate::{media, storage::Storage};
+
+mod espeak;
+
+pub use espeak::Espeak;
+
+/// Longest text spoken, in characters. Speech is for terms, not passages.
+pub const MAX_TEXT: usize = 200;
+
+#[async_trait]
+pub trait Tts: Send + Sync {
+	/// The voice to speak `lang` with, if there is one.
+	fn voice(&self, lang: &Language) -> Option<String>;
+
+	/// Audio of `voice` saying `text`.
+	async fn speak(&self, text: &str, voice: &str) -> io::Result<Vec<u8>>;
+}
+
+/// An engine without voices, for when there is nothing to speak with.
+pub struct Silent;
+
+#[async_trait]
+impl Tts for Silent {
+	fn voice(&self, _lang: &Language) -> Option<String> {
+		None
+	}
+
+	async fn speak(&self, _text: &str, _voice: &str) -> io::Result<Vec<u8>> {
+		Err(io::Error::new(io::ErrorKind::Unsupported, "No voices"))
+	}
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Db(DbErr),
+	Media(media::Error),
+	Speech(io::Error),
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Db(err) => err.fmt(f),
+			Self::Media(err) => err.fmt(f),
+			Self::Speech(err) => write!(f, "Speaking failed: {err}"),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<DbErr> for Error {
+	fn from(err: DbErr) -> Self {
+		Self::Db(err)
+	}
+}
+
+impl From<media::Error> for Error {
+	fn from(err: media::Error) -> Self {
+		Self::Media(err)
+	}
+}
+
+impl From<io::Error> for Error {
+	fn from(err: io::Error) -> Self {
+		Self::Speech(err)
+	}
+}
+
+/// The text of each language of a card's `Lang` sections, its first title or
+/// answer.
+fn terms(content: &FlashCardContent) -> HashMap<Language, String> {
+	let mut terms = HashMap::new();
+	for section in &content.0 {
+		let FlashCardSection::Lang(items) = section else {
+			continue;
+		};
+		for (lang, item) in items {
+			let text = match item {
+				FlashCardItem::Title(text) | FlashCardItem::Answer { text, .. } => text.trim(),
+				_ => continue,
+			};
+			if !text.is_empty() && text.chars().count() <= MAX_TEXT {
+				terms
+					.entry(lang.clone())
+					.or_insert_with(|| text.to_string());
+			}
+		}
+	}
+	terms
+}
+
+/// Media of `voice` saying `text` in `lang`, for `owner`. Spoken only if it
+/// wasn't before.
+async fn recording(
+	db: &DatabaseConnection,
+	storage: &dyn Storage,
+	tts: &dyn Tts,
+	owner: u32,
+	lang: &Language,
+	text: &str,
+) -> Result<Option<Uuid>, Error> {
+	let Some(voice) = tts.voice(lang) else {
+		return Ok(None);
+	};
+	let key = media::hash(format!("{lang}\n{voice}\n{text}").as_bytes());
+
+	// Garbage collection may have removed what was said since.
+	let cached = match Speech::find_by_id(&key).one(db).await? {
+		Some(speech) => storage.get(&speech.hash).await?,
+		None => None,
+	};
+	let (media, _) = match cached {
+		Some(data) => media::store(db, storage, owner, &data).await?,
+		None => {
+			let data = tts.speak(text, &voice).await?;
+			let stored = media::store(db, storage, owner, &data).await?;
+			Speech::insert(speech::ActiveModel {
+				key: Set(key),
+				language: Set(lang.to_string()),
+				voice: Set(voice),
+				text: Set(text.to_string()),
+				hash: Set(stored.0.hash.clone()),
+				created: Set(Utc::now()),
+			})
+			.on_conflict(
+				OnConflict::column(speech::Column::Key)
+					.update_columns([speech::Column::Hash, speech::Column::Created])
+					.to_owned(),
+			)
+			.exec(db)
+			.await?;
+			stored
+		}
+	};
+	Ok(Some(media.uid))
+}
+
+/// Gives the terms of a card that have no recording one, spoken by `tts`, and
+/// returns whether there were any.
+///
+/// Pronunciations in a `Lang` section without audio get that of the term in
+/// their language. So do pronunciations outside of them, on cards with terms
+/// in a single language. Terms without any pronunciation get one, in a `Lang`
+/// section after the last.
+pub async fn record(
+	db: &DatabaseConnection,
+	storage: &dyn Storage,
+	tts: &dyn Tts,
+	owner: u32,
+	content: &mut FlashCardContent,
+) -> Result<bool, Error> {
+	let terms = terms(content);
+	let single = (terms.len() == 1).then(|| terms.keys().next()).flatten();
+
+	let mut pronounced = HashSet::new();
+	let mut unrecorded = HashSet::new();
+	for section in &content.0 {
+		let items: Vec<(&Language, &FlashCardItem)> = match section {
+			FlashCardSection::Lang(items) => items.iter().collect(),
+			FlashCardSection::Item(item) => single.map(|lang| (lang, item)).into_iter().collect(),
+			_ => continue,
+		};
+		for (lang, item) in items {
+			if let FlashCardItem::Pronunciation { audio_url, .. } = item {
+				pronounced.insert(lang.clone());
+				if audio_url.is_none() {
+					unrecorded.insert(lang.clone());
+				}
+			}
+		}
+	}
+
+	let mut urls = HashMap::new();
+	for (lang, text) in &terms {
+		if unrecorded.contains(lang) || !pronounced.contains(lang) {
+			if let Some(uid) = recording(db, storage, tts, owner, lang, text).await? {
+				urls.insert(lang.clone(), media::path(uid));
+			}
+		}
+	}
+	if urls.is_empty() {
+		return Ok(false);
+	}
+
+	let mut last_lang = None;
+	for (i, section) in content.0.iter_mut().enumerate() {
+		let items: Vec<(&Language, &mut FlashCardItem)> = match section {
+			FlashCardSection::Lang(items) => {
+				last_lang = Some(i);
+				items.iter_mut().collect()
+			}
+			FlashCardSection::Item(item) => single.map(|lang| (lang, item)).into_iter().collect(),
+			_ => continue,
+		};
+		for (lang, item) in items {
+			if let FlashCardItem::Pronunciation { audio_url, .. } = item {
+				if audio_url.is_none() {
+					*audio_url = urls.get(lang).cloned();
+				}
+			}
+		}
+	}
+
+	let added: HashMap<Language, FlashCardItem> = urls
+		.into_iter()
+		.filter(|(lang, _)| !pronounced.contains(lang))
+		.map(|(lang, url)| {
+			let item = FlashCardItem::Pronunciation {
+				ipa: String::new(),
+				audio_url: Some(url),
+			};
+			(lang, item)
+		})
+		.collect();
+	if let Some(last) = last_lang.filter(|_| !added.is_empty()) {
+		content.0.insert(last + 1, FlashCardSection::Lang(added));
+	}
+	Ok(true)
+}
+
+/// Records the terms of a card and saves it, unless it was changed since.
+async fn record_card(
+	db: &DatabaseConnection,
+	storage: &dyn Storage,
+	tts: &dyn Tts,
+	uid: Uuid,
+) -> Result<(), Error> {
+	let Some(mut card) = FlashCard::find_by_id(uid).one(db).await? else {
+		return Ok(());
+	};
+	if !record(db, storage, tts, card.creator, &mut card.content).await? {
+		return Ok(());
+	}
+
+	let txn = db.begin().await?;
+	let saved = FlashCard::update_many()
+		.set(flash_card::ActiveModel {
+			content: Set(card.content.clone()),
+			updated: Set(Utc::now()),
+			..Default::default()
+		})
+		.filter(flash_card::Column::Uid.eq(uid))
+		.filter(flash_card::Column::Updated.eq(card.updated))
+		.exec(&txn)
+		.await?;
+	if saved.rows_affected == 0 {
+		// Edited in the meantime; the edit records its own terms.
+		return Ok(());
+	}
+	media::link(&txn, card.creator, [&card]).await?;
+	txn.commit().await?;
+	Ok(())
+}
+
+/// Records the terms of `cards` in the background.
+pub fn record_later(
+	db: DatabaseConnection,
+	storage: Arc<dyn Storage>,
+	tts: Arc<dyn Tts>,
+	cards: Vec<Uuid>,
+) {
+	tokio::spawn(async move {
+		for uid in cards {
+			if let Err(err) = record_card(&db, storage.as_ref(), tts.as_ref(), uid).await {
+				tracing::warn!("Recording the terms of card {uid} failed: {err}");
+			}
+		}
+	});
+}